@@ -0,0 +1,43 @@
+//! Deterministic output mode for reproducible snapshots in tests and CI.
+
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Environment variable enabling deterministic mode for any agpod entry point.
+pub const DETERMINISTIC_ENV: &str = "AGPOD_DETERMINISTIC";
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Enable deterministic mode for this process, e.g. from a `--deterministic` flag.
+pub fn set_deterministic(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether output should avoid anything that varies between runs: relative
+/// times render as absolute dates and machine-specific absolute paths are
+/// left as given.
+///
+/// Enabled by [`set_deterministic`] or by setting `AGPOD_DETERMINISTIC` to
+/// anything other than an empty string, `0` or `false`.
+pub fn is_deterministic() -> bool {
+    ENABLED.load(Ordering::Relaxed) || env_enabled(env::var(DETERMINISTIC_ENV).ok().as_deref())
+}
+
+fn env_enabled(value: Option<&str>) -> bool {
+    value.is_some_and(|value| !matches!(value.trim(), "" | "0" | "false"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_env_enabled() {
+        assert!(!env_enabled(None));
+        assert!(!env_enabled(Some("")));
+        assert!(!env_enabled(Some("0")));
+        assert!(!env_enabled(Some("false")));
+        assert!(env_enabled(Some("1")));
+        assert!(env_enabled(Some("true")));
+    }
+}
@@ -0,0 +1,214 @@
+//! Errors shared by the agpod crates, grouped by domain so embedders can
+//! tell a bad config from a git or I/O failure without matching on strings.
+
+use crate::include::IncludeError;
+use crate::paths::ExpandPathError;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+use thiserror::Error;
+
+/// A boxed error from a library agpod-core does not depend on.
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Any agpod error.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+
+    #[error(transparent)]
+    Git(#[from] GitError),
+
+    #[error(transparent)]
+    Io(#[from] IoError),
+
+    #[error(transparent)]
+    Review(#[from] ReviewError),
+
+    /// Errors not yet given a domain.
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+/// The domain of an [`Error`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    Config,
+    Git,
+    Io,
+    Review,
+    Other,
+}
+
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Config(_) => ErrorKind::Config,
+            Self::Git(_) => ErrorKind::Git,
+            Self::Io(_) => ErrorKind::Io,
+            Self::Review(_) => ErrorKind::Review,
+            Self::Other(_) => ErrorKind::Other,
+        }
+    }
+}
+
+/// The domain of the first agpod error in `error`'s source chain, whether
+/// it was wrapped in [`Error`] or not.
+pub fn error_kind(error: &(dyn std::error::Error + 'static)) -> Option<ErrorKind> {
+    std::iter::successors(Some(error), |error| error.source()).find_map(|error| {
+        if let Some(error) = error.downcast_ref::<Error>() {
+            Some(error.kind())
+        } else if error.is::<ConfigError>()
+            || error.is::<IncludeError>()
+            || error.is::<ExpandPathError>()
+        {
+            Some(ErrorKind::Config)
+        } else if error.is::<GitError>() {
+            Some(ErrorKind::Git)
+        } else if error.is::<IoError>() {
+            Some(ErrorKind::Io)
+        } else if error.is::<ReviewError>() {
+            Some(ErrorKind::Review)
+        } else {
+            None
+        }
+    })
+}
+
+/// Config files, config values and command-line patterns.
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error(transparent)]
+    Include(Box<IncludeError>),
+
+    #[error(transparent)]
+    ExpandPath(#[from] ExpandPathError),
+
+    #[error("invalid config {}: {source}", path.display())]
+    Invalid {
+        path: PathBuf,
+        source: Box<toml::de::Error>,
+    },
+
+    #[error("invalid exclude pattern: {source}")]
+    InvalidExcludePattern { source: BoxError },
+}
+
+impl From<IncludeError> for ConfigError {
+    fn from(error: IncludeError) -> Self {
+        Self::Include(Box::new(error))
+    }
+}
+
+/// The repository the diff is taken from.
+#[derive(Debug, Error)]
+pub enum GitError {
+    #[error("unknown ref `{reference}`{}", did_you_mean(suggestions))]
+    UnknownRef {
+        reference: String,
+        suggestions: Vec<String>,
+    },
+
+    #[error("git: {source}")]
+    Repository { source: BoxError },
+}
+
+/// Files agpod writes or reads besides REVIEW.md.
+#[derive(Debug, Error)]
+pub enum IoError {
+    #[error("failed to create output directory {}: {source}", path.display())]
+    CreateOutputDir { path: PathBuf, source: io::Error },
+
+    #[error("failed to write diff chunk {}: {source}", path.display())]
+    WriteChunk { path: PathBuf, source: io::Error },
+
+    #[error("failed to read diff chunk {}: {source}", path.display())]
+    ReadChunk { path: PathBuf, source: io::Error },
+
+    #[error("failed to write bundle {}: {source}", path.display())]
+    WriteBundle { path: PathBuf, source: io::Error },
+
+    #[error("failed to read exclude file {}: {source}", path.display())]
+    ReadExcludeFile { path: PathBuf, source: io::Error },
+
+    #[error("failed to lock {}: {source}", path.display())]
+    Lock { path: PathBuf, source: io::Error },
+
+    #[error(
+        "another agpod process holds the lock on {} (waited {}ms)",
+        path.display(),
+        timeout.as_millis()
+    )]
+    Locked { path: PathBuf, timeout: Duration },
+}
+
+/// Diff input, REVIEW.md and publishing reviews.
+#[derive(Debug, Error)]
+pub enum ReviewError {
+    #[error("input is not a diff: {reason}")]
+    NotADiff { reason: String },
+
+    #[error("{path} is not in the diff")]
+    FileNotInDiff { path: String },
+
+    #[error("cannot reconstruct {path}: {reason}")]
+    Reconstruct { path: String, reason: String },
+
+    #[error("failed to read existing review file {}: {source}", path.display())]
+    ReadReview { path: PathBuf, source: io::Error },
+
+    #[error("failed to write review file {}: {source}", path.display())]
+    WriteReview { path: PathBuf, source: io::Error },
+
+    #[error("no file in {} has review comments", path.display())]
+    NoReviewComments { path: PathBuf },
+
+    #[error("gh not found; install the GitHub CLI (https://cli.github.com) or use --dry-run to print the payload")]
+    GhNotFound,
+
+    #[error("gh is not authenticated; run `gh auth login` and try again")]
+    GhNotAuthenticated,
+
+    #[error("failed to run gh: {source}")]
+    RunGh { source: io::Error },
+
+    #[error("gh api failed: {stderr}")]
+    GhFailed { stderr: String },
+}
+
+fn did_you_mean(suggestions: &[String]) -> String {
+    if suggestions.is_empty() {
+        String::new()
+    } else {
+        format!("; did you mean: {}?", suggestions.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_error_kind_looks_through_context_and_wrappers() {
+        let wrapped: anyhow::Error = Error::from(GitError::UnknownRef {
+            reference: "mian".to_string(),
+            suggestions: vec!["main".to_string()],
+        })
+        .into();
+        assert_eq!(error_kind(wrapped.as_ref()), Some(ErrorKind::Git));
+        assert_eq!(
+            wrapped.to_string(),
+            "unknown ref `mian`; did you mean: main?"
+        );
+
+        let bare = Err::<(), _>(ReviewError::GhNotAuthenticated)
+            .context("publishing review")
+            .unwrap_err();
+        assert_eq!(error_kind(bare.as_ref()), Some(ErrorKind::Review));
+
+        let other = anyhow::anyhow!("something else");
+        assert_eq!(error_kind(other.as_ref()), None);
+    }
+}
@@ -0,0 +1,63 @@
+//! Human-friendly formatting helpers shared across agpod crates.
+
+use crate::locale::{Locale, TimeUnit};
+
+/// Format an elapsed duration in seconds as a relative time such as `3 days ago`.
+///
+/// Negative durations (timestamps in the future, e.g. from clock skew) render as
+/// `just now`.
+pub fn format_relative_time(elapsed_secs: i64) -> String {
+    format_relative_time_in(elapsed_secs, Locale::En)
+}
+
+/// [`format_relative_time`] in the given locale, e.g. `3 天前` for zh-CN.
+pub fn format_relative_time_in(elapsed_secs: i64, locale: Locale) -> String {
+    const MINUTE: i64 = 60;
+    const HOUR: i64 = 60 * MINUTE;
+    const DAY: i64 = 24 * HOUR;
+    const WEEK: i64 = 7 * DAY;
+    const MONTH: i64 = 30 * DAY;
+    const YEAR: i64 = 365 * DAY;
+
+    let (count, unit) = match elapsed_secs {
+        s if s < MINUTE => return locale.just_now().to_string(),
+        s if s < HOUR => (s / MINUTE, TimeUnit::Minute),
+        s if s < DAY => (s / HOUR, TimeUnit::Hour),
+        s if s < WEEK => (s / DAY, TimeUnit::Day),
+        s if s < MONTH => (s / WEEK, TimeUnit::Week),
+        s if s < YEAR => (s / MONTH, TimeUnit::Month),
+        s => (s / YEAR, TimeUnit::Year),
+    };
+
+    locale.time_ago(count, unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_relative_time() {
+        assert_eq!(format_relative_time(-5), "just now");
+        assert_eq!(format_relative_time(59), "just now");
+        assert_eq!(format_relative_time(60), "1 minute ago");
+        assert_eq!(format_relative_time(2 * 3600 + 5), "2 hours ago");
+        assert_eq!(format_relative_time(3 * 86400), "3 days ago");
+        assert_eq!(format_relative_time(14 * 86400), "2 weeks ago");
+        assert_eq!(format_relative_time(95 * 86400), "3 months ago");
+        assert_eq!(format_relative_time(800 * 86400), "2 years ago");
+    }
+
+    #[test]
+    fn test_format_relative_time_zh_cn() {
+        let zh = |secs| format_relative_time_in(secs, Locale::ZhCn);
+        assert_eq!(zh(-5), "刚刚");
+        assert_eq!(zh(59), "刚刚");
+        assert_eq!(zh(60), "1 分钟前");
+        assert_eq!(zh(2 * 3600 + 5), "2 小时前");
+        assert_eq!(zh(3 * 86400), "3 天前");
+        assert_eq!(zh(14 * 86400), "2 周前");
+        assert_eq!(zh(95 * 86400), "3 个月前");
+        assert_eq!(zh(800 * 86400), "2 年前");
+    }
+}
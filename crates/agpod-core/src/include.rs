@@ -0,0 +1,203 @@
+//! `include = [...]` in config files, for sharing a base config.
+//!
+//! Included files are read depth-first and merged key by key before the
+//! including file's own keys, so the including file always wins. Paths are
+//! relative to the including file and may use `~` and `$VAR`.
+
+use crate::paths::{expand_path, ExpandPathError};
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// How deeply includes may nest before loading gives up.
+pub const MAX_INCLUDE_DEPTH: usize = 8;
+
+/// Why a config file and its includes could not be read.
+#[derive(Debug, Error)]
+pub enum IncludeError {
+    #[error("failed to read {}: {source}", path.display())]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("failed to parse {}: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+
+    #[error("`include` in {} must be an array of paths", path.display())]
+    InvalidInclude { path: PathBuf },
+
+    #[error("invalid include path in {}: {source}", path.display())]
+    Expand {
+        path: PathBuf,
+        source: ExpandPathError,
+    },
+
+    #[error("include cycle: {}", chain.iter().map(|path| path.display().to_string()).collect::<Vec<_>>().join(" -> "))]
+    Cycle { chain: Vec<PathBuf> },
+
+    #[error("includes nested deeper than {MAX_INCLUDE_DEPTH} levels at {}", path.display())]
+    TooDeep { path: PathBuf },
+}
+
+/// Read `path` as a TOML table with its includes merged in.
+pub fn read_with_includes(path: &Path) -> Result<toml::Table, IncludeError> {
+    read_table(path, &mut Vec::new())
+}
+
+fn read_table(path: &Path, stack: &mut Vec<PathBuf>) -> Result<toml::Table, IncludeError> {
+    // canonical paths, so `a.toml` and `./a.toml` are recognised as the same file
+    let canonical = fs::canonicalize(path).map_err(|source| IncludeError::Read {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    if stack.contains(&canonical) {
+        let mut chain = stack.clone();
+        chain.push(canonical);
+        return Err(IncludeError::Cycle { chain });
+    }
+    if stack.len() > MAX_INCLUDE_DEPTH {
+        return Err(IncludeError::TooDeep {
+            path: path.to_path_buf(),
+        });
+    }
+
+    let content = fs::read_to_string(&canonical).map_err(|source| IncludeError::Read {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let mut own: toml::Table = toml::from_str(&content).map_err(|source| IncludeError::Parse {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let includes = match own.remove("include") {
+        None => Vec::new(),
+        Some(toml::Value::Array(items)) => items
+            .into_iter()
+            .map(|item| match item {
+                toml::Value::String(include) => Ok(include),
+                _ => Err(IncludeError::InvalidInclude {
+                    path: path.to_path_buf(),
+                }),
+            })
+            .collect::<Result<_, _>>()?,
+        Some(_) => {
+            return Err(IncludeError::InvalidInclude {
+                path: path.to_path_buf(),
+            })
+        }
+    };
+
+    let base_dir = canonical.parent().unwrap_or(Path::new("")).to_path_buf();
+    stack.push(canonical);
+    let mut merged = toml::Table::new();
+    for include in includes {
+        let expanded = expand_path(&include).map_err(|source| IncludeError::Expand {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let included = read_table(&base_dir.join(expanded), stack)?;
+        merge_tables(&mut merged, included);
+    }
+    stack.pop();
+
+    merge_tables(&mut merged, own);
+    Ok(merged)
+}
+
+/// Merge `overlay` into `base`; tables merge recursively, anything else
+/// (including arrays) is replaced.
+fn merge_tables(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base_table)), toml::Value::Table(overlay_table)) => {
+                merge_tables(base_table, overlay_table)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, name: &str, content: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_includes_merge_before_own_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        write(
+            dir.path(),
+            "shared/base.toml",
+            "locale = \"zh-CN\"\n[diff]\noutput_dir = \"base\"\nmax_consecutive_empty_lines = 1\n",
+        );
+        write(
+            dir.path(),
+            "shared/extra.toml",
+            "[diff]\nmax_consecutive_empty_lines = 3\n",
+        );
+        let config = write(
+            dir.path(),
+            "pkg/.agpod.toml",
+            "include = [\"../shared/base.toml\", \"../shared/extra.toml\"]\n[diff]\noutput_dir = \"pkg\"\n",
+        );
+
+        let table = read_with_includes(&config).unwrap();
+        assert!(table.get("include").is_none());
+        assert_eq!(table["locale"].as_str(), Some("zh-CN"));
+        assert_eq!(table["diff"]["output_dir"].as_str(), Some("pkg"));
+        assert_eq!(
+            table["diff"]["max_consecutive_empty_lines"].as_integer(),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn test_include_cycle_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "a.toml", "include = [\"b.toml\"]\n");
+        write(dir.path(), "b.toml", "include = [\"./a.toml\"]\n");
+
+        let error = read_with_includes(&dir.path().join("a.toml")).unwrap_err();
+        assert!(matches!(&error, IncludeError::Cycle { chain } if chain.len() == 3));
+        assert!(error.to_string().contains("a.toml -> "));
+    }
+
+    #[test]
+    fn test_include_depth_is_limited() {
+        let dir = tempfile::tempdir().unwrap();
+        for level in 0..=MAX_INCLUDE_DEPTH + 1 {
+            write(
+                dir.path(),
+                &format!("{level}.toml"),
+                &format!("include = [\"{}.toml\"]\n", level + 1),
+            );
+        }
+        write(dir.path(), &format!("{}.toml", MAX_INCLUDE_DEPTH + 2), "");
+
+        let error = read_with_includes(&dir.path().join("0.toml")).unwrap_err();
+        assert!(matches!(error, IncludeError::TooDeep { .. }));
+
+        assert!(read_with_includes(&dir.path().join("2.toml")).is_ok());
+    }
+
+    #[test]
+    fn test_include_must_be_an_array() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = write(dir.path(), "a.toml", "include = \"base.toml\"\n");
+
+        let error = read_with_includes(&config).unwrap_err();
+        assert!(matches!(error, IncludeError::InvalidInclude { .. }));
+    }
+}
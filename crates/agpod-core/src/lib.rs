@@ -4,15 +4,41 @@
 //! - [diff] - Git diff minimization settings
 //! - [case] - Case server settings
 //! - [mcp] - MCP server settings
+//! - [vcs_path] - VCS path info settings
+//!
+//! The top-level `locale` selects the language of human-facing strings, and
+//! `include` lists config files to merge in first.
+
+mod deterministic;
+mod error;
+mod humanize;
+mod include;
+mod locale;
+mod paths;
+mod progress;
+mod timings;
+
+pub use deterministic::{is_deterministic, set_deterministic, DETERMINISTIC_ENV};
+pub use error::{
+    error_kind, BoxError, ConfigError, Error, ErrorKind, GitError, IoError, ReviewError,
+};
+pub use humanize::{format_relative_time, format_relative_time_in};
+pub use include::{read_with_includes, IncludeError, MAX_INCLUDE_DEPTH};
+pub use locale::Locale;
+pub use paths::{expand_path, get_config_home, resolve_config_paths, ExpandPathError};
+pub use progress::Progress;
+pub use timings::{format_timings, TimingsLayer};
 
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
-use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::OnceLock;
+use tracing::{info_span, warn};
 use tracing_appender::non_blocking::WorkerGuard;
 use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::Layer;
 
 /// Current configuration version.
 pub const CURRENT_CONFIG_VERSION: &str = "1";
@@ -28,6 +54,10 @@ pub struct Config {
     #[serde(default = "default_config_version")]
     pub version: String,
 
+    /// Language for human-facing strings such as relative times (default: en).
+    #[serde(default)]
+    pub locale: Option<Locale>,
+
     /// Diff minimization configuration.
     #[serde(default)]
     pub diff: Option<DiffConfig>,
@@ -43,16 +73,22 @@ pub struct Config {
     /// MCP server configuration.
     #[serde(default)]
     pub mcp: Option<McpConfig>,
+
+    /// VCS path info configuration.
+    #[serde(default)]
+    pub vcs_path: Option<VcsPathConfig>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             version: default_config_version(),
+            locale: None,
             diff: None,
             case: None,
             log: None,
             mcp: None,
+            vcs_path: None,
         }
     }
 }
@@ -190,6 +226,100 @@ pub struct DiffConfig {
     /// Maximum consecutive empty lines to keep.
     #[serde(default = "default_max_consecutive_empty_lines")]
     pub max_consecutive_empty_lines: usize,
+
+    /// File of gitignore-style patterns for files to leave out of the diff,
+    /// relative to the working directory.
+    #[serde(default)]
+    pub exclude_file: Option<String>,
+
+    /// Estimated token limit for a saved chunk; larger chunks are replaced by
+    /// a summary of their hunks.
+    #[serde(default)]
+    pub max_tokens_per_chunk: Option<usize>,
+
+    /// File format of saved chunks.
+    #[serde(default)]
+    pub chunk_format: ChunkFormat,
+
+    /// Keep saved chunks byte-for-byte so they still apply with `git apply`;
+    /// when false, chunks get the same blank-line squashing as the minimized
+    /// output.
+    #[serde(default = "default_preserve_blank_lines_in_chunks")]
+    pub preserve_blank_lines_in_chunks: bool,
+
+    /// Append a list of excluded and summarized files to the minimized output.
+    #[serde(default = "default_omitted_footer")]
+    pub omitted_footer: bool,
+
+    /// How long `--save` waits for another agpod process to release the
+    /// output directory lock.
+    #[serde(default = "default_lock_timeout_ms")]
+    pub lock_timeout_ms: u64,
+
+    /// Order of files in the minimized output, chunks and REVIEW.md.
+    #[serde(default)]
+    pub order_by: FileOrder,
+
+    /// Leave out of the minimized output changes that only alter the amount
+    /// of whitespace, like `git diff --ignore-space-change`.
+    #[serde(default)]
+    pub ignore_space_change: bool,
+
+    /// Leave out of the minimized output added and removed lines that are
+    /// only a comment, for languages known by file extension.
+    #[serde(default)]
+    pub ignore_comments: bool,
+
+    /// Start the minimized output and REVIEW.md with the repository, branch,
+    /// commit, diff source, change totals and `--context` text.
+    #[serde(default)]
+    pub with_header: bool,
+
+    /// Follow large-file summaries of Rust, TypeScript/JavaScript and Python
+    /// files with the top-level signatures and imports they changed.
+    #[serde(default)]
+    pub signatures_in_summary: bool,
+}
+
+/// File format of saved diff chunks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkFormat {
+    /// Plain `diff --git` output, saved as `.diff`.
+    #[default]
+    Diff,
+    /// The diff wrapped in a minimal mbox header for `git am`, saved as `.patch`.
+    Patch,
+}
+
+impl ChunkFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Diff => "diff",
+            Self::Patch => "patch",
+        }
+    }
+}
+
+/// Order of files in diff output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileOrder {
+    /// Sorted by path with deletions last, so reruns give the same order
+    /// whatever order git listed the files in.
+    #[default]
+    Path,
+    /// The order files appear in the input diff.
+    Input,
+}
+
+/// Configuration for `vcs-path-info`.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VcsPathConfig {
+    /// Number of first-parent commits searched for jj bookmarks.
+    #[serde(default)]
+    pub jj_depth: Option<usize>,
 }
 
 /// Configuration for MCP-related features.
@@ -258,6 +388,17 @@ impl Default for DiffConfig {
             large_file_changes_threshold: default_large_file_changes_threshold(),
             large_file_lines_threshold: default_large_file_lines_threshold(),
             max_consecutive_empty_lines: default_max_consecutive_empty_lines(),
+            exclude_file: None,
+            max_tokens_per_chunk: None,
+            chunk_format: ChunkFormat::Diff,
+            preserve_blank_lines_in_chunks: default_preserve_blank_lines_in_chunks(),
+            omitted_footer: default_omitted_footer(),
+            lock_timeout_ms: default_lock_timeout_ms(),
+            order_by: FileOrder::Path,
+            ignore_space_change: false,
+            ignore_comments: false,
+            with_header: false,
+            signatures_in_summary: false,
         }
     }
 }
@@ -266,18 +407,6 @@ fn default_config_version() -> String {
     CURRENT_CONFIG_VERSION.to_string()
 }
 
-/// Get the configuration home directory, respecting XDG_CONFIG_HOME.
-#[allow(dead_code)]
-pub fn get_config_home() -> Option<PathBuf> {
-    if let Ok(xdg_config_home) = env::var("XDG_CONFIG_HOME") {
-        if !xdg_config_home.is_empty() {
-            return Some(PathBuf::from(xdg_config_home));
-        }
-    }
-
-    dirs::home_dir().map(|h| h.join(".config"))
-}
-
 fn default_diff_output_dir() -> String {
     "llm/diff".to_string()
 }
@@ -294,6 +423,18 @@ fn default_max_consecutive_empty_lines() -> usize {
     2
 }
 
+fn default_preserve_blank_lines_in_chunks() -> bool {
+    true
+}
+
+fn default_omitted_footer() -> bool {
+    true
+}
+
+fn default_lock_timeout_ms() -> u64 {
+    10_000
+}
+
 impl Config {
     /// Check if the configuration version is supported.
     #[allow(dead_code)]
@@ -315,11 +456,17 @@ impl Config {
         }
     }
 
-    /// Load configuration from file.
+    /// Load configuration from file, with the files it `include`s merged in.
     #[allow(dead_code)]
-    pub fn load_from_file(path: &PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
-        let content = fs::read_to_string(path)?;
-        let mut config: Config = toml::from_str(&content)?;
+    pub fn load_from_file(path: &Path) -> Result<Self, ConfigError> {
+        let table = read_with_includes(path)?;
+        let mut config: Config =
+            toml::Value::Table(table)
+                .try_into()
+                .map_err(|source| ConfigError::Invalid {
+                    path: path.to_path_buf(),
+                    source: Box::new(source),
+                })?;
 
         if let Some(warning) = config.version_warning() {
             eprintln!("{}", warning);
@@ -344,21 +491,24 @@ impl Config {
     /// 3. Repo config (.agpod.toml)
     #[allow(dead_code)]
     pub fn load() -> Self {
+        let _span = info_span!("config_load").entered();
         let mut config = Self::default();
 
         if let Some(config_dir) = Self::get_config_dir() {
             let global_config = config_dir.join("config.toml");
             if global_config.exists() {
-                if let Ok(loaded) = Self::load_from_file(&global_config) {
-                    config = config.merge(loaded);
+                match Self::load_from_file(&global_config) {
+                    Ok(loaded) => config = config.merge(loaded),
+                    Err(e) => warn!("ignoring {}: {}", global_config.display(), e),
                 }
             }
         }
 
         let repo_config = PathBuf::from(".agpod.toml");
         if repo_config.exists() {
-            if let Ok(loaded) = Self::load_from_file(&repo_config) {
-                config = config.merge(loaded);
+            match Self::load_from_file(&repo_config) {
+                Ok(loaded) => config = config.merge(loaded),
+                Err(e) => warn!("ignoring {}: {}", repo_config.display(), e),
             }
         }
 
@@ -372,6 +522,10 @@ impl Config {
             self.version = other.version;
         }
 
+        if other.locale.is_some() {
+            self.locale = other.locale;
+        }
+
         if other.diff.is_some() {
             self.diff = other.diff;
         }
@@ -388,6 +542,10 @@ impl Config {
             self.mcp = other.mcp;
         }
 
+        if other.vcs_path.is_some() {
+            self.vcs_path = other.vcs_path;
+        }
+
         self
     }
 
@@ -410,6 +568,17 @@ impl Config {
 static LOG_GUARD: OnceLock<WorkerGuard> = OnceLock::new();
 
 pub fn init_logging(app_name: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    init_logging_with_timings(app_name, false)
+}
+
+/// Like [`init_logging`], also recording span durations for
+/// [`format_timings`] when `timings` is set.
+pub fn init_logging_with_timings(
+    app_name: &str,
+    timings: bool,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    // created first so the total covers loading the config below
+    let timings_layer = timings.then(TimingsLayer::new);
     let config = Config::load();
     let resolved = config.resolved_log_config();
     fs::create_dir_all(&resolved.dir)?;
@@ -418,11 +587,13 @@ pub fn init_logging(app_name: &str) -> Result<PathBuf, Box<dyn std::error::Error
     let file_appender = tracing_appender::rolling::never(&resolved.dir, format!("{app_name}.log"));
     let (non_blocking, guard) = tracing_appender::non_blocking(file_appender);
 
-    let subscriber = tracing_subscriber::fmt()
-        .with_max_level(resolved.level.as_level_filter())
+    let file_layer = tracing_subscriber::fmt::layer()
         .with_ansi(false)
         .with_writer(non_blocking)
-        .finish();
+        .with_filter(resolved.level.as_level_filter());
+    let subscriber = tracing_subscriber::registry()
+        .with(file_layer)
+        .with(timings_layer);
 
     let _ = tracing::subscriber::set_global_default(subscriber);
     let _ = LOG_GUARD.set(guard);
@@ -433,6 +604,7 @@ pub fn init_logging(app_name: &str) -> Result<PathBuf, Box<dyn std::error::Error
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::env;
     use std::sync::Mutex;
 
     static ENV_LOCK: Mutex<()> = Mutex::new(());
@@ -445,26 +617,32 @@ mod tests {
         assert!(config.case.is_none());
         assert!(config.log.is_none());
         assert!(config.mcp.is_none());
+        assert!(config.vcs_path.is_none());
+        assert!(config.locale.is_none());
     }
 
     #[test]
     fn test_config_version_validation() {
         let config = Config {
             version: "1".to_string(),
+            locale: None,
             diff: None,
             case: None,
             log: None,
             mcp: None,
+            vcs_path: None,
         };
         assert!(config.is_version_supported());
         assert!(config.version_warning().is_none());
 
         let unsupported_config = Config {
             version: "999".to_string(),
+            locale: None,
             diff: None,
             case: None,
             log: None,
             mcp: None,
+            vcs_path: None,
         };
         assert!(!unsupported_config.is_version_supported());
         assert!(unsupported_config.version_warning().is_some());
@@ -567,6 +745,30 @@ level = "debug"
         ));
     }
 
+    #[test]
+    fn test_parse_locale() {
+        let config: Config = toml::from_str("locale = \"zh-CN\"\n").unwrap();
+        assert_eq!(config.locale, Some(Locale::ZhCn));
+
+        let merged = config.merge(toml::from_str("[diff]\n").unwrap());
+        assert_eq!(merged.locale, Some(Locale::ZhCn));
+        let merged = merged.merge(toml::from_str("locale = \"en\"\n").unwrap());
+        assert_eq!(merged.locale, Some(Locale::En));
+
+        assert!(toml::from_str::<Config>("locale = \"fr\"\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_vcs_path_config() {
+        let toml_str = r#"
+[vcs_path]
+jj_depth = 25
+"#;
+
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert_eq!(config.vcs_path.and_then(|cfg| cfg.jj_depth), Some(25));
+    }
+
     #[test]
     fn test_resolved_log_config_defaults_to_warning() {
         let config = Config::default();
@@ -582,6 +784,12 @@ level = "debug"
         assert_eq!(diff_config.large_file_changes_threshold, 100);
         assert_eq!(diff_config.large_file_lines_threshold, 500);
         assert_eq!(diff_config.max_consecutive_empty_lines, 2);
+        assert_eq!(diff_config.exclude_file, None);
+        assert_eq!(diff_config.max_tokens_per_chunk, None);
+        assert_eq!(diff_config.chunk_format, ChunkFormat::Diff);
+        assert!(diff_config.preserve_blank_lines_in_chunks);
+        assert!(diff_config.omitted_footer);
+        assert_eq!(diff_config.lock_timeout_ms, 10_000);
     }
 
     #[test]
@@ -598,6 +806,13 @@ large_file_changes_threshold = 200
         let diff = config.diff.unwrap();
         assert_eq!(diff.output_dir, "custom/diff");
         assert_eq!(diff.large_file_changes_threshold, 200);
+        assert_eq!(diff.chunk_format, ChunkFormat::Diff);
+
+        let config: Config = toml::from_str("[diff]\nchunk_format = \"patch\"\n").unwrap();
+        assert_eq!(config.diff.unwrap().chunk_format, ChunkFormat::Patch);
+
+        let config: Config = toml::from_str("[diff]\norder_by = \"input\"\n").unwrap();
+        assert_eq!(config.diff.unwrap().order_by, FileOrder::Input);
     }
 
     #[test]
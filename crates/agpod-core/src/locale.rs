@@ -0,0 +1,67 @@
+//! Display-string tables for the supported locales.
+//!
+//! Only human-facing text goes through here; JSON and other machine output
+//! stays English/ISO regardless of the locale.
+
+use serde::{Deserialize, Serialize};
+
+/// Language for human-facing strings.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Locale {
+    #[default]
+    #[serde(rename = "en")]
+    En,
+    #[serde(rename = "zh-CN")]
+    ZhCn,
+}
+
+/// Unit of a relative time such as `3 days ago`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TimeUnit {
+    Minute,
+    Hour,
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl Locale {
+    pub(crate) fn just_now(self) -> &'static str {
+        match self {
+            Self::En => "just now",
+            Self::ZhCn => "刚刚",
+        }
+    }
+
+    pub(crate) fn time_ago(self, count: i64, unit: TimeUnit) -> String {
+        match self {
+            Self::En => {
+                let unit = match unit {
+                    TimeUnit::Minute => "minute",
+                    TimeUnit::Hour => "hour",
+                    TimeUnit::Day => "day",
+                    TimeUnit::Week => "week",
+                    TimeUnit::Month => "month",
+                    TimeUnit::Year => "year",
+                };
+                if count == 1 {
+                    format!("1 {unit} ago")
+                } else {
+                    format!("{count} {unit}s ago")
+                }
+            }
+            Self::ZhCn => {
+                let unit = match unit {
+                    TimeUnit::Minute => "分钟",
+                    TimeUnit::Hour => "小时",
+                    TimeUnit::Day => "天",
+                    TimeUnit::Week => "周",
+                    TimeUnit::Month => "个月",
+                    TimeUnit::Year => "年",
+                };
+                format!("{count} {unit}前")
+            }
+        }
+    }
+}
@@ -0,0 +1,198 @@
+//! Expansion of `~` and environment variables in configured paths.
+
+use crate::Config;
+use std::env;
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Why a path could not be expanded.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ExpandPathError {
+    #[error("`~user` paths are not supported: {path}")]
+    UserTilde { path: String },
+
+    #[error("cannot expand {path}: home directory is unknown")]
+    NoHomeDir { path: String },
+}
+
+/// Expand a leading `~` and `$VAR`/`${VAR}` references in `path`.
+///
+/// - `~` and `~/…` use [`dirs::home_dir`], which reads `$HOME` on Unix and
+///   falls back to the user database when it is unset.
+/// - `~user/…` is rejected rather than passed through, since it would
+///   otherwise silently create a directory literally named `~user`.
+/// - Unset variables are left as written (`$MISSING/path`), so the mistake
+///   shows up in the resulting path instead of collapsing it to `/path`.
+/// - Expansion is a single pass: a variable whose value contains `$OTHER`
+///   is not expanded again.
+pub fn expand_path(path: &str) -> Result<String, ExpandPathError> {
+    let with_home = match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            let home = dirs::home_dir().ok_or_else(|| ExpandPathError::NoHomeDir {
+                path: path.to_string(),
+            })?;
+            format!("{}{}", home.display(), rest)
+        }
+        Some(_) => {
+            return Err(ExpandPathError::UserTilde {
+                path: path.to_string(),
+            })
+        }
+        None => path.to_string(),
+    };
+    Ok(expand_env_vars(&with_home))
+}
+
+fn expand_env_vars(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(index) = rest.find('$') {
+        output.push_str(&rest[..index]);
+        let after = &rest[index + 1..];
+        let (name, reference_len) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => ("", 0),
+            },
+            None => {
+                let end = after
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(after.len());
+                (&after[..end], end)
+            }
+        };
+        let is_name = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_');
+        let reference = &rest[index..index + 1 + reference_len];
+        match env::var(name) {
+            Ok(value) if is_name => output.push_str(&value),
+            _ => output.push_str(reference),
+        }
+        rest = &rest[index + 1 + reference_len..];
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Get the configuration home directory, respecting XDG_CONFIG_HOME.
+pub fn get_config_home() -> Option<PathBuf> {
+    if let Ok(xdg_config_home) = env::var("XDG_CONFIG_HOME") {
+        if !xdg_config_home.is_empty() {
+            return Some(PathBuf::from(xdg_config_home));
+        }
+    }
+
+    dirs::home_dir().map(|h| h.join(".config"))
+}
+
+/// Expand `~` and environment variables in every path-valued setting:
+/// `[diff] output_dir` and `exclude_file`, and `[case] data_dir`.
+pub fn resolve_config_paths(mut config: Config) -> Result<Config, ExpandPathError> {
+    if let Some(diff) = config.diff.as_mut() {
+        diff.output_dir = expand_path(&diff.output_dir)?;
+        if let Some(exclude_file) = diff.exclude_file.as_mut() {
+            *exclude_file = expand_path(exclude_file)?;
+        }
+    }
+    if let Some(data_dir) = config.case.as_mut().and_then(|case| case.data_dir.as_mut()) {
+        *data_dir = expand_path(data_dir)?;
+    }
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn home() -> String {
+        dirs::home_dir().unwrap().display().to_string()
+    }
+
+    #[test]
+    fn test_expand_path_tilde() {
+        assert_eq!(expand_path("~").unwrap(), home());
+        assert_eq!(expand_path("~/").unwrap(), format!("{}/", home()));
+        assert_eq!(expand_path("~/work").unwrap(), format!("{}/work", home()));
+        assert_eq!(expand_path("/tmp/~").unwrap(), "/tmp/~");
+        assert_eq!(expand_path("relative/path").unwrap(), "relative/path");
+        assert_eq!(
+            expand_path("~alice/work"),
+            Err(ExpandPathError::UserTilde {
+                path: "~alice/work".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_expand_path_env_vars() {
+        env::set_var("AGPOD_PATHS_TEST_DIR", "/test/path");
+        env::set_var("AGPOD_PATHS_TEST_NESTED", "$AGPOD_PATHS_TEST_DIR/inner");
+        env::remove_var("AGPOD_PATHS_TEST_MISSING");
+
+        assert_eq!(
+            expand_path("$AGPOD_PATHS_TEST_DIR/subdir").unwrap(),
+            "/test/path/subdir"
+        );
+        assert_eq!(
+            expand_path("prefix/${AGPOD_PATHS_TEST_DIR}suffix").unwrap(),
+            "prefix//test/pathsuffix"
+        );
+        assert_eq!(
+            expand_path("~/$AGPOD_PATHS_TEST_DIR").unwrap(),
+            format!("{}//test/path", home())
+        );
+        // unset variables stay visible in the result
+        assert_eq!(
+            expand_path("$AGPOD_PATHS_TEST_MISSING/path").unwrap(),
+            "$AGPOD_PATHS_TEST_MISSING/path"
+        );
+        assert_eq!(
+            expand_path("${AGPOD_PATHS_TEST_MISSING}/path").unwrap(),
+            "${AGPOD_PATHS_TEST_MISSING}/path"
+        );
+        // values are not expanded a second time
+        assert_eq!(
+            expand_path("$AGPOD_PATHS_TEST_NESTED").unwrap(),
+            "$AGPOD_PATHS_TEST_DIR/inner"
+        );
+        assert_eq!(
+            expand_path("cost$5/${unclosed").unwrap(),
+            "cost$5/${unclosed"
+        );
+        assert_eq!(expand_path("trailing$").unwrap(), "trailing$");
+    }
+
+    #[test]
+    fn test_resolve_config_paths() {
+        env::set_var("AGPOD_PATHS_TEST_OUT", "/srv/review");
+        let config: Config = toml::from_str(
+            r#"
+[diff]
+output_dir = "$AGPOD_PATHS_TEST_OUT/diff"
+exclude_file = "~/.agpodignore"
+
+[case]
+data_dir = "~/case"
+"#,
+        )
+        .unwrap();
+
+        let config = resolve_config_paths(config).unwrap();
+        let diff = config.diff.unwrap();
+        assert_eq!(diff.output_dir, "/srv/review/diff");
+        assert_eq!(
+            diff.exclude_file.unwrap(),
+            format!("{}/.agpodignore", home())
+        );
+        assert_eq!(
+            config.case.unwrap().data_dir.unwrap(),
+            format!("{}/case", home())
+        );
+
+        let config: Config = toml::from_str("[diff]\noutput_dir = \"~bob/diff\"\n").unwrap();
+        assert!(matches!(
+            resolve_config_paths(config),
+            Err(ExpandPathError::UserTilde { .. })
+        ));
+        assert!(resolve_config_paths(Config::default()).is_ok());
+    }
+}
@@ -0,0 +1,133 @@
+//! `--progress-json`: newline-delimited JSON progress events on stderr, so
+//! an agent driving a long command can tell it is still working.
+//!
+//! Events are throttled to about five a second, except that the first and
+//! last event of each phase are always written.
+
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Minimum time between two events of the same phase
+const MIN_INTERVAL: Duration = Duration::from_millis(200);
+
+/// A progress reporter; [`Progress::none`] (the default) reports nothing.
+#[derive(Default)]
+pub struct Progress {
+    sink: Option<Mutex<Sink>>,
+}
+
+struct Sink {
+    out: Box<dyn Write + Send>,
+    phase: &'static str,
+    last_event: Option<Instant>,
+}
+
+impl Progress {
+    /// A reporter that reports nothing
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// JSON events on stderr
+    pub fn json() -> Self {
+        Self::json_to(io::stderr())
+    }
+
+    /// JSON events written to `out`
+    pub fn json_to(out: impl Write + Send + 'static) -> Self {
+        Self {
+            sink: Some(Mutex::new(Sink {
+                out: Box::new(out),
+                phase: "",
+                last_event: None,
+            })),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.sink.is_some()
+    }
+
+    /// `done` of `total` files of `phase` are finished
+    pub fn files(&self, phase: &'static str, done: usize, total: usize) {
+        self.emit(phase, done == total, || {
+            format!(
+                "{{\"phase\":\"{}\",\"files_done\":{},\"files_total\":{}}}",
+                phase, done, total
+            )
+        });
+    }
+
+    /// `phase` has been running for `elapsed`, for work without a count
+    pub fn elapsed(&self, phase: &'static str, elapsed: Duration) {
+        self.emit(phase, false, || {
+            format!(
+                "{{\"phase\":\"{}\",\"elapsed_ms\":{}}}",
+                phase,
+                elapsed.as_millis()
+            )
+        });
+    }
+
+    fn emit(&self, phase: &'static str, last: bool, event: impl FnOnce() -> String) {
+        let Some(sink) = &self.sink else {
+            return;
+        };
+        let mut sink = sink.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        let throttled = sink.phase == phase
+            && sink
+                .last_event
+                .is_some_and(|last_event| now.duration_since(last_event) < MIN_INTERVAL);
+        if throttled && !last {
+            return;
+        }
+        sink.phase = phase;
+        sink.last_event = Some(now);
+        // progress is best effort; a closed stderr must not fail the command
+        let _ = writeln!(sink.out, "{}", event());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_progress_throttles_but_keeps_phase_boundaries() {
+        let buffer = Buffer::default();
+        let progress = Progress::json_to(buffer.clone());
+        progress.files("parse", 3, 3);
+        for done in 1..=100 {
+            progress.files("save", done, 100);
+        }
+        progress.elapsed("plugin", Duration::from_millis(2100));
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            output,
+            "{\"phase\":\"parse\",\"files_done\":3,\"files_total\":3}\n\
+             {\"phase\":\"save\",\"files_done\":1,\"files_total\":100}\n\
+             {\"phase\":\"save\",\"files_done\":100,\"files_total\":100}\n\
+             {\"phase\":\"plugin\",\"elapsed_ms\":2100}\n"
+        );
+
+        Progress::none().files("save", 1, 2);
+        assert!(!Progress::none().is_enabled());
+    }
+}
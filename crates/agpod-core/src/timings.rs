@@ -0,0 +1,141 @@
+//! `--timings`: where a command spends its time, from tracing spans.
+//!
+//! Each closed span adds its wall time to a phase named after the span, so
+//! instrumenting a phase is just `let _span = info_span!("diff_parse").entered();`.
+
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::span::{Attributes, Id};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Phase {
+    name: &'static str,
+    total: Duration,
+    count: usize,
+}
+
+/// Phases in the order they first closed
+static PHASES: Mutex<Vec<Phase>> = Mutex::new(Vec::new());
+static STARTED: OnceLock<Instant> = OnceLock::new();
+
+struct SpanStart(Instant);
+
+/// A tracing layer recording how long each span was open.
+pub struct TimingsLayer;
+
+impl TimingsLayer {
+    pub fn new() -> Self {
+        STARTED.get_or_init(Instant::now);
+        Self
+    }
+}
+
+impl Default for TimingsLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> Layer<S> for TimingsLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, _attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanStart(Instant::now()));
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(elapsed) = span
+            .extensions()
+            .get::<SpanStart>()
+            .map(|start| start.0.elapsed())
+        else {
+            return;
+        };
+        record(span.name(), elapsed);
+    }
+}
+
+fn record(name: &'static str, elapsed: Duration) {
+    let mut phases = PHASES.lock().unwrap_or_else(|e| e.into_inner());
+    match phases.iter_mut().find(|phase| phase.name == name) {
+        Some(phase) => {
+            phase.total += elapsed;
+            phase.count += 1;
+        }
+        None => phases.push(Phase {
+            name,
+            total: elapsed,
+            count: 1,
+        }),
+    }
+}
+
+/// The breakdown of recorded phases plus the total since the layer was
+/// created, or `None` when timings were not enabled.
+pub fn format_timings() -> Option<String> {
+    let started = STARTED.get()?;
+    let phases = PHASES.lock().unwrap_or_else(|e| e.into_inner());
+    Some(render(&phases, started.elapsed()))
+}
+
+fn render(phases: &[Phase], total: Duration) -> String {
+    let width = phases
+        .iter()
+        .map(|phase| phase.name.len())
+        .chain(["total".len()])
+        .max()
+        .unwrap_or_default();
+    let mut text = String::from("timings:\n");
+    for phase in phases {
+        text.push_str(&format!(
+            "  {:<width$}  {}",
+            phase.name,
+            format_ms(phase.total)
+        ));
+        if phase.count > 1 {
+            text.push_str(&format!(" ({}x)", phase.count));
+        }
+        text.push('\n');
+    }
+    text.push_str(&format!("  {:<width$}  {}\n", "total", format_ms(total)));
+    text
+}
+
+fn format_ms(duration: Duration) -> String {
+    format!("{:.1} ms", duration.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_aligns_phases_and_counts_repeats() {
+        let phases = [
+            Phase {
+                name: "config_load",
+                total: Duration::from_micros(400),
+                count: 1,
+            },
+            Phase {
+                name: "chunk_write",
+                total: Duration::from_micros(3_300),
+                count: 12,
+            },
+        ];
+
+        assert_eq!(
+            render(&phases, Duration::from_millis(6)),
+            "timings:\n  config_load  0.4 ms\n  chunk_write  3.3 ms (12x)\n  total        6.0 ms\n"
+        );
+    }
+}
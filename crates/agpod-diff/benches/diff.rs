@@ -0,0 +1,69 @@
+//! Parser, minimizer and save throughput on generated diffs of 1 to 100 MB.
+//!
+//! Run with `cargo bench -p agpod-diff`; pass a filter such as `parse/10MB`
+//! to run a subset.
+
+use agpod_diff::{minimize_diff, parse_git_diff, save_diff_chunks};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use std::hint::black_box;
+
+#[path = "../tests/support/mod.rs"]
+mod support;
+
+use support::{generate_diff, FixtureSpec};
+
+const SIZES_MB: [usize; 3] = [1, 10, 100];
+const SEED: u64 = 42;
+
+fn fixtures() -> Vec<(String, String)> {
+    SIZES_MB
+        .iter()
+        .map(|mb| {
+            let spec = FixtureSpec::with_size(mb * 1024 * 1024, SEED);
+            (format!("{}MB", mb), generate_diff(&spec))
+        })
+        .collect()
+}
+
+fn bench_diff(c: &mut Criterion) {
+    let fixtures = fixtures();
+
+    let mut group = c.benchmark_group("parse");
+    group.sample_size(10);
+    for (name, diff) in &fixtures {
+        group.throughput(Throughput::Bytes(diff.len() as u64));
+        group.bench_function(name.as_str(), |b| {
+            b.iter(|| parse_git_diff(black_box(diff)))
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("minimize");
+    group.sample_size(10);
+    for (name, diff) in &fixtures {
+        group.throughput(Throughput::Bytes(diff.len() as u64));
+        group.bench_function(name.as_str(), |b| b.iter(|| minimize_diff(black_box(diff))));
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("save");
+    group.sample_size(10);
+    for (name, diff) in &fixtures {
+        group.throughput(Throughput::Bytes(diff.len() as u64));
+        group.bench_function(name.as_str(), |b| {
+            b.iter_batched(
+                || tempfile::tempdir().unwrap(),
+                |dir| {
+                    let output = dir.path().join("review");
+                    save_diff_chunks(black_box(diff), output.to_str().unwrap(), None).unwrap();
+                    dir
+                },
+                BatchSize::PerIteration,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_diff);
+criterion_main!(benches);
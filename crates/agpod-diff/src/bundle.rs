@@ -0,0 +1,141 @@
+//! Splitting the minimized output into bundles for multi-pass review
+
+use super::lock::DirLock;
+use super::processor::{format_minimized_files, MinimizedFile};
+use super::save::{estimate_tokens, write_file};
+use agpod_core::DiffConfig;
+use agpod_core::{Error, IoError};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::info_span;
+
+/// How to split the minimized output into bundles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Split {
+    /// This many bundles of roughly equal size
+    Count(usize),
+    /// As few bundles as fit this many estimated tokens each; a file larger
+    /// than the limit gets a bundle of its own
+    Tokens(usize),
+}
+
+/// Group file indices into bundles by estimated token count, never splitting
+/// a file. Files keep their input order within a bundle, and empty bundles
+/// are dropped.
+pub fn plan_bundles(file_tokens: &[usize], split: Split) -> Vec<Vec<usize>> {
+    // place the largest files first so the greedy fill stays balanced
+    let mut order: Vec<usize> = (0..file_tokens.len()).collect();
+    order.sort_by_key(|&index| std::cmp::Reverse(file_tokens[index]));
+
+    let mut bundles: Vec<(usize, Vec<usize>)> = Vec::new();
+    match split {
+        Split::Count(count) => {
+            bundles.resize_with(count.max(1), Default::default);
+            for index in order {
+                let lightest = bundles
+                    .iter_mut()
+                    .min_by_key(|(tokens, _)| *tokens)
+                    .expect("at least one bundle");
+                lightest.0 += file_tokens[index];
+                lightest.1.push(index);
+            }
+        }
+        Split::Tokens(limit) => {
+            for index in order {
+                let tokens = file_tokens[index];
+                match bundles
+                    .iter_mut()
+                    .find(|(total, _)| total + tokens <= limit)
+                {
+                    Some(bundle) => {
+                        bundle.0 += tokens;
+                        bundle.1.push(index);
+                    }
+                    None => bundles.push((tokens, vec![index])),
+                }
+            }
+        }
+    }
+
+    bundles
+        .into_iter()
+        .filter(|(_, files)| !files.is_empty())
+        .map(|(_, mut files)| {
+            files.sort_unstable();
+            files
+        })
+        .collect()
+}
+
+/// A bundle's text: a manifest of its files, then their minimized output
+pub fn format_bundle(
+    number: usize,
+    total: usize,
+    files: &[&MinimizedFile],
+    config: &DiffConfig,
+) -> String {
+    let body = format_minimized_files(files.iter().copied(), config);
+    let mut bundle = format!(
+        "# Bundle {} of {} (~{} tokens)\n\n## Files\n",
+        number,
+        total,
+        estimate_tokens(&body)
+    );
+    for file in files {
+        bundle.push_str(&format!("- {}\n", file.file_change.display_path()));
+    }
+    bundle.push_str("\n---\n\n");
+    bundle.push_str(&body);
+    bundle
+}
+
+/// Write `files` as `bundle_1.md` ... `bundle_N.md` into `output_dir`,
+/// replacing bundles from an earlier run, and return the written paths
+pub fn write_bundles(
+    files: &[MinimizedFile],
+    split: Split,
+    output_dir: &Path,
+    config: &DiffConfig,
+) -> Result<Vec<PathBuf>, Error> {
+    fs::create_dir_all(output_dir).map_err(|source| IoError::CreateOutputDir {
+        path: output_dir.to_path_buf(),
+        source,
+    })?;
+    let _lock = DirLock::acquire(output_dir, Duration::from_millis(config.lock_timeout_ms))?;
+    remove_old_bundles(output_dir);
+
+    let file_tokens: Vec<usize> = files
+        .iter()
+        .map(|file| estimate_tokens(&file.text))
+        .collect();
+    let bundles = plan_bundles(&file_tokens, split);
+    let mut written = Vec::with_capacity(bundles.len());
+    for (number, indices) in bundles.iter().enumerate() {
+        let members: Vec<&MinimizedFile> = indices.iter().map(|&index| &files[index]).collect();
+        let path = output_dir.join(format!("bundle_{}.md", number + 1));
+        let content = format_bundle(number + 1, bundles.len(), &members, config);
+        info_span!("bundle_write")
+            .in_scope(|| write_file(&path, &content))
+            .map_err(|source| IoError::WriteBundle {
+                path: path.clone(),
+                source,
+            })?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Best-effort removal of `bundle_*.md` files left by an earlier run
+fn remove_old_bundles(output_dir: &Path) {
+    let Ok(entries) = fs::read_dir(output_dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with("bundle_") && name.ends_with(".md") {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
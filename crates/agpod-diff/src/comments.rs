@@ -0,0 +1,107 @@
+//! Review comment extraction from REVIEW.md for handing back to agents
+
+use super::save::{parse_review_entries, resolve_review_dir};
+use agpod_core::{Error, IoError, ReviewError};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+/// Output format for `agpod diff comments`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CommentsFormat {
+    #[default]
+    Markdown,
+    Json,
+}
+
+/// A file from REVIEW.md that has review comments
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ReviewComment {
+    pub file: String,
+    pub status: String,
+    pub chunk: Option<String>,
+    pub comments: String,
+    /// Chunk diff content, only loaded when requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<String>,
+}
+
+/// Print the commented files of the REVIEW.md saved under `output_dir`.
+///
+/// Nothing to address prints `[]` for JSON and nothing for Markdown.
+pub fn print_review_comments(
+    output_dir: &str,
+    format: CommentsFormat,
+    with_diff: bool,
+) -> Result<()> {
+    let review_dir = resolve_review_dir(output_dir);
+    let comments = collect_review_comments(&review_dir, with_diff)
+        .context("failed to collect review comments")?;
+
+    match format {
+        CommentsFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&comments)?);
+        }
+        CommentsFormat::Markdown => print!("{}", format_comments_markdown(&comments)),
+    }
+    Ok(())
+}
+
+/// Collect files with non-empty comments from `review_dir/REVIEW.md`, in
+/// document order, optionally inlining each file's chunk diff
+pub fn collect_review_comments(
+    review_dir: &Path,
+    with_diff: bool,
+) -> Result<Vec<ReviewComment>, Error> {
+    let review_path = review_dir.join("REVIEW.md");
+    let content = fs::read_to_string(&review_path).map_err(|source| ReviewError::ReadReview {
+        path: review_path.clone(),
+        source,
+    })?;
+
+    parse_review_entries(&content)
+        .into_iter()
+        .filter(|(_, entry)| !entry.comments.is_empty())
+        .map(|(file, entry)| {
+            let diff = match (&entry.diff_chunk, with_diff) {
+                (Some(chunk), true) => {
+                    let path = review_dir.join(chunk);
+                    let diff = fs::read_to_string(&path)
+                        .map_err(|source| IoError::ReadChunk { path, source })?;
+                    Some(diff)
+                }
+                _ => None,
+            };
+            Ok(ReviewComment {
+                file,
+                status: entry.status,
+                chunk: entry.diff_chunk,
+                comments: entry.comments,
+                diff,
+            })
+        })
+        .collect()
+}
+
+/// Format comments as Markdown, one `##` section per file
+pub fn format_comments_markdown(comments: &[ReviewComment]) -> String {
+    let mut result = String::new();
+    for comment in comments {
+        result.push_str(&format!("## {}\n", comment.file));
+        result.push_str(&format!("- status: {}\n", comment.status));
+        if let Some(chunk) = &comment.chunk {
+            result.push_str(&format!("- chunk: {}\n", chunk));
+        }
+        result.push('\n');
+        result.push_str(&comment.comments);
+        result.push_str("\n\n");
+
+        if let Some(diff) = &comment.diff {
+            result.push_str("```diff\n");
+            result.push_str(diff.trim_end());
+            result.push_str("\n```\n\n");
+        }
+    }
+    result
+}
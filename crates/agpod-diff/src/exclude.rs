@@ -0,0 +1,105 @@
+//! Gitignore-style exclusion of files from a diff
+
+use super::types::FileChange;
+use agpod_core::DiffConfig;
+use agpod_core::{ConfigError, Error, IoError};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Matches diff paths against `[diff] exclude_file` and `--exclude` patterns.
+///
+/// Patterns use gitignore syntax (negations, directory patterns, comments),
+/// with command-line patterns applied after the file so they take precedence.
+#[derive(Debug, Default)]
+pub struct ExcludeMatcher {
+    gitignore: Option<Gitignore>,
+}
+
+impl ExcludeMatcher {
+    /// Build a matcher from `config.exclude_file` plus extra `patterns`
+    pub fn from_config(config: &DiffConfig, patterns: &[String]) -> Result<Self, Error> {
+        Self::new(config.exclude_file.as_deref().map(Path::new), patterns)
+    }
+
+    /// Build a matcher from an optional pattern file plus extra `patterns`.
+    ///
+    /// A missing pattern file is treated as empty, so one config can be
+    /// shared across repositories that don't all have it.
+    pub fn new(exclude_file: Option<&Path>, patterns: &[String]) -> Result<Self, Error> {
+        let mut builder = GitignoreBuilder::new(".");
+        let mut has_patterns = false;
+
+        if let Some(path) = exclude_file {
+            match fs::read_to_string(path) {
+                Ok(content) => {
+                    for (index, line) in content.lines().enumerate() {
+                        builder
+                            .add_line(Some(path.to_path_buf()), line)
+                            .map_err(|source| ConfigError::InvalidExcludePattern {
+                                source: ignore::Error::WithPath {
+                                    path: path.to_path_buf(),
+                                    err: Box::new(ignore::Error::WithLineNumber {
+                                        line: index as u64 + 1,
+                                        err: Box::new(source),
+                                    }),
+                                }
+                                .into(),
+                            })?;
+                        has_patterns = true;
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(source) => {
+                    return Err(IoError::ReadExcludeFile {
+                        path: path.to_path_buf(),
+                        source,
+                    }
+                    .into())
+                }
+            }
+        }
+
+        for pattern in patterns {
+            builder.add_line(None, pattern).map_err(|source| {
+                ConfigError::InvalidExcludePattern {
+                    source: source.into(),
+                }
+            })?;
+            has_patterns = true;
+        }
+
+        if !has_patterns {
+            return Ok(Self::default());
+        }
+        let gitignore = builder
+            .build()
+            .map_err(|source| ConfigError::InvalidExcludePattern {
+                source: source.into(),
+            })?;
+        Ok(Self {
+            gitignore: Some(gitignore),
+        })
+    }
+
+    /// Whether `path` (relative to the repository root) is excluded
+    pub fn is_match(&self, path: &str) -> bool {
+        let Some(gitignore) = &self.gitignore else {
+            return false;
+        };
+        let path = path.trim_start_matches('/');
+        !path.is_empty()
+            && gitignore
+                .matched_path_or_any_parents(path, false)
+                .is_ignore()
+    }
+
+    /// Whether either side of `file_change` is excluded
+    pub fn is_excluded(&self, file_change: &FileChange) -> bool {
+        [&file_change.old_path, &file_change.new_path]
+            .into_iter()
+            .flatten()
+            .any(|path| self.is_match(path))
+    }
+}
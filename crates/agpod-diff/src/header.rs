@@ -0,0 +1,105 @@
+//! Repository metadata prepended to the output with `--with-header`
+
+use super::save::get_project_identifier;
+use super::source::DiffSource;
+use super::types::FileChange;
+use std::process::Command;
+
+/// Marks the header block in REVIEW.md, so it is regenerated on each run
+/// instead of being kept as user prose
+pub(crate) const HEADER_START: &str = "<!-- agpod:header -->";
+pub(crate) const HEADER_END: &str = "<!-- /agpod:header -->";
+
+/// Grounding for the reader of a diff: where it comes from and how big it is
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffHeader {
+    pub repository: String,
+    pub branch: Option<String>,
+    pub commit: Option<String>,
+    /// What was compared, e.g. `working tree vs HEAD`; `None` when the
+    /// caller passed the diff text directly
+    pub source: Option<String>,
+    pub files: usize,
+    pub added: usize,
+    pub removed: usize,
+    pub context: Option<String>,
+}
+
+impl DiffHeader {
+    /// Collect the header for `file_changes` read from `source` in the
+    /// current repository
+    pub fn collect<'a>(
+        file_changes: impl IntoIterator<Item = &'a FileChange>,
+        source: Option<&DiffSource>,
+        context: Option<&str>,
+    ) -> Self {
+        let (mut files, mut added, mut removed) = (0, 0, 0);
+        for file_change in file_changes {
+            let (file_added, file_removed) = file_change.line_counts();
+            files += 1;
+            added += file_added;
+            removed += file_removed;
+        }
+        Self {
+            repository: get_project_identifier(),
+            branch: git_output(&["rev-parse", "--abbrev-ref", "HEAD"]),
+            commit: git_output(&["rev-parse", "--short", "HEAD"]),
+            source: source.map(DiffSource::to_string),
+            files,
+            added,
+            removed,
+            context: context.map(str::to_string),
+        }
+    }
+
+    /// The header as a Markdown list, one fact per line
+    pub fn render(&self) -> String {
+        let mut header = format!("- repository: {}\n", self.repository);
+        match (&self.branch, &self.commit) {
+            (Some(branch), Some(commit)) => {
+                header.push_str(&format!("- branch: {} @ {}\n", branch, commit))
+            }
+            (Some(branch), None) => header.push_str(&format!("- branch: {}\n", branch)),
+            (None, Some(commit)) => header.push_str(&format!("- commit: {}\n", commit)),
+            (None, None) => {}
+        }
+        if let Some(source) = &self.source {
+            header.push_str(&format!("- source: {}\n", source));
+        }
+        header.push_str(&format!(
+            "- files: {} changed, +{} -{}\n",
+            self.files, self.added, self.removed
+        ));
+        if let Some(context) = &self.context {
+            // continuation lines indented to stay inside the list item
+            let mut lines = context.lines();
+            header.push_str("- context:");
+            for (prefix, line) in std::iter::once((" ", lines.next().unwrap_or_default()))
+                .chain(lines.map(|line| ("  ", line)))
+            {
+                if !line.is_empty() {
+                    header.push_str(prefix);
+                    header.push_str(line);
+                }
+                header.push('\n');
+            }
+        }
+        header
+    }
+
+    /// The header wrapped in markers for the REVIEW.md preamble
+    pub(crate) fn render_for_review(&self) -> String {
+        format!("{}\n{}{}\n\n", HEADER_START, self.render(), HEADER_END)
+    }
+}
+
+/// Trimmed stdout of a git command, or `None` outside a repository
+fn git_output(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8(output.stdout).ok()?;
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
@@ -4,26 +4,75 @@
 //! token usage in Large Language Model contexts. It intelligently summarizes
 //! large files while preserving essential change information.
 
+mod bundle;
+mod comments;
+mod exclude;
+mod header;
+mod lock;
+mod noise;
 mod processor;
+mod publish;
+mod quote;
+mod review_merge;
 mod save;
+mod show;
+mod signatures;
+mod sniff;
+mod source;
 mod types;
 
 // Public API - only export what's needed by main.rs
+pub use bundle::Split;
+pub use comments::{print_review_comments, CommentsFormat};
+pub use exclude::ExcludeMatcher;
 pub use processor::process_git_diff;
+pub use publish::publish_review;
+pub use review_merge::merge_review_files;
+pub use show::{show_file, Side};
+pub use source::DiffSource;
 
 // Re-export for library users (allow unused since these are library APIs)
 #[allow(unused_imports)]
+pub use bundle::{format_bundle, plan_bundles, write_bundles};
+#[allow(unused_imports)]
+pub use comments::{collect_review_comments, format_comments_markdown, ReviewComment};
+#[allow(unused_imports)]
+pub use header::DiffHeader;
+#[allow(unused_imports)]
+pub use lock::{DirLock, LOCK_FILE_NAME};
+#[allow(unused_imports)]
+pub use noise::Suppressed;
+#[allow(unused_imports)]
 pub use processor::{
-    format_deleted_file_summary, format_large_file_summary, format_regular_file_diff,
-    minimize_diff, parse_git_diff, remove_excessive_empty_lines,
+    format_deleted_file_summary, format_excluded_file_summary, format_large_file_summary,
+    format_minimized_files, format_regular_file_diff, minimize_diff, minimize_diff_with_config,
+    minimize_diff_with_exclude, minimize_files, parse_git_diff, parse_git_diff_with_config,
+    remove_excessive_empty_lines,
 };
 #[allow(unused_imports)]
+pub use publish::{build_review_payload, ReviewPayload, ReviewPayloadComment};
+#[allow(unused_imports)]
+pub use review_merge::{merge_reviews, MergedReview, OURS_MARKER, THEIRS_MARKER};
+#[allow(unused_imports)]
 pub use save::{
-    compute_file_hash, generate_chunk_suffix, get_project_identifier, parse_existing_review,
-    save_diff_chunks, ReviewEntry,
+    assign_chunk_suffixes, compute_file_hash, compute_hunks_hash, estimate_tokens,
+    format_patch_chunk, generate_chunk_suffix, get_project_identifier, parse_existing_review,
+    parse_review_entries, resolve_review_dir, save_diff_chunks, save_diff_chunks_with_config,
+    save_diff_chunks_with_progress, summarize_chunk, ReviewEntry, SavedReview,
 };
 #[allow(unused_imports)]
+pub use show::reconstruct_file;
+#[allow(unused_imports)]
+pub use signatures::format_signature_summary;
+#[allow(unused_imports)]
+pub use sniff::check_diff_input;
+#[allow(unused_imports)]
+pub use source::merge_base_with_head;
+#[allow(unused_imports)]
 pub use types::{ChangeType, FileChange};
 
+/// Diff settings are defined once in agpod-core and consumed here as-is.
+pub use agpod_core::DiffConfig;
+
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,56 @@
+//! Advisory locking of an output directory across agpod processes
+
+use agpod_core::{Error, IoError};
+use std::fs::{self, File, TryLockError};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Name of the lock file created inside a locked directory
+pub const LOCK_FILE_NAME: &str = ".agpod.lock";
+
+const RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Exclusive advisory lock on a directory, released when dropped.
+///
+/// The lock file itself is left in place; removing it would let a waiting
+/// process lock a file that a newer process has already replaced.
+#[derive(Debug)]
+pub struct DirLock {
+    _file: File,
+}
+
+impl DirLock {
+    /// Lock `dir`, waiting up to `timeout` for another process to release it
+    pub fn acquire(dir: &Path, timeout: Duration) -> Result<Self, Error> {
+        let path = dir.join(LOCK_FILE_NAME);
+        let lock_error = |source| IoError::Lock {
+            path: path.clone(),
+            source,
+        };
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .map_err(lock_error)?;
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            match file.try_lock() {
+                Ok(()) => return Ok(Self { _file: file }),
+                Err(TryLockError::WouldBlock) if Instant::now() < deadline => {
+                    thread::sleep(RETRY_INTERVAL);
+                }
+                Err(TryLockError::WouldBlock) => {
+                    return Err(IoError::Locked {
+                        path: PathBuf::from(dir),
+                        timeout,
+                    }
+                    .into())
+                }
+                Err(TryLockError::Error(source)) => return Err(lock_error(source).into()),
+            }
+        }
+    }
+}
@@ -0,0 +1,243 @@
+//! Suppression of whitespace-only and comment-only changes in the
+//! minimized output
+
+use super::types::FileChange;
+use agpod_core::DiffConfig;
+use std::path::Path;
+
+/// Number of changed lines a file lost to `ignore_space_change` and
+/// `ignore_comments`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Suppressed {
+    pub whitespace: usize,
+    pub comments: usize,
+}
+
+impl Suppressed {
+    /// A `# suppressed: ...` note for the file header, if anything was dropped
+    pub fn note(&self) -> Option<String> {
+        let lines = |count: usize, kind: &str| {
+            format!("{count} {kind} line{}", if count == 1 { "" } else { "s" })
+        };
+        let mut parts = Vec::new();
+        if self.whitespace > 0 {
+            parts.push(lines(self.whitespace, "whitespace-only"));
+        }
+        if self.comments > 0 {
+            parts.push(lines(self.comments, "comment"));
+        }
+        (!parts.is_empty()).then(|| format!("# suppressed: {}", parts.join(", ")))
+    }
+}
+
+/// Comment markers of a language
+struct CommentSyntax {
+    line: &'static [&'static str],
+    block: Option<(&'static str, &'static str)>,
+}
+
+const C_LIKE: CommentSyntax = CommentSyntax {
+    line: &["//"],
+    block: Some(("/*", "*/")),
+};
+const CSS: CommentSyntax = CommentSyntax {
+    line: &[],
+    block: Some(("/*", "*/")),
+};
+const HASH: CommentSyntax = CommentSyntax {
+    line: &["#"],
+    block: None,
+};
+const MARKUP: CommentSyntax = CommentSyntax {
+    line: &[],
+    block: Some(("<!--", "-->")),
+};
+
+/// Comment syntax for `path`, picked by file extension
+fn comment_syntax(path: &str) -> Option<&'static CommentSyntax> {
+    let extension = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "rs" | "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "cs" | "java" | "js" | "jsx" | "mjs"
+        | "cjs" | "ts" | "tsx" | "go" | "swift" | "kt" | "kts" | "scala" | "dart" | "scss"
+        | "less" | "proto" | "zig" => Some(&C_LIKE),
+        "css" => Some(&CSS),
+        "py" | "rb" | "sh" | "bash" | "zsh" | "fish" | "pl" | "r" | "toml" | "yaml" | "yml"
+        | "cmake" | "conf" | "ini" => Some(&HASH),
+        "html" | "htm" | "xml" | "svg" | "md" | "markdown" | "vue" => Some(&MARKUP),
+        _ => None,
+    }
+}
+
+/// Whether `text` holds nothing but a comment, tracking whether a block
+/// comment is still open after it in `in_block`
+fn is_comment_line(text: &str, syntax: &CommentSyntax, in_block: &mut bool) -> bool {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return false;
+    }
+    let Some((open, close)) = syntax.block else {
+        return syntax.line.iter().any(|marker| trimmed.starts_with(marker));
+    };
+
+    if *in_block {
+        return match trimmed.find(close) {
+            Some(end) => {
+                *in_block = false;
+                trimmed[end + close.len()..].trim().is_empty()
+            }
+            None => true,
+        };
+    }
+    if syntax.line.iter().any(|marker| trimmed.starts_with(marker)) {
+        return true;
+    }
+    let Some(rest) = trimmed.strip_prefix(open) else {
+        return false;
+    };
+    match rest.find(close) {
+        Some(end) => rest[end + close.len()..].trim().is_empty(),
+        None => {
+            *in_block = true;
+            true
+        }
+    }
+}
+
+/// `text` with whitespace runs collapsed to one space and trailing
+/// whitespace dropped, as `git diff --ignore-space-change` compares lines
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut pending_space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            pending_space = true;
+        } else {
+            if pending_space {
+                collapsed.push(' ');
+                pending_space = false;
+            }
+            collapsed.push(c);
+        }
+    }
+    collapsed
+}
+
+fn is_change(line: &str) -> bool {
+    line.starts_with('+') || line.starts_with('-')
+}
+
+/// Turn runs of removed and added lines that differ only in whitespace into
+/// context lines, returning how many changed lines that removed
+fn collapse_whitespace_changes(hunk: &mut Vec<String>) -> usize {
+    let mut suppressed = 0;
+    let mut result = Vec::with_capacity(hunk.len());
+    let mut i = 0;
+    while i < hunk.len() {
+        if !is_change(&hunk[i]) {
+            result.push(std::mem::take(&mut hunk[i]));
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        while i < hunk.len() && (is_change(&hunk[i]) || hunk[i].starts_with('\\')) {
+            i += 1;
+        }
+        let block = &mut hunk[start..i];
+        let side = |prefix: char| -> Vec<String> {
+            block
+                .iter()
+                .filter_map(|line| line.strip_prefix(prefix))
+                .map(collapse_whitespace)
+                .collect()
+        };
+        let (removed, added) = (side('-'), side('+'));
+        if !removed.is_empty() && removed == added {
+            suppressed += removed.len() + added.len();
+            result.extend(
+                block
+                    .iter()
+                    .filter_map(|line| line.strip_prefix('+'))
+                    .map(|line| format!(" {line}")),
+            );
+        } else {
+            result.extend(block.iter_mut().map(std::mem::take));
+        }
+    }
+    *hunk = result;
+    suppressed
+}
+
+/// Drop added and removed lines that are only a comment, returning how many
+/// were dropped
+fn drop_comment_changes(hunk: &mut Vec<String>, syntax: &CommentSyntax) -> usize {
+    let mut suppressed = 0;
+    let (mut old_in_block, mut new_in_block) = (false, false);
+    let mut dropped_previous = false;
+    hunk.retain(|line| {
+        let drop = match line.chars().next() {
+            Some('-') => is_comment_line(&line[1..], syntax, &mut old_in_block),
+            Some('+') => is_comment_line(&line[1..], syntax, &mut new_in_block),
+            // "\ No newline at end of file" belongs to the line before it
+            Some('\\') => dropped_previous,
+            _ => {
+                let text = line.get(1..).unwrap_or_default();
+                is_comment_line(text, syntax, &mut old_in_block);
+                is_comment_line(text, syntax, &mut new_in_block);
+                false
+            }
+        };
+        if drop && !line.starts_with('\\') {
+            suppressed += 1;
+        }
+        dropped_previous = drop;
+        !drop
+    });
+    suppressed
+}
+
+/// Content lines of `file_change` with the changes `config` asks to ignore
+/// removed; hunks left without changes are dropped entirely
+pub(crate) fn suppress_noise(
+    file_change: &FileChange,
+    config: &DiffConfig,
+) -> (Vec<String>, Suppressed) {
+    let mut suppressed = Suppressed::default();
+    let syntax = config
+        .ignore_comments
+        .then(|| comment_syntax(file_change.display_path()))
+        .flatten();
+    if !config.ignore_space_change && syntax.is_none() {
+        return (file_change.content_lines.clone(), suppressed);
+    }
+
+    let hunk_start = file_change
+        .content_lines
+        .iter()
+        .position(|line| line.starts_with("@@"))
+        .unwrap_or(file_change.content_lines.len());
+    let mut lines = file_change.content_lines[..hunk_start].to_vec();
+
+    let mut rest = &file_change.content_lines[hunk_start..];
+    while let Some(header) = rest.first() {
+        let end = rest[1..]
+            .iter()
+            .position(|line| line.starts_with("@@"))
+            .map_or(rest.len(), |index| index + 1);
+        let mut body = rest[1..end].to_vec();
+        rest = &rest[end..];
+
+        if config.ignore_space_change {
+            suppressed.whitespace += collapse_whitespace_changes(&mut body);
+        }
+        if let Some(syntax) = syntax {
+            suppressed.comments += drop_comment_changes(&mut body, syntax);
+        }
+        if body.iter().any(|line| is_change(line)) {
+            lines.push(header.clone());
+            lines.extend(body);
+        }
+    }
+
+    (lines, suppressed)
+}
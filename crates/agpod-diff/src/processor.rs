@@ -1,24 +1,81 @@
 //! Core diff processing and minimization logic
 
+use super::bundle::{write_bundles, Split};
+use super::exclude::ExcludeMatcher;
+use super::header::DiffHeader;
+use super::noise::{suppress_noise, Suppressed};
+use super::quote::{unquote, GitPath};
+use super::signatures::format_signature_summary;
+use super::sniff::{check_diff_input, find_plain_unified_header};
+use super::source::DiffSource;
 use super::types::{ChangeType, FileChange};
-use regex::Regex;
-use std::io::{self, Read};
-
-/// Process git diff from stdin and output minimized version
+use agpod_core::{DiffConfig, FileOrder, Progress};
+use anyhow::{Context, Result};
+use tracing::info_span;
+
+/// Path prefix pairs git can put in `diff --git` headers: the default
+/// `a/`/`b/`, the `diff.mnemonicPrefix` pairs and `--no-index`'s `1/`/`2/`
+const PATH_PREFIX_PAIRS: &[(&str, &str)] = &[
+    ("a/", "b/"),
+    ("i/", "w/"),
+    ("c/", "w/"),
+    ("c/", "i/"),
+    ("o/", "w/"),
+    ("1/", "2/"),
+];
+
+/// Process a git diff from `source` and output the minimized version
+#[allow(clippy::too_many_arguments)]
 pub fn process_git_diff(
+    source: &DiffSource,
     save_mode: bool,
     save_path: Option<String>,
     context: Option<String>,
-) -> io::Result<()> {
-    let mut input = String::new();
-    io::stdin().read_to_string(&mut input)?;
-
+    exclude: &[String],
+    split: Option<Split>,
+    config: &DiffConfig,
+    progress: &Progress,
+) -> Result<()> {
+    let exclude = ExcludeMatcher::from_config(config, exclude)?;
+    let input = source.read()?;
+    check_diff_input(&input)?;
+
+    let path = save_path.as_deref().unwrap_or(&config.output_dir);
     if save_mode {
-        let path = save_path.as_deref().unwrap_or("llm/diff");
-        super::save::save_diff_chunks(&input, path, context.as_deref())?;
-    } else {
-        let minimized_diff = minimize_diff(&input);
-        print!("{}", minimized_diff);
+        super::save::save_diff_chunks_with_progress(
+            &input,
+            path,
+            context.as_deref(),
+            config,
+            &exclude,
+            Some(source),
+            progress,
+        )
+        .context("failed to save diff chunks")?
+        // Output paths in machine-readable format to stdout
+        .print();
+    }
+    if let Some(split) = split {
+        let files = minimize_files(&input, config, &exclude);
+        let output_dir = super::save::resolve_review_dir(path);
+        let bundles =
+            write_bundles(&files, split, &output_dir, config).context("failed to write bundles")?;
+        for bundle in bundles {
+            println!("bundle: {}", bundle.display());
+        }
+    } else if !save_mode {
+        let files = minimize_files(&input, config, &exclude);
+        if config.with_header {
+            let shown = files
+                .iter()
+                .filter(|file| file.omitted != Some("excluded"))
+                .map(|file| &file.file_change);
+            println!(
+                "{}",
+                DiffHeader::collect(shown, Some(source), context.as_deref()).render()
+            );
+        }
+        print!("{}", format_minimized_files(files.iter(), config));
     }
 
     Ok(())
@@ -26,113 +83,347 @@ pub fn process_git_diff(
 
 /// Minimize a git diff by summarizing large files and removing excessive empty lines
 pub fn minimize_diff(diff_content: &str) -> String {
-    let mut result = String::new();
-    let file_changes = parse_git_diff(diff_content);
+    minimize_diff_with_config(diff_content, &DiffConfig::default())
+}
+
+/// Minimize a git diff using the thresholds from `config`
+pub fn minimize_diff_with_config(diff_content: &str, config: &DiffConfig) -> String {
+    minimize_diff_with_exclude(diff_content, config, &ExcludeMatcher::default())
+}
+
+/// Minimize a git diff, reducing files matched by `exclude` to a one-line summary
+pub fn minimize_diff_with_exclude(
+    diff_content: &str,
+    config: &DiffConfig,
+    exclude: &ExcludeMatcher,
+) -> String {
+    let files = minimize_files(diff_content, config, exclude);
+    format_minimized_files(files.iter(), config)
+}
+
+/// The minimized output of one file
+#[derive(Debug)]
+pub struct MinimizedFile {
+    pub file_change: FileChange,
+    /// The file's part of the output, ending with a blank line
+    pub text: String,
+    /// Why the file is not shown in full, for the omitted files footer
+    pub omitted: Option<&'static str>,
+}
 
-    for file_change in file_changes {
-        match file_change.change_type {
-            ChangeType::Deleted => {
+/// Minimize each file of a git diff separately
+pub fn minimize_files(
+    diff_content: &str,
+    config: &DiffConfig,
+    exclude: &ExcludeMatcher,
+) -> Vec<MinimizedFile> {
+    let max_empty = config.max_consecutive_empty_lines;
+
+    let file_changes = parse_git_diff_with_config(diff_content, config);
+    let _span = info_span!("minimize").entered();
+    file_changes
+        .into_iter()
+        .map(|file_change| {
+            let (mut text, omitted) = if exclude.is_excluded(&file_change) {
+                (format_excluded_file_summary(&file_change), Some("excluded"))
+            } else if let ChangeType::Deleted = file_change.change_type {
                 // For deleted files, only show metadata
-                result.push_str(&format_deleted_file_summary(&file_change));
-            }
-            ChangeType::Added => {
-                if file_change.is_large {
-                    // Strategy 1: For large added files, only show metadata
-                    result.push_str(&format_large_file_summary(&file_change));
-                } else {
-                    // For smaller added files, show the diff but remove excessive empty lines
-                    result.push_str(&format_regular_file_diff(&file_change));
-                }
-            }
-            _ => {
-                // For modified and renamed files, apply the original logic
-                if file_change.is_large {
-                    result.push_str(&format_large_file_summary(&file_change));
-                } else {
-                    result.push_str(&format_regular_file_diff(&file_change));
+                (format_deleted_file_summary(&file_change), Some("deleted"))
+            } else if file_change.is_large {
+                // For large added, modified and renamed files, only show metadata
+                let mut summary = format_large_file_summary(&file_change);
+                if config.signatures_in_summary {
+                    summary.push_str(&format_signature_summary(&file_change));
                 }
+                (summary, Some("large"))
+            } else {
+                // Show the diff without ignored changes and excessive empty lines
+                let (lines, suppressed) = suppress_noise(&file_change, config);
+                (
+                    format_file_diff_lines(&file_change, &lines, suppressed, max_empty),
+                    file_change.is_binary().then_some("binary"),
+                )
+            };
+            text.push('\n');
+            MinimizedFile {
+                file_change,
+                text,
+                omitted,
             }
-        }
-        result.push('\n');
-    }
+        })
+        .collect()
+}
 
+/// Join minimized files, followed by the omitted files footer if enabled
+pub fn format_minimized_files<'a>(
+    files: impl Iterator<Item = &'a MinimizedFile> + Clone,
+    config: &DiffConfig,
+) -> String {
+    let mut result: String = files.clone().map(|file| file.text.as_str()).collect();
+    let omitted: Vec<_> = files
+        .filter_map(|file| Some((&file.file_change, file.omitted?)))
+        .collect();
+    if config.omitted_footer && !omitted.is_empty() {
+        result.push_str(&format_omitted_footer(&omitted));
+    }
     result
 }
 
+/// List files that were not shown in full, so the reader knows they exist
+fn format_omitted_footer(omitted: &[(&FileChange, &str)]) -> String {
+    let mut footer = String::from("## Omitted or summarized files\n");
+    for (file_change, reason) in omitted {
+        let (added, removed) = file_change.line_counts();
+        footer.push_str(&format!(
+            "- {} ({}, +{} -{})\n",
+            file_change.display_path(),
+            reason,
+            added,
+            removed
+        ));
+    }
+    footer
+}
+
 /// Parse git diff content into structured file changes
 pub fn parse_git_diff(diff_content: &str) -> Vec<FileChange> {
+    parse_git_diff_with_config(diff_content, &DiffConfig::default())
+}
+
+/// Parse git diff content, flagging files as large per the `config` thresholds
+pub fn parse_git_diff_with_config(diff_content: &str, config: &DiffConfig) -> Vec<FileChange> {
+    let _span = info_span!("diff_parse").entered();
     let mut file_changes = Vec::new();
     let lines: Vec<&str> = diff_content.lines().collect();
     let mut i = 0;
 
     while i < lines.len() {
-        if let Some(file_change) = parse_file_change(&lines, &mut i) {
+        if let Some(file_change) = parse_file_change(&lines, &mut i, config) {
             file_changes.push(file_change);
         } else {
             i += 1;
         }
     }
 
+    if file_changes.is_empty() {
+        file_changes.extend(parse_plain_unified_diff(&lines, config));
+    }
+
+    if config.order_by == FileOrder::Path {
+        sort_file_changes(&mut file_changes);
+    }
     file_changes
 }
 
-fn parse_file_change(lines: &[&str], index: &mut usize) -> Option<FileChange> {
-    let diff_header_re = Regex::new(r"^diff --git a/(.*?) b/(.*?)$").unwrap();
+/// Sort by path with deletions last; the sort is stable so repeated paths
+/// keep their input order
+fn sort_file_changes(file_changes: &mut [FileChange]) {
+    file_changes.sort_by(|a, b| {
+        let is_deleted = |change: &FileChange| matches!(change.change_type, ChangeType::Deleted);
+        (is_deleted(a), a.display_path()).cmp(&(is_deleted(b), b.display_path()))
+    });
+}
 
+/// Treat plain `diff -u` output (no `diff --git` header) as a single file,
+/// taking its paths from the `---`/`+++` lines
+fn parse_plain_unified_diff(lines: &[&str], config: &DiffConfig) -> Option<FileChange> {
+    let start = find_plain_unified_header(lines)?;
+    // `diff -u` appends a tab and the modification time to each path
+    let path_of = |line: &str| {
+        let path = line[4..].split('\t').next().unwrap_or_default().trim_end();
+        (path != "/dev/null").then(|| path.to_string())
+    };
+    let old_path = path_of(lines[start]);
+    let new_path = path_of(lines[start + 1]);
+    let change_type = match (&old_path, &new_path) {
+        (None, _) => ChangeType::Added,
+        (_, None) => ChangeType::Deleted,
+        _ => ChangeType::Modified,
+    };
+
+    let content_lines: Vec<String> = lines[start..].iter().map(|l| l.to_string()).collect();
+    let total_changes = lines[start + 2..]
+        .iter()
+        .filter(|line| {
+            (line.starts_with('+') && !line.starts_with("+++"))
+                || (line.starts_with('-') && !line.starts_with("---"))
+        })
+        .count();
+    let is_large = total_changes > config.large_file_changes_threshold
+        || content_lines.len() > config.large_file_lines_threshold;
+
+    Some(FileChange {
+        old_path: old_path.clone().or_else(|| new_path.clone()),
+        new_path: new_path.or(old_path),
+        quoted_old_path: None,
+        quoted_new_path: None,
+        change_type,
+        content_lines,
+        is_large,
+    })
+}
+
+/// Split the old and new paths out of a `diff --git` header, stripping
+/// whichever prefix pair git used, or none with `diff.noprefix`.
+///
+/// Headers are ambiguous when paths contain spaces; a split leaving the same
+/// path on both sides wins, and renames are corrected later from their
+/// prefix-free `rename from`/`rename to` lines. Quoted paths are unambiguous
+/// and decoded.
+pub(crate) fn parse_diff_header(line: &str) -> Option<(GitPath, GitPath)> {
+    let rest = line.strip_prefix("diff --git ")?;
+    if let Some((old, new)) = split_quoted_header(rest) {
+        return Some(
+            PATH_PREFIX_PAIRS
+                .iter()
+                .find_map(|(old_prefix, new_prefix)| {
+                    Some((old.strip_prefix(old_prefix)?, new.strip_prefix(new_prefix)?))
+                })
+                .unwrap_or((old, new)),
+        );
+    }
+    let unquoted = |path: &str| GitPath {
+        path: path.to_string(),
+        quoted: None,
+    };
+
+    for (old_prefix, new_prefix) in PATH_PREFIX_PAIRS {
+        let Some(paths) = rest.strip_prefix(old_prefix) else {
+            continue;
+        };
+        let separator = format!(" {}", new_prefix);
+        let splits: Vec<usize> = paths.match_indices(&separator).map(|(i, _)| i).collect();
+        let split = splits
+            .iter()
+            .copied()
+            .find(|&i| paths[..i] == paths[i + separator.len()..])
+            .or(splits.first().copied());
+        if let Some(i) = split {
+            return Some((
+                unquoted(&paths[..i]),
+                unquoted(&paths[i + separator.len()..]),
+            ));
+        }
+    }
+
+    let mid = rest.len() / 2;
+    if rest.len() % 2 == 1
+        && rest.is_char_boundary(mid)
+        && rest[mid..].starts_with(' ')
+        && rest[..mid] == rest[mid + 1..]
+    {
+        return Some((unquoted(&rest[..mid]), unquoted(&rest[mid + 1..])));
+    }
+    let (old, new) = rest.split_once(' ')?;
+    Some((unquoted(old), unquoted(new)))
+}
+
+/// Split a header where git quoted either path: `"a/x" "b/y"`, `"a/x" b/y`
+/// or `a/x "b/y"`
+fn split_quoted_header(rest: &str) -> Option<(GitPath, GitPath)> {
+    if let Some((_, consumed)) = unquote(rest) {
+        let new = rest[consumed..].strip_prefix(' ')?;
+        return Some((GitPath::parse(&rest[..consumed]), GitPath::parse(new)));
+    }
+    if !rest.ends_with('"') {
+        return None;
+    }
+    rest.match_indices(" \"").find_map(|(i, _)| {
+        let new = &rest[i + 1..];
+        matches!(unquote(new), Some((_, consumed)) if consumed == new.len())
+            .then(|| (GitPath::parse(&rest[..i]), GitPath::parse(new)))
+    })
+}
+
+fn parse_file_change(lines: &[&str], index: &mut usize, config: &DiffConfig) -> Option<FileChange> {
     if *index >= lines.len() {
         return None;
     }
 
     // Look for diff header
-    let line = lines[*index];
-    if let Some(captures) = diff_header_re.captures(line) {
-        let old_path = captures.get(1).map(|m| m.as_str().to_string());
-        let new_path = captures.get(2).map(|m| m.as_str().to_string());
+    let (old, new) = parse_diff_header(lines[*index])?;
+    let (mut old_path, mut quoted_old_path) = (old.path, old.quoted);
+    let (mut new_path, mut quoted_new_path) = (new.path, new.quoted);
+    *index += 1;
+
+    // Parse file metadata and determine change type
+    let mut change_type = ChangeType::Modified;
+    let mut content_lines = Vec::new();
+    let mut total_changes = 0;
+    let mut in_hunks = false;
+
+    // Collect all lines until next diff or end
+    while *index < lines.len() && !lines[*index].starts_with("diff --git") {
+        let line = lines[*index];
+        in_hunks |= line.starts_with("@@");
+
+        // Determine change type from file mode lines
+        if line.starts_with("new file mode") {
+            change_type = ChangeType::Added;
+        } else if line.starts_with("deleted file mode") {
+            change_type = ChangeType::Deleted;
+        } else if let Some(path) = line.strip_prefix("rename from ") {
+            change_type = ChangeType::Renamed;
+            GitPath {
+                path: old_path,
+                quoted: quoted_old_path,
+            } = GitPath::parse(path);
+        } else if let Some(path) = line.strip_prefix("rename to ") {
+            change_type = ChangeType::Renamed;
+            GitPath {
+                path: new_path,
+                quoted: quoted_new_path,
+            } = GitPath::parse(path);
+        }
 
-        *index += 1;
+        // Count actual content changes
+        if (line.starts_with('+') && !line.starts_with("+++"))
+            || (line.starts_with('-') && !line.starts_with("---"))
+        {
+            total_changes += 1;
+        }
 
-        // Parse file metadata and determine change type
-        let mut change_type = ChangeType::Modified;
-        let mut content_lines = Vec::new();
-        let mut total_changes = 0;
-
-        // Collect all lines until next diff or end
-        while *index < lines.len() && !lines[*index].starts_with("diff --git") {
-            let line = lines[*index];
-
-            // Determine change type from file mode lines
-            if line.starts_with("new file mode") {
-                change_type = ChangeType::Added;
-            } else if line.starts_with("deleted file mode") {
-                change_type = ChangeType::Deleted;
-            } else if line.starts_with("rename from") || line.starts_with("rename to") {
-                change_type = ChangeType::Renamed;
-            }
+        content_lines.push(normalize_file_header_line(
+            line, in_hunks, &old_path, &new_path,
+        ));
+        *index += 1;
+    }
 
-            // Count actual content changes
-            if (line.starts_with('+') && !line.starts_with("+++"))
-                || (line.starts_with('-') && !line.starts_with("---"))
-            {
-                total_changes += 1;
-            }
+    // Determine if file is "large" (default: more than 100 changes or 500 total lines)
+    let is_large = total_changes > config.large_file_changes_threshold
+        || content_lines.len() > config.large_file_lines_threshold;
+
+    Some(FileChange {
+        old_path: Some(old_path),
+        new_path: Some(new_path),
+        quoted_old_path,
+        quoted_new_path,
+        change_type,
+        content_lines,
+        is_large,
+    })
+}
 
-            content_lines.push(line.to_string());
-            *index += 1;
+/// Rewrite `---`/`+++` lines to the default `a/`/`b/` prefixes so chunks
+/// read the same whatever prefix configuration produced the diff. The tab
+/// git ends these lines with for paths containing spaces is kept, since
+/// `git apply` needs it to find where the path ends.
+fn normalize_file_header_line(
+    line: &str,
+    in_hunks: bool,
+    old_path: &str,
+    new_path: &str,
+) -> String {
+    if !in_hunks && line != "--- /dev/null" && line != "+++ /dev/null" {
+        let tab = if line.ends_with('\t') { "\t" } else { "" };
+        if line.starts_with("--- ") && !line.starts_with("--- \"") {
+            return format!("--- a/{}{}", old_path, tab);
+        }
+        if line.starts_with("+++ ") && !line.starts_with("+++ \"") {
+            return format!("+++ b/{}{}", new_path, tab);
         }
-
-        // Determine if file is "large" (heuristic: more than 100 changes or 500 total lines)
-        let is_large = total_changes > 100 || content_lines.len() > 500;
-
-        return Some(FileChange {
-            old_path,
-            new_path,
-            change_type,
-            content_lines,
-            is_large,
-        });
     }
-
-    None
+    line.to_string()
 }
 
 /// Format a large file change as a summary
@@ -169,8 +460,8 @@ pub fn format_deleted_file_summary(file_change: &FileChange) -> String {
     format!("Deleted file: {}\n", path)
 }
 
-/// Format a regular file change with full diff
-pub fn format_regular_file_diff(file_change: &FileChange) -> String {
+/// Format a file matched by the exclude patterns as a summary
+pub fn format_excluded_file_summary(file_change: &FileChange) -> String {
     let unknown_path = "unknown".to_string();
     let path = file_change
         .new_path
@@ -178,14 +469,43 @@ pub fn format_regular_file_diff(file_change: &FileChange) -> String {
         .or(file_change.old_path.as_ref())
         .unwrap_or(&unknown_path);
 
-    let mut result = format!(
-        "diff --git a/{} b/{}\n",
-        file_change.old_path.as_ref().unwrap_or(path),
-        file_change.new_path.as_ref().unwrap_or(path)
-    );
+    format!("Excluded file: {}\n", path)
+}
+
+/// Format a regular file change with full diff
+pub fn format_regular_file_diff(file_change: &FileChange) -> String {
+    format_file_diff(
+        file_change,
+        DiffConfig::default().max_consecutive_empty_lines,
+    )
+}
+
+fn format_file_diff(file_change: &FileChange, max_empty: usize) -> String {
+    format_file_diff_lines(
+        file_change,
+        &file_change.content_lines,
+        Suppressed::default(),
+        max_empty,
+    )
+}
+
+/// Format `file_change` with `lines` in place of its content, noting any
+/// suppressed lines under the `diff --git` header
+fn format_file_diff_lines(
+    file_change: &FileChange,
+    lines: &[String],
+    suppressed: Suppressed,
+    max_empty: usize,
+) -> String {
+    let mut result = format!("{}\n", file_change.git_header());
+
+    if let Some(note) = suppressed.note() {
+        result.push_str(&note);
+        result.push('\n');
+    }
 
     // Remove excessive empty lines while preserving structure
-    let cleaned_content = remove_excessive_empty_lines(&file_change.content_lines);
+    let cleaned_content = remove_excessive_empty_lines(lines, max_empty);
 
     for line in cleaned_content {
         result.push_str(&line);
@@ -195,8 +515,8 @@ pub fn format_regular_file_diff(file_change: &FileChange) -> String {
     result
 }
 
-/// Remove excessive consecutive empty lines (keep max 2)
-pub fn remove_excessive_empty_lines(lines: &[String]) -> Vec<String> {
+/// Remove consecutive empty lines beyond `max_empty`
+pub fn remove_excessive_empty_lines(lines: &[String], max_empty: usize) -> Vec<String> {
     let mut result = Vec::new();
     let mut consecutive_empty = 0;
 
@@ -205,8 +525,7 @@ pub fn remove_excessive_empty_lines(lines: &[String]) -> Vec<String> {
 
         if is_empty {
             consecutive_empty += 1;
-            // Keep at most 2 consecutive empty lines
-            if consecutive_empty <= 2 {
+            if consecutive_empty <= max_empty {
                 result.push(line.clone());
             }
         } else {
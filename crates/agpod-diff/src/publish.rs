@@ -0,0 +1,159 @@
+//! Publishing REVIEW.md comments to a GitHub pull request via `gh api`:
+//! one file-level comment per commented file plus a review summarizing the
+//! status counts
+
+use super::save::{parse_review_entries, resolve_review_dir};
+use agpod_core::{Error, ReviewError};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+/// gh's exit status when a command needs `gh auth login` first
+const GH_AUTH_REQUIRED: i32 = 4;
+
+/// What `publish-review` posts: `body` and `event` as the review, and each
+/// of `comments` as a file-level review comment
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct ReviewPayload {
+    pub body: String,
+    pub event: &'static str,
+    pub comments: Vec<ReviewPayloadComment>,
+}
+
+/// One file's comment block, posted with `subject_type: "file"` so it is
+/// not tied to a line of a diff whose base may differ from the PR's
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct ReviewPayloadComment {
+    pub path: String,
+    pub body: String,
+    pub subject_type: &'static str,
+}
+
+/// Build the review from REVIEW.md `content`: a summary of the status
+/// counts plus one comment per file with comments, in document order
+pub fn build_review_payload(content: &str) -> ReviewPayload {
+    let entries = parse_review_entries(content);
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for (_, entry) in &entries {
+        *counts.entry(entry.status.as_str()).or_default() += 1;
+    }
+    let counts: Vec<String> = counts
+        .iter()
+        .map(|(status, count)| format!("{} {}", count, status))
+        .collect();
+    let files = match entries.len() {
+        1 => "1 file".to_string(),
+        count => format!("{} files", count),
+    };
+    let body = if counts.is_empty() {
+        format!("Review status: {}", files)
+    } else {
+        format!("Review status: {} ({})", files, counts.join(", "))
+    };
+
+    let comments = entries
+        .into_iter()
+        .filter(|(_, entry)| !entry.comments.is_empty())
+        .map(|(path, entry)| ReviewPayloadComment {
+            path,
+            body: entry.comments,
+            subject_type: "file",
+        })
+        .collect();
+    ReviewPayload {
+        body,
+        event: "COMMENT",
+        comments,
+    }
+}
+
+/// Publish the comments of the REVIEW.md saved under `output_dir` on pull
+/// request `pr`, or print the payload when `dry_run` is set; a dry run
+/// prints it even without comments
+pub fn publish_review(output_dir: &str, pr: u64, dry_run: bool) -> Result<()> {
+    let review_path = resolve_review_dir(output_dir).join("REVIEW.md");
+    let content = fs::read_to_string(&review_path).map_err(|source| ReviewError::ReadReview {
+        path: review_path.clone(),
+        source,
+    })?;
+    let payload = build_review_payload(&content);
+    if dry_run {
+        println!("{}", serde_json::to_string_pretty(&payload)?);
+        return Ok(());
+    }
+    if payload.comments.is_empty() {
+        return Err(ReviewError::NoReviewComments { path: review_path }.into());
+    }
+
+    // file-level comments must name the commit they were made on
+    let pull = format!("repos/{{owner}}/{{repo}}/pulls/{}", pr);
+    let commit_id = gh_api(&[&pull, "--jq", ".head.sha"], None)?
+        .trim()
+        .to_string();
+    for comment in &payload.comments {
+        let request = json!({
+            "commit_id": commit_id,
+            "path": comment.path,
+            "body": comment.body,
+            "subject_type": comment.subject_type,
+        });
+        gh_api_post(&format!("{}/comments", pull), &request.to_string())
+            .with_context(|| format!("failed to comment on {}", comment.path))?;
+    }
+    let review = json!({ "body": payload.body, "event": payload.event });
+    let response = gh_api_post(&format!("{}/reviews", pull), &review.to_string())?;
+    let response: serde_json::Value =
+        serde_json::from_str(&response).context("unexpected response from gh api")?;
+    match response.get("html_url").and_then(|url| url.as_str()) {
+        Some(url) => println!("review: {}", url),
+        None => println!("review: published on #{}", pr),
+    }
+    Ok(())
+}
+
+/// POST `payload` to `endpoint` with `gh api`, returning the response body
+fn gh_api_post(endpoint: &str, payload: &str) -> Result<String, Error> {
+    gh_api(
+        &["--method", "POST", endpoint, "--input", "-"],
+        Some(payload),
+    )
+}
+
+/// Run `gh api` with `args`, writing `input` to its stdin, and return its
+/// stdout
+fn gh_api(args: &[&str], input: Option<&str>) -> Result<String, Error> {
+    let mut child = Command::new("gh")
+        .arg("api")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|source| match source.kind() {
+            io::ErrorKind::NotFound => ReviewError::GhNotFound,
+            _ => ReviewError::RunGh { source },
+        })?;
+    // dropping stdin closes it, so gh does not wait for input it won't get
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(input.unwrap_or_default().as_bytes())
+            .map_err(|source| ReviewError::RunGh { source })?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|source| ReviewError::RunGh { source })?;
+    if output.status.code() == Some(GH_AUTH_REQUIRED) {
+        return Err(ReviewError::GhNotAuthenticated.into());
+    }
+    if !output.status.success() {
+        return Err(ReviewError::GhFailed {
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        }
+        .into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
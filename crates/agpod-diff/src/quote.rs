@@ -0,0 +1,104 @@
+//! Paths git writes C-style quoted, as with `core.quotePath` (the default)
+//! for non-ASCII names and always for names with control characters,
+//! quotes or backslashes: `"\346\226\207.txt"`
+
+/// A path from a diff: decoded for display and REVIEW.md keys, plus git's
+/// quoted form when git quoted it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct GitPath {
+    pub path: String,
+    pub quoted: Option<String>,
+}
+
+impl GitPath {
+    /// Parse one path as git wrote it, quoted or not
+    pub fn parse(text: &str) -> Self {
+        match unquote(text) {
+            Some((bytes, consumed)) if consumed == text.len() => Self {
+                path: path_from_bytes(&bytes),
+                quoted: Some(text.to_string()),
+            },
+            _ => Self {
+                path: text.to_string(),
+                quoted: None,
+            },
+        }
+    }
+
+    /// Drop a leading `prefix` such as `a/`, inside the quotes if quoted
+    pub fn strip_prefix(&self, prefix: &str) -> Option<Self> {
+        Some(Self {
+            path: self.path.strip_prefix(prefix)?.to_string(),
+            quoted: match &self.quoted {
+                Some(quoted) => Some(format!("\"{}", quoted[1..].strip_prefix(prefix)?)),
+                None => None,
+            },
+        })
+    }
+}
+
+/// `path` with `prefix` in the form git writes it in headers: inside the
+/// quotes when `quoted` is set
+pub(crate) fn prefixed(prefix: &str, path: &str, quoted: Option<&str>) -> String {
+    match quoted {
+        Some(quoted) => format!("\"{}{}", prefix, &quoted[1..]),
+        None => format!("{}{}", prefix, path),
+    }
+}
+
+/// Decode a quoted string at the start of `text`, returning its bytes and
+/// the length of the quoted form
+pub(crate) fn unquote(text: &str) -> Option<(Vec<u8>, usize)> {
+    let bytes = text.as_bytes();
+    if bytes.first() != Some(&b'"') {
+        return None;
+    }
+    let mut decoded = Vec::new();
+    let mut i = 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => return Some((decoded, i + 1)),
+            b'\\' => {
+                let escape = *bytes.get(i + 1)?;
+                i += 2;
+                let byte = match escape {
+                    b'a' => 0x07,
+                    b'b' => 0x08,
+                    b't' => b'\t',
+                    b'n' => b'\n',
+                    b'v' => 0x0b,
+                    b'f' => 0x0c,
+                    b'r' => b'\r',
+                    b'"' | b'\\' => escape,
+                    b'0'..=b'3' => {
+                        let digits = bytes.get(i..i + 2)?;
+                        if !digits.iter().all(|digit| (b'0'..=b'7').contains(digit)) {
+                            return None;
+                        }
+                        i += 2;
+                        (escape - b'0') << 6 | (digits[0] - b'0') << 3 | (digits[1] - b'0')
+                    }
+                    _ => return None,
+                };
+                decoded.push(byte);
+            }
+            byte => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    None
+}
+
+/// A display path for raw path bytes: UTF-8 as-is, other bytes as `%XX`
+pub(crate) fn path_from_bytes(bytes: &[u8]) -> String {
+    let mut path = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        path.push_str(chunk.valid());
+        for byte in chunk.invalid() {
+            path.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    path
+}
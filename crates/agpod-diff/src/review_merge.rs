@@ -0,0 +1,193 @@
+//! Merging two edited copies of REVIEW.md entry by entry, so regenerating
+//! and reviewing on two branches does not end in a whole-file conflict
+
+use super::save::{
+    parse_existing_review, parse_review_entries, parse_user_sections, push_review_entry,
+    push_user_block, write_file, ReviewEntry,
+};
+use agpod_core::{Error, ReviewError};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Put above each side's comments when both sides edited them
+pub const OURS_MARKER: &str = "<!-- agpod merge: ours -->";
+pub const THEIRS_MARKER: &str = "<!-- agpod merge: theirs -->";
+
+/// A merged REVIEW.md
+#[derive(Debug, PartialEq, Eq)]
+pub struct MergedReview {
+    pub content: String,
+    /// Files whose comments were edited on both sides, now kept one after
+    /// the other under [`OURS_MARKER`] and [`THEIRS_MARKER`]
+    pub both_commented: Vec<String>,
+}
+
+/// Merge `ours` and `theirs`, optionally against their common ancestor
+/// `base`.
+///
+/// - A file's status, hash and chunk come from the side that changed them
+///   since `base`, or else from the newer status: the later
+///   `reviewed@YYYY-MM-DD`, and any review over `outdated` or `pending`.
+/// - Comments changed on one side only are taken from that side; edited on
+///   both, both blocks are kept with attribution markers.
+/// - A file dropped on one side is dropped unless the other side changed
+///   it since `base`; without `base` every file is kept.
+/// - Everything before the first file section comes from `ours`; notes
+///   after the file sections from both.
+pub fn merge_reviews(base: Option<&str>, ours: &str, theirs: &str) -> MergedReview {
+    let base_entries = base.map(parse_existing_review).unwrap_or_default();
+    let ours_entries = parse_review_entries(ours);
+    let theirs_entries = parse_review_entries(theirs);
+    let ours_by_path: HashMap<&str, &ReviewEntry> = ours_entries
+        .iter()
+        .map(|(path, entry)| (path.as_str(), entry))
+        .collect();
+    let theirs_by_path: HashMap<&str, &ReviewEntry> = theirs_entries
+        .iter()
+        .map(|(path, entry)| (path.as_str(), entry))
+        .collect();
+    // kept unless the other side dropped a file this side left untouched
+    let keep = |path: &str, entry: &ReviewEntry| {
+        base_entries
+            .get(path)
+            .is_none_or(|base_entry| base_entry != entry)
+    };
+
+    let mut content = ours[..files_start(ours)].to_string();
+    let mut both_commented = Vec::new();
+    for (path, entry) in &ours_entries {
+        let merged = match theirs_by_path.get(path.as_str()) {
+            Some(theirs) => {
+                let (merged, conflict) =
+                    merge_entry(base_entries.get(path.as_str()), entry, theirs);
+                if conflict {
+                    both_commented.push(path.clone());
+                }
+                merged
+            }
+            None if keep(path, entry) => entry.clone(),
+            None => continue,
+        };
+        push_review_entry(&mut content, path, &merged);
+    }
+    for (path, entry) in &theirs_entries {
+        if !ours_by_path.contains_key(path.as_str()) && keep(path, entry) {
+            push_review_entry(&mut content, path, entry);
+        }
+    }
+
+    let ours_notes = parse_user_sections(ours).after_files;
+    let theirs_notes = parse_user_sections(theirs).after_files;
+    for block in &ours_notes {
+        push_user_block(&mut content, block);
+    }
+    for block in theirs_notes
+        .iter()
+        .filter(|block| !ours_notes.contains(block))
+    {
+        push_user_block(&mut content, block);
+    }
+
+    MergedReview {
+        content,
+        both_commented,
+    }
+}
+
+/// Merge one file's entries; also returns whether both comment blocks were
+/// kept
+fn merge_entry(
+    base: Option<&ReviewEntry>,
+    ours: &ReviewEntry,
+    theirs: &ReviewEntry,
+) -> (ReviewEntry, bool) {
+    let unchanged = |entry: &ReviewEntry| {
+        base.is_some_and(|base| base.status == entry.status && base.hash == entry.hash)
+    };
+    let mut merged = if unchanged(ours) {
+        theirs.clone()
+    } else if unchanged(theirs) || status_rank(&ours.status) >= status_rank(&theirs.status) {
+        ours.clone()
+    } else {
+        theirs.clone()
+    };
+
+    let comments_unchanged =
+        |entry: &ReviewEntry| base.is_some_and(|base| base.comments == entry.comments);
+    let mut conflict = false;
+    merged.comments = if ours.comments == theirs.comments || comments_unchanged(ours) {
+        theirs.comments.clone()
+    } else if comments_unchanged(theirs) || theirs.comments.is_empty() {
+        ours.comments.clone()
+    } else if ours.comments.is_empty() {
+        theirs.comments.clone()
+    } else {
+        conflict = true;
+        format!(
+            "{}\n{}\n\n{}\n{}",
+            OURS_MARKER, ours.comments, THEIRS_MARKER, theirs.comments
+        )
+    };
+    (merged, conflict)
+}
+
+/// Orders statuses by recency: any review over `outdated` and `pending`,
+/// later reviews over earlier ones
+fn status_rank(status: &str) -> (bool, &str) {
+    match status.strip_prefix("reviewed@") {
+        Some(date) => (true, date),
+        None => (status.starts_with("reviewed"), ""),
+    }
+}
+
+/// Byte offset of the first file section in REVIEW.md `content`
+fn files_start(content: &str) -> usize {
+    let mut offset = 0;
+    let mut heading = None;
+    for line in content.split_inclusive('\n') {
+        if line.starts_with("## ") {
+            heading = Some(offset);
+        } else if line.starts_with("- meta:hash: ") {
+            if let Some(heading) = heading {
+                return heading;
+            }
+        }
+        offset += line.len();
+    }
+    content.len()
+}
+
+/// Merge the REVIEW.md files at `ours` and `theirs` (and `base`), writing
+/// the result to `output` or stdout
+pub fn merge_review_files(
+    base: Option<&Path>,
+    ours: &Path,
+    theirs: &Path,
+    output: Option<&Path>,
+) -> Result<MergedReview, Error> {
+    let read = |path: &Path| {
+        fs::read_to_string(path).map_err(|source| ReviewError::ReadReview {
+            path: path.to_path_buf(),
+            source,
+        })
+    };
+    let base = base.map(read).transpose()?;
+    let merged = merge_reviews(base.as_deref(), &read(ours)?, &read(theirs)?);
+    match output {
+        Some(path) => {
+            write_file(path, &merged.content).map_err(|source| ReviewError::WriteReview {
+                path: path.to_path_buf(),
+                source,
+            })?
+        }
+        None => print!("{}", merged.content),
+    }
+    if !merged.both_commented.is_empty() {
+        eprintln!(
+            "kept both sides' comments for: {}",
+            merged.both_commented.join(", ")
+        );
+    }
+    Ok(merged)
+}
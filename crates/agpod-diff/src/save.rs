@@ -1,21 +1,36 @@
 //! Diff chunk saving and review tracking functionality
 
-use super::processor::parse_git_diff;
-use regex::Regex;
+use super::exclude::ExcludeMatcher;
+use super::header::{DiffHeader, HEADER_END, HEADER_START};
+use super::lock::DirLock;
+use super::processor::{parse_git_diff_with_config, remove_excessive_empty_lines};
+use super::source::DiffSource;
+use super::types::ChangeType;
+use agpod_core::{ChunkFormat, DiffConfig, Error, IoError, Progress, ReviewError};
 use std::collections::hash_map::DefaultHasher;
 use std::env;
 use std::fs;
 use std::hash::{Hash, Hasher};
 use std::io::{self, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
 use std::process::Command;
+use std::time::Duration;
+use tracing::info_span;
 
 /// Entry in REVIEW.md tracking file
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ReviewEntry {
     pub hash: String,
     pub status: String,
     pub comments: String,
+    /// Chunk file name from `meta:diff_chunk`, relative to the review directory
+    pub diff_chunk: Option<String>,
+    /// Whether the chunk was replaced by a summary (`meta:truncated`)
+    pub truncated: bool,
+    /// Path the entry was migrated from after a rename (`meta:renamed_from`)
+    pub renamed_from: Option<String>,
+    /// The path as git quoted it, for non-ASCII paths (`meta:quoted_path`)
+    pub quoted_path: Option<String>,
 }
 
 /// Get the git repository name or current directory name as project identifier
@@ -57,11 +72,83 @@ pub fn compute_file_hash(content: &str) -> String {
     format!("{:x}", hasher.finish())
 }
 
+/// Hash of a chunk from its first hunk header on, which leaves out the
+/// paths so a renamed file can be compared with its previous chunk
+pub fn compute_hunks_hash(chunk_content: &str) -> String {
+    let hunks = chunk_content
+        .find("\n@@")
+        .map_or("", |start| &chunk_content[start + 1..]);
+    compute_file_hash(hunks)
+}
+
+/// Rough token count for `content`, at about four bytes per token
+pub fn estimate_tokens(content: &str) -> usize {
+    content.len().div_ceil(4)
+}
+
+/// Reduce a chunk to its file header and hunk headers, each followed by its
+/// line counts, for chunks over `max_tokens_per_chunk`
+pub fn summarize_chunk(chunk_content: &str, tokens: usize, limit: usize) -> String {
+    let mut lines = chunk_content.lines().peekable();
+    let mut summary = String::new();
+    while let Some(line) = lines.next_if(|line| !line.starts_with("@@")) {
+        summary.push_str(line);
+        summary.push('\n');
+    }
+    summary.push_str(&format!(
+        "# truncated: ~{} tokens over max_tokens_per_chunk ({}); hunk headers and line counts only\n",
+        tokens, limit
+    ));
+
+    while let Some(header) = lines.next() {
+        let (mut added, mut removed) = (0, 0);
+        while let Some(line) = lines.next_if(|line| !line.starts_with("@@")) {
+            if line.starts_with('+') {
+                added += 1;
+            } else if line.starts_with('-') {
+                removed += 1;
+            }
+        }
+        summary.push_str(header);
+        summary.push('\n');
+        summary.push_str(&format!("# +{} -{}\n", added, removed));
+    }
+    summary
+}
+
+/// Wrap a chunk in a minimal mbox header so it can be applied with `git am`
+pub fn format_patch_chunk(change_type: &ChangeType, path: &str, chunk_content: &str) -> String {
+    let verb = match change_type {
+        ChangeType::Added => "add",
+        ChangeType::Deleted => "delete",
+        ChangeType::Modified => "modify",
+        ChangeType::Renamed => "rename",
+    };
+    format!(
+        "From 0000000000000000000000000000000000000000 Mon Sep 17 00:00:00 2001\n\
+         From: agpod <agpod@localhost>\n\
+         Subject: [agpod] {} {}\n\
+         \n\
+         ---\n\
+         {}",
+        verb, path, chunk_content
+    )
+}
+
 /// Parse existing REVIEW.md file to extract file entries
 pub fn parse_existing_review(content: &str) -> std::collections::HashMap<String, ReviewEntry> {
-    let mut entries = std::collections::HashMap::new();
+    parse_review_entries(content).into_iter().collect()
+}
+
+/// Parse REVIEW.md file entries in document order
+pub fn parse_review_entries(content: &str) -> Vec<(String, ReviewEntry)> {
+    let mut entries = Vec::new();
     let mut current_file: Option<String> = None;
     let mut current_hash: Option<String> = None;
+    let mut current_chunk: Option<String> = None;
+    let mut current_truncated = false;
+    let mut current_renamed_from: Option<String> = None;
+    let mut current_quoted_path: Option<String> = None;
     let mut current_status: Option<String> = None;
     let mut current_comments = String::new();
     let mut in_comments = false;
@@ -74,23 +161,39 @@ pub fn parse_existing_review(content: &str) -> std::collections::HashMap<String,
                 current_hash.take(),
                 current_status.take(),
             ) {
-                entries.insert(
+                entries.push((
                     file,
                     ReviewEntry {
                         hash,
                         status,
                         comments: current_comments.trim().to_string(),
+                        diff_chunk: current_chunk.take(),
+                        truncated: current_truncated,
+                        renamed_from: current_renamed_from.take(),
+                        quoted_path: current_quoted_path.take(),
                     },
-                );
+                ));
                 current_comments.clear();
                 in_comments = false;
             }
 
             // Start new entry
             current_file = Some(line[3..].trim().to_string());
+            current_chunk = None;
+            current_truncated = false;
+            current_renamed_from = None;
+            current_quoted_path = None;
         } else if current_file.is_some() {
             if let Some(stripped) = line.strip_prefix("- meta:hash: ") {
                 current_hash = Some(stripped.trim().to_string());
+            } else if let Some(stripped) = line.strip_prefix("- meta:diff_chunk: ") {
+                current_chunk = Some(stripped.trim().to_string());
+            } else if let Some(stripped) = line.strip_prefix("- meta:truncated: ") {
+                current_truncated = stripped.trim() == "true";
+            } else if let Some(stripped) = line.strip_prefix("- meta:renamed_from: ") {
+                current_renamed_from = Some(stripped.trim().to_string());
+            } else if let Some(stripped) = line.strip_prefix("- meta:quoted_path: ") {
+                current_quoted_path = Some(stripped.trim().to_string());
             } else if let Some(stripped) = line.strip_prefix("- meta:status: ") {
                 current_status = Some(stripped.trim().to_string());
                 in_comments = true; // Comments come after status
@@ -111,19 +214,133 @@ pub fn parse_existing_review(content: &str) -> std::collections::HashMap<String,
 
     // Save last entry if exists
     if let (Some(file), Some(hash), Some(status)) = (current_file, current_hash, current_status) {
-        entries.insert(
+        entries.push((
             file,
             ReviewEntry {
                 hash,
                 status,
                 comments: current_comments.trim().to_string(),
+                diff_chunk: current_chunk,
+                truncated: current_truncated,
+                renamed_from: current_renamed_from,
+                quoted_path: current_quoted_path,
             },
-        );
+        ));
     }
 
     entries
 }
 
+const REVIEW_TITLE: &str = "# Code Review Tracking";
+const REVIEW_INTRO: &str = "This file tracks the review status of code changes.";
+
+/// `##` sections agpod writes itself and regenerates on every run.
+const GENERATED_SECTIONS: &[&str] = &["Context", "Guidelines"];
+
+/// User-authored parts of an existing REVIEW.md, carried over verbatim when
+/// the generated sections are rebuilt.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct UserSections {
+    /// Prose between the title and the first `##` heading.
+    pub preamble: String,
+    /// Custom sections and notes placed before the first file section.
+    pub before_files: Vec<String>,
+    /// Custom sections and notes placed after the first file section; these
+    /// are written after all file sections since files may be reordered.
+    pub after_files: Vec<String>,
+}
+
+/// Split an existing REVIEW.md into the parts the user wrote, leaving out the
+/// title, the generated `Context`/`Guidelines` sections and file sections.
+///
+/// A generated or file section ends at its `---` line; anything after that up
+/// to the next heading is a user note.
+pub(crate) fn parse_user_sections(content: &str) -> UserSections {
+    let mut sections = UserSections::default();
+    let mut blocks: Vec<Vec<&str>> = vec![Vec::new()];
+    for line in content.lines() {
+        if line.starts_with("## ") {
+            blocks.push(Vec::new());
+        }
+        blocks.last_mut().unwrap().push(line);
+    }
+
+    let mut blocks = blocks.into_iter();
+    let mut in_header = false;
+    let preamble: Vec<&str> = blocks
+        .next()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|line| {
+            // the --with-header block is regenerated, not user prose
+            let header_line = in_header || *line == HEADER_START;
+            in_header = (in_header || *line == HEADER_START) && *line != HEADER_END;
+            !header_line && *line != REVIEW_TITLE && *line != REVIEW_INTRO
+        })
+        .collect();
+    sections.preamble = preamble.join("\n").trim().to_string();
+
+    let mut seen_file = false;
+    for block in blocks {
+        let heading = block[0][3..].trim();
+        let is_file = block.iter().any(|line| line.starts_with("- meta:hash: "));
+        seen_file |= is_file;
+
+        let user_lines = if is_file || GENERATED_SECTIONS.contains(&heading) {
+            let end = block.iter().position(|line| *line == "---");
+            end.map_or(&[][..], |end| &block[end + 1..])
+        } else {
+            &block[..]
+        };
+        let text = user_lines.join("\n").trim().to_string();
+        if text.is_empty() {
+            continue;
+        }
+        if seen_file {
+            sections.after_files.push(text);
+        } else {
+            sections.before_files.push(text);
+        }
+    }
+
+    sections
+}
+
+/// Append the file section for `path` to REVIEW.md `content`
+pub(crate) fn push_review_entry(content: &mut String, path: &str, entry: &ReviewEntry) {
+    content.push_str(&format!("## {}\n", path));
+    content.push_str(&format!("- meta:hash: {}\n", entry.hash));
+    if let Some(chunk) = &entry.diff_chunk {
+        content.push_str(&format!("- meta:diff_chunk: {}\n", chunk));
+    }
+    if let Some(quoted) = &entry.quoted_path {
+        content.push_str(&format!("- meta:quoted_path: {}\n", quoted));
+    }
+    if let Some(old_path) = &entry.renamed_from {
+        content.push_str(&format!("- meta:renamed_from: {}\n", old_path));
+    }
+    if entry.truncated {
+        content.push_str("- meta:truncated: true\n");
+    }
+    content.push_str(&format!("- meta:status: {}\n\n", entry.status));
+
+    if entry.comments.is_empty() {
+        content.push_str("<!-- Review comments go here -->\n\n");
+    } else {
+        content.push_str(&entry.comments);
+        content.push('\n');
+    }
+
+    content.push_str("---\n\n");
+}
+
+pub(crate) fn push_user_block(content: &mut String, block: &str) {
+    if !block.is_empty() {
+        content.push_str(block);
+        content.push_str("\n\n");
+    }
+}
+
 /// Generate chunk suffix (aa-zz, then numbers)
 pub fn generate_chunk_suffix(index: usize) -> String {
     // First use aa-zz (26*26 = 676 combinations)
@@ -137,32 +354,61 @@ pub fn generate_chunk_suffix(index: usize) -> String {
     }
 }
 
-/// Expand environment variables and tilde in path
-#[allow(dead_code)]
-pub(crate) fn expand_path(path: &str) -> String {
-    let mut expanded = path.to_string();
-
-    // Expand tilde (~) to home directory
-    if expanded.starts_with("~/") {
-        if let Ok(home) = env::var("HOME") {
-            expanded = expanded.replacen("~/", &format!("{}/", home), 1);
-        }
-    } else if expanded == "~" {
-        if let Ok(home) = env::var("HOME") {
-            expanded = home;
-        }
-    }
+/// Suffix of a chunk named by this module, like `ab` in `chunk_ab.diff`
+fn chunk_suffix(chunk_name: &str) -> Option<&str> {
+    let (stem, _extension) = chunk_name.rsplit_once('.')?;
+    let suffix = stem.strip_prefix("chunk_")?;
+    (!suffix.is_empty() && suffix.chars().all(|c| c.is_ascii_alphanumeric())).then_some(suffix)
+}
 
-    // Expand environment variables like $HOME, $VAR, etc.
-    let re = Regex::new(r"\$([A-Z_][A-Z0-9_]*)").unwrap();
-    expanded = re
-        .replace_all(&expanded, |caps: &regex::Captures| {
-            let var_name = &caps[1];
-            env::var(var_name).unwrap_or_else(|_| format!("${}", var_name))
+/// Chunk suffix for each file: the suffix of its previous chunk unless an
+/// earlier file already took it, otherwise the lowest suffix nobody uses
+pub fn assign_chunk_suffixes(previous_chunks: &[Option<&str>]) -> Vec<String> {
+    let mut used = std::collections::HashSet::new();
+    let kept: Vec<Option<String>> = previous_chunks
+        .iter()
+        .map(|chunk| {
+            let suffix = chunk.and_then(chunk_suffix)?;
+            used.insert(suffix.to_string()).then(|| suffix.to_string())
         })
-        .to_string();
+        .collect();
+
+    let mut next = 0;
+    kept.into_iter()
+        .map(|suffix| {
+            suffix.unwrap_or_else(|| loop {
+                let candidate = generate_chunk_suffix(next);
+                next += 1;
+                if !used.contains(&candidate) {
+                    break candidate;
+                }
+            })
+        })
+        .collect()
+}
+
+/// Resolve the directory chunks and REVIEW.md are saved to for `output_dir`
+pub fn resolve_review_dir(output_dir: &str) -> PathBuf {
+    let output_dir = Path::new(output_dir);
+    // Add project subfolder only for absolute paths (outside the project)
+    // For relative paths, user is saving within their project, so no subfolder needed
+    if output_dir.is_absolute() {
+        // For absolute paths, add project identifier to prevent conflicts
+        output_dir.join(get_project_identifier())
+    } else {
+        output_dir.to_path_buf()
+    }
+}
 
-    expanded
+/// `path` with forward slashes, for text that should read the same on every
+/// platform such as REVIEW.md
+pub(crate) fn portable_path(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    if MAIN_SEPARATOR == '/' {
+        path.into_owned()
+    } else {
+        path.replace(MAIN_SEPARATOR, "/")
+    }
 }
 
 /// Save diff chunks to separate files with review tracking
@@ -170,56 +416,145 @@ pub fn save_diff_chunks(
     diff_content: &str,
     output_dir: &str,
     context: Option<&str>,
-) -> io::Result<()> {
-    // Determine if we should add project identifier to path
-    // Add project subfolder only for absolute paths (outside the project)
-    // For relative paths, user is saving within their project, so no subfolder needed
-    let is_relative_path = !output_dir.starts_with('/');
-    let project_output_dir = if is_relative_path {
-        // For relative paths, don't add project subfolder since we're already in the project
-        output_dir.to_string()
-    } else {
-        // For absolute paths, add project identifier to prevent conflicts
-        let project_id = get_project_identifier();
-        format!("{}/{}", output_dir, project_id)
-    };
+) -> Result<SavedReview, Error> {
+    save_diff_chunks_with_config(
+        diff_content,
+        output_dir,
+        context,
+        &DiffConfig::default(),
+        &ExcludeMatcher::default(),
+    )
+}
+
+/// Where a save run wrote its chunks and REVIEW.md
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SavedReview {
+    /// Directory holding the chunks and REVIEW.md
+    pub output_dir: PathBuf,
+    /// REVIEW.md, absolute unless in deterministic mode
+    pub review_path: PathBuf,
+    pub chunks: usize,
+    pub truncated_chunks: usize,
+}
+
+impl SavedReview {
+    /// Print the paths in the machine-readable `generated:`/`REVIEW.md:` form
+    pub fn print(&self) {
+        println!("generated: {}{}", self.output_dir.display(), MAIN_SEPARATOR);
+        println!("REVIEW.md: {}", self.review_path.display());
+    }
+}
+
+/// Save diff chunks using `config`, skipping files matched by `exclude`
+pub fn save_diff_chunks_with_config(
+    diff_content: &str,
+    output_dir: &str,
+    context: Option<&str>,
+    config: &DiffConfig,
+    exclude: &ExcludeMatcher,
+) -> Result<SavedReview, Error> {
+    save_diff_chunks_with_progress(
+        diff_content,
+        output_dir,
+        context,
+        config,
+        exclude,
+        None,
+        &Progress::none(),
+    )
+}
+
+/// [`save_diff_chunks_with_config`], reporting `parse` and `save` progress;
+/// `source` is named in the `with_header` header
+pub fn save_diff_chunks_with_progress(
+    diff_content: &str,
+    output_dir: &str,
+    context: Option<&str>,
+    config: &DiffConfig,
+    exclude: &ExcludeMatcher,
+    source: Option<&DiffSource>,
+    progress: &Progress,
+) -> Result<SavedReview, Error> {
+    let project_output_dir = resolve_review_dir(output_dir);
+    fs::create_dir_all(&project_output_dir).map_err(|source| IoError::CreateOutputDir {
+        path: project_output_dir.clone(),
+        source,
+    })?;
+
+    // Held until return so concurrent runs can't interleave the REVIEW.md
+    // read-modify-write
+    let _lock = DirLock::acquire(
+        &project_output_dir,
+        Duration::from_millis(config.lock_timeout_ms),
+    )?;
 
     // Try to read existing REVIEW.md from the output directory BEFORE cleaning up
-    let review_path = format!("{}/REVIEW.md", project_output_dir);
-    let existing_review = fs::read_to_string(review_path.clone()).ok();
+    let review_path = project_output_dir.join("REVIEW.md");
+    let existing_review = match fs::read_to_string(&review_path) {
+        Ok(content) => Some(content),
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::NotFound | io::ErrorKind::NotADirectory
+            ) =>
+        {
+            None
+        }
+        Err(source) => {
+            // failing here keeps existing review comments from being overwritten
+            return Err(ReviewError::ReadReview {
+                path: review_path,
+                source,
+            }
+            .into());
+        }
+    };
     let existing_entries = if let Some(content) = &existing_review {
         parse_existing_review(content)
     } else {
         std::collections::HashMap::new()
     };
+    let user_sections = existing_review
+        .as_deref()
+        .map(parse_user_sections)
+        .unwrap_or_default();
+
+    let file_changes: Vec<_> = parse_git_diff_with_config(diff_content, config)
+        .into_iter()
+        .filter(|file_change| !exclude.is_excluded(file_change))
+        .collect();
+    progress.files("parse", file_changes.len(), file_changes.len());
+
+    // Entries of renamed files are looked up under their old path, and their
+    // previous chunks are compared by hunks since the paths in them changed;
+    // read those chunks before they are removed
+    let previous_hunk_hashes: std::collections::HashMap<&str, String> = file_changes
+        .iter()
+        .filter(|file_change| !existing_entries.contains_key(file_change.display_path()))
+        .filter_map(|file_change| file_change.old_path.as_deref())
+        .filter_map(|old_path| {
+            let chunk = existing_entries.get(old_path)?.diff_chunk.as_ref()?;
+            let content = fs::read_to_string(project_output_dir.join(chunk)).ok()?;
+            Some((old_path, compute_hunks_hash(&content)))
+        })
+        .collect();
 
     // Remove old chunk files but keep REVIEW.md
-    if Path::new(&project_output_dir).exists() {
-        // Read directory and remove only .diff files
-        if let Ok(entries) = fs::read_dir(&project_output_dir) {
-            for entry in entries.flatten() {
-                if let Ok(file_type) = entry.file_type() {
-                    if file_type.is_file() {
-                        if let Some(path_str) = entry.path().to_str() {
-                            if path_str.ends_with(".diff") {
-                                let _ = fs::remove_file(entry.path());
-                            }
-                        }
-                    }
-                }
-            }
+    let stale = remove_old_chunks(&project_output_dir);
+    if !stale.is_empty() {
+        eprintln!("Warning: could not remove old diff chunks:");
+        for (path, error) in &stale {
+            eprintln!("  {}: {}", path.display(), error);
         }
-    } else {
-        fs::create_dir_all(&project_output_dir)?;
     }
 
-    let file_changes = parse_git_diff(diff_content);
-
     // Prepare REVIEW.md content
-    let mut review_content = String::from(
-        "# Code Review Tracking\n\n\
-        This file tracks the review status of code changes.\n\n",
-    );
+    let mut review_content = format!("{}\n\n{}\n\n", REVIEW_TITLE, REVIEW_INTRO);
+    if config.with_header {
+        review_content
+            .push_str(&DiffHeader::collect(&file_changes, source, context).render_for_review());
+    }
+    push_user_block(&mut review_content, &user_sections.preamble);
 
     // Add context section if provided
     if let Some(ctx) = context {
@@ -232,24 +567,41 @@ pub fn save_diff_chunks(
         "## Guidelines\n\
         - Diff chunks are stored in: ",
     );
-    review_content.push_str(&project_output_dir);
+    review_content.push_str(&portable_path(&project_output_dir));
     review_content.push_str(
         "/\n\
         - Update `meta:status` after reviewing each file\n\
         - Status values: `pending`, `reviewed@YYYY-MM-DD`, `outdated`\n\
         - If file hash changes on subsequent runs, status will be automatically set to `outdated`\n\
         - Add review comments in the placeholder section below each file\n\
-        - On each run, file sections not present in current diff are removed\n\n\
+        - On each run, file sections not present in current diff are removed\n\
+        - Notes and extra `##` sections outside file sections are kept as written\n\n\
         ---\n\n",
     );
+    for block in &user_sections.before_files {
+        push_user_block(&mut review_content, block);
+    }
 
     // Track which files are in the current diff
     let mut current_files = std::collections::HashSet::new();
+    let mut truncated_chunks = 0;
+
+    // Files keep the chunk name from the previous REVIEW.md, so notes that
+    // mention a chunk still point at the same file after a rerun
+    let previous_chunks: Vec<Option<&str>> = file_changes
+        .iter()
+        .map(|file_change| {
+            existing_entries
+                .get(file_change.display_path())
+                .or_else(|| existing_entries.get(file_change.old_path.as_deref()?))
+                .and_then(|entry| entry.diff_chunk.as_deref())
+        })
+        .collect();
+    let suffixes = assign_chunk_suffixes(&previous_chunks);
 
-    for (index, file_change) in file_changes.iter().enumerate() {
-        let suffix = generate_chunk_suffix(index);
-        let chunk_filename = format!("chunk_{}.diff", suffix);
-        let chunk_path = format!("{}/{}", project_output_dir, chunk_filename);
+    for (index, (file_change, suffix)) in file_changes.iter().zip(&suffixes).enumerate() {
+        let chunk_filename = format!("chunk_{}.{}", suffix, config.chunk_format.extension());
+        let chunk_path = project_output_dir.join(&chunk_filename);
 
         let unknown_path = "unknown".to_string();
         let filepath = file_change
@@ -260,70 +612,196 @@ pub fn save_diff_chunks(
 
         current_files.insert(filepath.clone());
 
-        let mut chunk_content = format!(
-            "diff --git a/{} b/{}\n",
-            file_change.old_path.as_ref().unwrap_or(filepath),
-            file_change.new_path.as_ref().unwrap_or(filepath)
-        );
+        let header = format!("{}\n", file_change.git_header());
+        let render = |lines: &[String]| {
+            let mut content = header.clone();
+            for line in lines {
+                content.push_str(line);
+                content.push('\n');
+            }
+            content
+        };
+        let mut chunk_content = render(&file_change.content_lines);
 
-        for line in &file_change.content_lines {
-            chunk_content.push_str(line);
-            chunk_content.push('\n');
+        // Compute hash of the original chunk content; hashing before blank-line
+        // squashing, truncation and the patch header keeps status tracking
+        // independent of those settings
+        let file_hash = compute_file_hash(&chunk_content);
+
+        if !config.preserve_blank_lines_in_chunks {
+            chunk_content = render(&remove_excessive_empty_lines(
+                &file_change.content_lines,
+                config.max_consecutive_empty_lines,
+            ));
         }
 
-        // Compute hash of the chunk content
-        let file_hash = compute_file_hash(&chunk_content);
+        let tokens = estimate_tokens(&chunk_content);
+        let truncated = match config.max_tokens_per_chunk {
+            Some(limit) if tokens > limit => {
+                chunk_content = summarize_chunk(&chunk_content, tokens, limit);
+                truncated_chunks += 1;
+                true
+            }
+            _ => false,
+        };
+        if config.chunk_format == ChunkFormat::Patch {
+            chunk_content = format_patch_chunk(&file_change.change_type, filepath, &chunk_content);
+        }
 
         // Write chunk file
-        let mut file = fs::File::create(&chunk_path)?;
-        file.write_all(chunk_content.as_bytes())?;
+        info_span!("chunk_write")
+            .in_scope(|| write_file(&chunk_path, &chunk_content))
+            .map_err(|source| IoError::WriteChunk {
+                path: chunk_path.clone(),
+                source,
+            })?;
 
         // Check if this file existed before
-        let (status, comments) = if let Some(existing) = existing_entries.get(filepath) {
-            // File existed before - check if hash changed
-            if existing.hash == file_hash {
-                // Hash unchanged - preserve status and comments
-                (existing.status.clone(), existing.comments.clone())
+        let renamed_entry = file_change
+            .old_path
+            .as_deref()
+            .filter(|_| !existing_entries.contains_key(filepath))
+            .and_then(|old_path| Some((old_path, existing_entries.get(old_path)?)));
+        let (status, comments, renamed_from) =
+            if let Some(existing) = existing_entries.get(filepath) {
+                // File existed before - check if hash changed
+                let status = if existing.hash == file_hash {
+                    // Hash unchanged - preserve status and comments
+                    existing.status.clone()
+                } else {
+                    // Hash changed - mark as outdated
+                    "outdated".to_string()
+                };
+                (
+                    status,
+                    existing.comments.clone(),
+                    existing.renamed_from.clone(),
+                )
+            } else if let Some((old_path, existing)) = renamed_entry {
+                // Renamed since the last run - carry the old entry over, outdated
+                // only if the hunks changed
+                let unchanged = previous_hunk_hashes
+                    .get(old_path)
+                    .is_some_and(|hash| *hash == compute_hunks_hash(&chunk_content));
+                let status = if unchanged {
+                    existing.status.clone()
+                } else {
+                    "outdated".to_string()
+                };
+                (
+                    status,
+                    existing.comments.clone(),
+                    Some(old_path.to_string()),
+                )
             } else {
-                // Hash changed - mark as outdated
-                ("outdated".to_string(), existing.comments.clone())
-            }
-        } else {
-            // New file - set as pending with no comments
-            ("pending".to_string(), String::new())
-        };
+                // New file - set as pending with no comments
+                ("pending".to_string(), String::new(), None)
+            };
 
         // Add entry to REVIEW.md
-        review_content.push_str(&format!("## {}\n", filepath));
-        review_content.push_str(&format!("- meta:hash: {}\n", file_hash));
-        review_content.push_str(&format!("- meta:diff_chunk: {}\n", chunk_filename));
-        review_content.push_str(&format!("- meta:status: {}\n\n", status));
-
-        if comments.is_empty() {
-            review_content.push_str("<!-- Review comments go here -->\n\n");
-        } else {
-            review_content.push_str(&comments);
-            review_content.push('\n');
-        }
+        let entry = ReviewEntry {
+            hash: file_hash,
+            status,
+            comments,
+            diff_chunk: Some(chunk_filename),
+            truncated,
+            renamed_from,
+            quoted_path: file_change.quoted_display_path().map(str::to_string),
+        };
+        push_review_entry(&mut review_content, filepath, &entry);
+        progress.files("save", index + 1, file_changes.len());
+    }
 
-        review_content.push_str("---\n\n");
+    for block in &user_sections.after_files {
+        push_user_block(&mut review_content, block);
     }
 
     // Write REVIEW.md to the same directory as chunks
-    let mut review_file = fs::File::create(&review_path)?;
-    review_file.write_all(review_content.as_bytes())?;
-
-    // Get absolute path for REVIEW.md
-    let review_absolute_path = std::path::PathBuf::from(&project_output_dir)
-        .join("REVIEW.md")
-        .canonicalize()
-        .ok()
-        .and_then(|p| p.to_str().map(String::from))
-        .unwrap_or_else(|| review_path.clone());
-
-    // Output paths in machine-readable format to stdout
-    println!("generated: {}/", project_output_dir);
-    println!("REVIEW.md: {}", review_absolute_path);
-
-    Ok(())
+    info_span!("review_write")
+        .in_scope(|| write_file(&review_path, &review_content))
+        .map_err(|source| ReviewError::WriteReview {
+            path: review_path.clone(),
+            source,
+        })?;
+
+    // Get absolute path for REVIEW.md; deterministic mode keeps the path as
+    // given so output does not depend on the working directory
+    let review_absolute_path = if agpod_core::is_deterministic() {
+        review_path
+    } else {
+        review_path.canonicalize().unwrap_or(review_path)
+    };
+
+    if let Some(limit) = config.max_tokens_per_chunk.filter(|_| truncated_chunks > 0) {
+        eprintln!(
+            "truncated {} of {} chunks over max_tokens_per_chunk ({})",
+            truncated_chunks,
+            file_changes.len(),
+            limit
+        );
+    }
+
+    Ok(SavedReview {
+        output_dir: project_output_dir,
+        review_path: review_absolute_path,
+        chunks: file_changes.len(),
+        truncated_chunks,
+    })
+}
+
+pub(crate) fn write_file(path: &Path, content: &str) -> io::Result<()> {
+    write_atomic(path, |file| file.write_all(content.as_bytes()))
+}
+
+/// Write a file via a temporary sibling that is renamed over `path`, so an
+/// interrupted run leaves either the old or the new content, never a
+/// truncated file.
+pub(crate) fn write_atomic(
+    path: &Path,
+    write: impl FnOnce(&mut fs::File) -> io::Result<()>,
+) -> io::Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(file_name);
+    tmp_name.push(format!(".{}.tmp", std::process::id()));
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let result = fs::File::create(&tmp_path).and_then(|mut file| {
+        write(&mut file)?;
+        file.sync_all()
+    });
+    match result.and_then(|()| fs::rename(&tmp_path, path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(&tmp_path);
+            Err(e)
+        }
+    }
+}
+
+/// Remove `.diff` and `.patch` chunk files left from a previous run,
+/// returning the ones that could not be removed so the caller can report them.
+fn remove_old_chunks(dir: &Path) -> Vec<(PathBuf, io::Error)> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => return vec![(dir.to_path_buf(), e)],
+    };
+
+    let mut failed = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_chunk = entry.file_type().is_ok_and(|t| t.is_file())
+            && path
+                .extension()
+                .is_some_and(|ext| ext == "diff" || ext == "patch");
+        if !is_chunk {
+            continue;
+        }
+        if let Err(e) = fs::remove_file(&path) {
+            failed.push((path, e));
+        }
+    }
+    failed
 }
@@ -0,0 +1,188 @@
+//! Reconstruction of a single file's before/after content from a diff
+
+use super::processor::parse_git_diff;
+use super::sniff::check_diff_input;
+use super::source::read_stdin_diff;
+use super::types::{ChangeType, FileChange};
+use agpod_core::{Error, ReviewError};
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::sync::LazyLock;
+
+static HUNK_HEADER_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^@@ -(\d+)(?:,(\d+))? \+(\d+)(?:,(\d+))? @@").unwrap());
+
+/// Context passed to `git diff -U` for `--from-git`, large enough that hunks
+/// span whole files
+const FULL_CONTEXT_LINES: usize = 1_000_000;
+
+/// Which side of a change to reconstruct
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Side {
+    Before,
+    #[default]
+    After,
+}
+
+/// Print the before or after content of `path`, reading the diff from stdin
+/// or from `git diff HEAD` with full context when `from_git` is set
+pub fn show_file(path: &str, side: Side, from_git: bool, output: Option<&Path>) -> Result<()> {
+    let diff = if from_git {
+        git_diff_full_context(path)?
+    } else {
+        let input = read_stdin_diff()?;
+        check_diff_input(&input)?;
+        input
+    };
+
+    let content = reconstruct_file(&diff, path, side)?;
+    match output {
+        Some(output) => fs::write(output, content)
+            .with_context(|| format!("failed to write {}", output.display()))?,
+        None => print!("{}", content),
+    }
+    Ok(())
+}
+
+fn git_diff_full_context(path: &str) -> Result<String> {
+    let output = Command::new("git")
+        .args(["diff", "HEAD", "--no-color", "--no-ext-diff"])
+        .arg(format!("--unified={}", FULL_CONTEXT_LINES))
+        .args(["--", path])
+        .output()
+        .context("failed to run git diff")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git diff failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Rebuild the full text of `path` on one side of the diff from its hunks.
+///
+/// Every line of that side must be covered by the hunks, as with
+/// `git diff -U<large>`; a gap between hunks is reported as an error. Lines
+/// after the last hunk cannot be detected from the diff itself.
+pub fn reconstruct_file(diff: &str, path: &str, side: Side) -> Result<String, Error> {
+    let file_changes = parse_git_diff(diff);
+    let file_change = file_changes
+        .iter()
+        .find(|change| {
+            change.new_path.as_deref() == Some(path) || change.old_path.as_deref() == Some(path)
+        })
+        .ok_or_else(|| ReviewError::FileNotInDiff {
+            path: path.to_string(),
+        })?;
+
+    let fail = |reason: String| ReviewError::Reconstruct {
+        path: path.to_string(),
+        reason,
+    };
+    match (&file_change.change_type, side) {
+        (ChangeType::Added, Side::Before) => {
+            return Err(fail("the file does not exist before this change".into()).into())
+        }
+        (ChangeType::Deleted, Side::After) => {
+            return Err(fail("the file is deleted by this change".into()).into())
+        }
+        _ => {}
+    }
+
+    Ok(reconstruct_side(file_change, side).map_err(fail)?)
+}
+
+fn reconstruct_side(file_change: &FileChange, side: Side) -> Result<String, String> {
+    let lines = &file_change.content_lines;
+    let mut i = lines
+        .iter()
+        .position(|line| line.starts_with("@@"))
+        .ok_or("the diff has no hunks (binary, mode-only or pure rename change)")?;
+
+    let mut result: Vec<&str> = Vec::new();
+    let mut next_line = 1;
+    let mut missing_newline = false;
+
+    while i < lines.len() {
+        let captures = HUNK_HEADER_RE
+            .captures(&lines[i])
+            .ok_or_else(|| format!("malformed hunk header: {}", lines[i]))?;
+        let number = |index: usize| -> usize {
+            captures
+                .get(index)
+                .map_or(1, |m| m.as_str().parse().unwrap_or(0))
+        };
+        let (old_count, new_count) = (number(2), number(4));
+        let (start, count) = match side {
+            Side::Before => (number(1), old_count),
+            Side::After => (number(3), new_count),
+        };
+        i += 1;
+
+        // an empty range starts after the given line
+        let first_line = if count == 0 { start + 1 } else { start };
+        if first_line != next_line {
+            return Err(format!(
+                "lines {}-{} are not in the diff; regenerate it with more context (e.g. `git diff -U{}`)",
+                next_line,
+                first_line - 1,
+                FULL_CONTEXT_LINES
+            ));
+        }
+
+        let (mut old_left, mut new_left) = (old_count, new_count);
+        let mut last_kind = ' ';
+        while i < lines.len() && (old_left > 0 || new_left > 0 || lines[i].starts_with('\\')) {
+            let line = lines[i].as_str();
+            i += 1;
+            let kind = line.chars().next().unwrap_or(' ');
+            let text = line.get(1..).unwrap_or("");
+            match kind {
+                ' ' => {
+                    old_left = old_left.saturating_sub(1);
+                    new_left = new_left.saturating_sub(1);
+                    result.push(text);
+                }
+                '-' => {
+                    old_left = old_left.saturating_sub(1);
+                    if side == Side::Before {
+                        result.push(text);
+                    }
+                }
+                '+' => {
+                    new_left = new_left.saturating_sub(1);
+                    if side == Side::After {
+                        result.push(text);
+                    }
+                }
+                '\\' => {
+                    // "\ No newline at end of file" refers to the previous line
+                    let applies = match last_kind {
+                        ' ' => true,
+                        '-' => side == Side::Before,
+                        '+' => side == Side::After,
+                        _ => false,
+                    };
+                    missing_newline |= applies;
+                    continue;
+                }
+                _ => return Err(format!("unexpected line in hunk: {}", line)),
+            }
+            last_kind = kind;
+        }
+        if old_left > 0 || new_left > 0 {
+            return Err("the diff ends in the middle of a hunk".into());
+        }
+        next_line = first_line + count;
+    }
+
+    let mut content = result.join("\n");
+    if !result.is_empty() && !missing_newline {
+        content.push('\n');
+    }
+    Ok(content)
+}
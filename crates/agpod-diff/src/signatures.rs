@@ -0,0 +1,185 @@
+//! Top-level signatures and import changes of large source files, for
+//! `signatures_in_summary`
+//!
+//! Extraction is line-based and regex-driven: good enough to tell a reader
+//! which items a summarized file touched, not a parser.
+
+use super::types::FileChange;
+use regex::Regex;
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// How to spot signatures and imports in one language
+struct Language {
+    extensions: &'static [&'static str],
+    /// A top-level item; `kind` and `name` identify it across both sides
+    signature: &'static str,
+    /// The first line of an import
+    import: &'static str,
+}
+
+/// Add a language by adding an entry; patterns match lines without their
+/// `+`/`-`/` ` prefix and only unindented lines are considered.
+const LANGUAGES: &[Language] = &[
+    Language {
+        extensions: &["rs"],
+        signature: r#"^(?:pub(?:\([^)]*\))?\s+)?(?:(?:const|async|unsafe|extern\s+"[^"]*")\s+)*(?P<kind>fn|struct|impl)\b\s*(?P<name>[^({;]*)"#,
+        import: r"^(?:pub(?:\([^)]*\))?\s+)?(?:use|extern\s+crate)\s",
+    },
+    Language {
+        extensions: &["ts", "tsx", "js", "jsx", "mjs", "cjs"],
+        signature: r"^(?P<kind>(?:export\s+(?:default\s+)?)?(?:declare\s+)?(?:async\s+)?function\*?|(?:export\s+(?:default\s+)?)?(?:abstract\s+)?class|export\s+(?:const|let|var|interface|type|enum))\s+(?P<name>[\w$]+)",
+        import: r"^(?:import\b|export\s+(?:\*|\{[^}]*\}?)\s*(?:as\s+\w+\s+)?from\b)",
+    },
+    Language {
+        extensions: &["py", "pyi"],
+        signature: r"^(?P<kind>(?:async\s+)?def|class)\s+(?P<name>\w+)",
+        import: r"^(?:import|from)\s+\S",
+    },
+];
+
+struct Compiled {
+    language: &'static Language,
+    signature: Regex,
+    import: Regex,
+}
+
+static COMPILED: LazyLock<Vec<Compiled>> = LazyLock::new(|| {
+    LANGUAGES
+        .iter()
+        .map(|language| Compiled {
+            language,
+            signature: Regex::new(language.signature).unwrap(),
+            import: Regex::new(language.import).unwrap(),
+        })
+        .collect()
+});
+
+/// Patterns for `path`, picked by file extension
+fn language_for(path: &str) -> Option<&'static Compiled> {
+    let extension = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+    COMPILED
+        .iter()
+        .find(|compiled| compiled.language.extensions.contains(&extension.as_str()))
+}
+
+/// A signature line without its opening brace or trailing colon
+fn signature_text(text: &str) -> String {
+    text.trim_end()
+        .trim_end_matches('{')
+        .trim_end_matches(':')
+        .trim_end()
+        .to_string()
+}
+
+/// `(kind, name)` of the item declared on `text`, with the kind reduced to
+/// its last word so `function f` and `export function f` are one item
+fn signature_key(compiled: &Compiled, text: &str) -> Option<(String, String)> {
+    let captures = compiled.signature.captures(text)?;
+    let kind = captures["kind"].split_whitespace().last()?.to_string();
+    Some((kind, captures["name"].trim().to_string()))
+}
+
+/// Brackets still open after `text`, starting from `depth`
+fn bracket_depth(depth: usize, text: &str) -> usize {
+    let opened = text.matches(['{', '(']).count();
+    let closed = text.matches(['}', ')']).count();
+    (depth + opened).saturating_sub(closed)
+}
+
+/// Changed signatures and imports of one side-by-side walk through a file
+#[derive(Default)]
+struct Changes {
+    removed_signatures: Vec<((String, String), String)>,
+    added_signatures: Vec<((String, String), String)>,
+    imports: Vec<String>,
+}
+
+/// An "Imports:" and "Signatures:" listing of what `file_change` changed,
+/// or an empty string for unknown languages and files without such changes
+pub fn format_signature_summary(file_change: &FileChange) -> String {
+    let Some(compiled) = language_for(file_change.display_path()) else {
+        return String::new();
+    };
+
+    let mut changes = Changes::default();
+    // brackets of a multi-line import still open on the old and new side
+    let (mut old_depth, mut new_depth) = (0, 0);
+    let hunks = file_change
+        .content_lines
+        .iter()
+        .skip_while(|line| !line.starts_with("@@"));
+    for line in hunks {
+        if line.starts_with("@@") {
+            (old_depth, new_depth) = (0, 0);
+            continue;
+        }
+        let Some(prefix) = line.chars().next() else {
+            continue;
+        };
+        let text = &line[prefix.len_utf8()..];
+        let is_import = |depth: &mut usize| {
+            if *depth > 0 || compiled.import.is_match(text) {
+                *depth = bracket_depth(*depth, text);
+                return true;
+            }
+            false
+        };
+        match prefix {
+            '-' => {
+                if is_import(&mut old_depth) {
+                    changes.imports.push(format!("- {}", text.trim()));
+                } else if let Some(key) = signature_key(compiled, text) {
+                    changes.removed_signatures.push((key, signature_text(text)));
+                }
+            }
+            '+' => {
+                if is_import(&mut new_depth) {
+                    changes.imports.push(format!("+ {}", text.trim()));
+                } else if let Some(key) = signature_key(compiled, text) {
+                    changes.added_signatures.push((key, signature_text(text)));
+                }
+            }
+            ' ' => {
+                is_import(&mut old_depth);
+                is_import(&mut new_depth);
+            }
+            _ => {}
+        }
+    }
+
+    let mut signatures = Vec::new();
+    for (key, removed) in &changes.removed_signatures {
+        match changes
+            .added_signatures
+            .iter()
+            .find(|(other, _)| other == key)
+        {
+            // the same line removed and added again was only moved
+            Some((_, added)) if added == removed => {}
+            Some((_, added)) => signatures.push(format!("~ {} (was: {})", added, removed)),
+            None => signatures.push(format!("- {}", removed)),
+        }
+    }
+    for (key, added) in &changes.added_signatures {
+        if !changes
+            .removed_signatures
+            .iter()
+            .any(|(other, _)| other == key)
+        {
+            signatures.push(format!("+ {}", added));
+        }
+    }
+
+    let mut summary = String::new();
+    for (title, lines) in [("Imports", &changes.imports), ("Signatures", &signatures)] {
+        if lines.is_empty() {
+            continue;
+        }
+        summary.push_str(&format!("{}:\n", title));
+        for line in lines {
+            summary.push_str(&format!("  {}\n", line));
+        }
+    }
+    summary
+}
@@ -0,0 +1,54 @@
+//! Detection of input that is not a git diff
+
+use agpod_core::{Error, ReviewError};
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// Number of leading lines inspected when classifying input
+pub(crate) const SNIFF_LINES: usize = 50;
+
+static GIT_LOG_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(commit [0-9a-f]{7,64}\b|[0-9a-f]{7,64} \S)").unwrap());
+
+/// Index of the first `--- `/`+++ ` pair in the sniffed lines, as emitted by
+/// plain `diff -u` without a `diff --git` header
+pub(crate) fn find_plain_unified_header(lines: &[&str]) -> Option<usize> {
+    (0..lines.len().min(SNIFF_LINES)).find(|&i| {
+        lines[i].starts_with("--- ") && lines.get(i + 1).is_some_and(|l| l.starts_with("+++ "))
+    })
+}
+
+/// Reject input that is clearly not a diff, such as `git log` output or
+/// prose, instead of silently producing empty output.
+///
+/// Empty input is accepted, since an empty `git diff` is a normal result.
+pub fn check_diff_input(input: &str) -> Result<(), Error> {
+    if input.trim().is_empty()
+        || ["diff --git ", "diff --cc ", "diff --combined "]
+            .iter()
+            .any(|header| input.starts_with(header) || input.contains(&format!("\n{header}")))
+    {
+        return Ok(());
+    }
+
+    let lines: Vec<&str> = input.lines().take(SNIFF_LINES).collect();
+    if find_plain_unified_header(&lines).is_some() {
+        return Ok(());
+    }
+
+    let first_line = lines
+        .iter()
+        .find(|line| !line.trim().is_empty())
+        .copied()
+        .unwrap_or_default();
+    let reason = if GIT_LOG_RE.is_match(first_line) {
+        "it looks like `git log` output; use `git log -p` or `git show <commit>` to include changes"
+            .to_string()
+    } else {
+        format!(
+            "no `diff --git` or `---`/`+++` header in the first {} lines; pipe `git diff` into it instead",
+            SNIFF_LINES
+        )
+    };
+    Err(ReviewError::NotADiff { reason }.into())
+}
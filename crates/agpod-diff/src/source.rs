@@ -0,0 +1,175 @@
+//! Where `agpod diff` reads its diff from: stdin or the current repository
+
+use agpod_core::{Error, GitError};
+use anyhow::{Context, Result};
+use git2::{BranchType, ErrorCode, Repository};
+use std::io::{self, Read};
+use std::process::Command;
+use tracing::info_span;
+
+/// How many branch names an unknown-ref error suggests at most
+const MAX_SUGGESTIONS: usize = 5;
+
+/// Input of `agpod diff`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum DiffSource {
+    /// A diff piped in on stdin
+    #[default]
+    Stdin,
+    /// `git diff` run in the current repository
+    Git {
+        /// Diff from the merge base of this ref and HEAD instead of from HEAD
+        since_ref: Option<String>,
+        /// Stop at HEAD instead of the working tree (only with `since_ref`)
+        committed: bool,
+    },
+}
+
+/// What the diff compares, for `--with-header`
+impl std::fmt::Display for DiffSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Stdin => write!(f, "stdin"),
+            Self::Git {
+                since_ref: None, ..
+            } => write!(f, "working tree vs HEAD"),
+            Self::Git {
+                since_ref: Some(since_ref),
+                committed,
+            } => write!(
+                f,
+                "{} vs merge-base of {} and HEAD",
+                if *committed { "HEAD" } else { "working tree" },
+                since_ref
+            ),
+        }
+    }
+}
+
+impl DiffSource {
+    /// Read the diff text
+    pub fn read(&self) -> Result<String> {
+        let _span = info_span!("read_input").entered();
+        match self {
+            Self::Stdin => read_stdin_diff(),
+            Self::Git {
+                since_ref,
+                committed,
+            } => {
+                let mut revisions = Vec::new();
+                match since_ref {
+                    Some(since_ref) => {
+                        revisions.push(merge_base_with_head(since_ref)?);
+                        if *committed {
+                            revisions.push("HEAD".to_string());
+                        }
+                    }
+                    None => revisions.push("HEAD".to_string()),
+                }
+                git_diff(&revisions)
+            }
+        }
+    }
+}
+
+/// The diff on stdin; bytes that are not UTF-8, such as Latin-1 file
+/// content, are replaced rather than failing the whole read
+pub(crate) fn read_stdin_diff() -> Result<String> {
+    let mut input = Vec::new();
+    io::stdin()
+        .read_to_end(&mut input)
+        .context("failed to read diff from stdin")?;
+    Ok(String::from_utf8_lossy(&input).into_owned())
+}
+
+/// The merge base of `reference` and HEAD in the repository containing the
+/// working directory, as a full commit id
+pub fn merge_base_with_head(reference: &str) -> Result<String, Error> {
+    let repo = Repository::discover(".").map_err(git_error)?;
+    let since = match repo.revparse_single(reference) {
+        Ok(object) => object.peel_to_commit().map_err(git_error)?,
+        Err(e) if e.code() == ErrorCode::NotFound => {
+            return Err(GitError::UnknownRef {
+                reference: reference.to_string(),
+                suggestions: similar_branch_names(&repo, reference),
+            }
+            .into())
+        }
+        Err(source) => return Err(git_error(source).into()),
+    };
+    let head = repo
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .map_err(git_error)?;
+    let base = repo.merge_base(since.id(), head.id()).map_err(git_error)?;
+    Ok(base.to_string())
+}
+
+fn git_error(source: git2::Error) -> GitError {
+    GitError::Repository {
+        source: source.into(),
+    }
+}
+
+fn git_diff(revisions: &[String]) -> Result<String> {
+    let output = Command::new("git")
+        .args(["diff", "--no-color", "--no-ext-diff"])
+        .args(revisions)
+        .arg("--")
+        .output()
+        .context("failed to run git diff")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git diff failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Local branch names close to `reference`, closest first
+fn similar_branch_names(repo: &Repository, reference: &str) -> Vec<String> {
+    let Ok(branches) = repo.branches(Some(BranchType::Local)) else {
+        return Vec::new();
+    };
+    let names: Vec<String> = branches
+        .flatten()
+        .filter_map(|(branch, _)| branch.name().ok().flatten().map(str::to_string))
+        .collect();
+    suggest(reference, names)
+}
+
+/// Candidates within a few edits of `reference`, or containing it or
+/// contained in it (`origin/main` suggests `main`), closest first
+pub(crate) fn suggest(reference: &str, candidates: Vec<String>) -> Vec<String> {
+    let max_distance = (reference.chars().count() / 3).max(2);
+    let mut scored: Vec<(usize, String)> = candidates
+        .into_iter()
+        .filter_map(|candidate| {
+            let distance = edit_distance(reference, &candidate);
+            let related = candidate.contains(reference) || reference.contains(candidate.as_str());
+            (distance <= max_distance || related).then_some((distance, candidate))
+        })
+        .collect();
+    scored.sort();
+    scored
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+/// Levenshtein distance in chars
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
@@ -1,10 +1,12 @@
 //! Tests for diff module
 
 use super::*;
-use std::env;
+use agpod_core::{ConfigError, IoError, ReviewError};
 use std::fs;
+use std::io::Write;
 use std::path::Path;
 use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
 
 // Shared lock to prevent parallel execution of tests that write to REVIEW.md
 static REVIEW_MD_LOCK: LazyLock<Mutex<()>> = LazyLock::new(|| Mutex::new(()));
@@ -166,11 +168,17 @@ fn test_remove_excessive_empty_lines() {
         "Line 2".to_string(),
     ];
 
-    let result = remove_excessive_empty_lines(&lines);
+    let result = remove_excessive_empty_lines(&lines, 2);
     let empty_count = result.iter().filter(|line| line.trim().is_empty()).count();
     assert_eq!(empty_count, 2); // Should have at most 2 empty lines
     assert_eq!(result[0], "Line 1");
     assert_eq!(result[result.len() - 1], "Line 2");
+
+    assert_eq!(
+        remove_excessive_empty_lines(&lines, 0),
+        ["Line 1", "Line 2"]
+    );
+    assert_eq!(remove_excessive_empty_lines(&lines, 10), lines);
 }
 
 #[test]
@@ -181,65 +189,6 @@ fn test_change_type_strings() {
     assert_eq!(ChangeType::Renamed.as_str(), "renamed");
 }
 
-#[test]
-fn test_expand_path_tilde() {
-    // Test tilde expansion
-    if let Ok(home) = env::var("HOME") {
-        assert_eq!(super::save::expand_path("~/test"), format!("{}/test", home));
-        assert_eq!(super::save::expand_path("~"), home);
-    }
-
-    // Test path without tilde (should remain unchanged)
-    assert_eq!(super::save::expand_path("/tmp/test"), "/tmp/test");
-    assert_eq!(super::save::expand_path("relative/path"), "relative/path");
-}
-
-#[test]
-fn test_expand_path_env_vars() {
-    // Set a test environment variable
-    env::set_var("TEST_VAR", "/test/path");
-
-    // Test environment variable expansion
-    assert_eq!(
-        super::save::expand_path("$TEST_VAR/subdir"),
-        "/test/path/subdir"
-    );
-    assert_eq!(
-        super::save::expand_path("prefix/$TEST_VAR/suffix"),
-        "prefix//test/path/suffix"
-    );
-
-    // Test with HOME variable (should exist)
-    if let Ok(home) = env::var("HOME") {
-        assert_eq!(
-            super::save::expand_path("$HOME/work"),
-            format!("{}/work", home)
-        );
-    }
-
-    // Test non-existent variable (should keep as-is)
-    assert_eq!(
-        super::save::expand_path("$NONEXISTENT_VAR/path"),
-        "$NONEXISTENT_VAR/path"
-    );
-
-    // Clean up
-    env::remove_var("TEST_VAR");
-}
-
-#[test]
-fn test_expand_path_combined() {
-    // Test combination of tilde and env var
-    if let Ok(home) = env::var("HOME") {
-        env::set_var("TEST_DIR", "mydir");
-        assert_eq!(
-            super::save::expand_path("~/work/$TEST_DIR"),
-            format!("{}/work/mydir", home)
-        );
-        env::remove_var("TEST_DIR");
-    }
-}
-
 #[test]
 fn test_renamed_file() {
     let diff = r#"diff --git a/old_name.txt b/new_name.txt
@@ -406,6 +355,52 @@ fn test_get_project_identifier() {
     // In non-git context, returns current directory name or "default-project"
 }
 
+#[test]
+fn test_resolve_review_dir_relative_path_has_no_project_subfolder() {
+    let dir = resolve_review_dir("llm/diff");
+    assert_eq!(dir, Path::new("llm").join("diff"));
+    assert!(dir.is_relative());
+}
+
+#[test]
+fn test_resolve_review_dir_absolute_path_adds_project_subfolder() {
+    let temp = tempfile::tempdir().unwrap();
+    let dir = resolve_review_dir(temp.path().to_str().unwrap());
+    assert_eq!(dir.parent(), Some(temp.path()));
+    assert_eq!(
+        dir.file_name().and_then(|name| name.to_str()),
+        Some(get_project_identifier().as_str())
+    );
+}
+
+#[test]
+fn test_portable_path_uses_forward_slashes() {
+    let path = Path::new("llm").join("diff").join("agpod");
+    assert_eq!(super::save::portable_path(&path), "llm/diff/agpod");
+}
+
+#[cfg(windows)]
+#[test]
+fn test_resolve_review_dir_windows_paths() {
+    // a drive letter path is absolute, a leading backslash alone is not
+    let dir = resolve_review_dir(r"C:\review\diff");
+    assert_eq!(
+        dir,
+        Path::new(r"C:\review\diff").join(get_project_identifier())
+    );
+    assert_eq!(resolve_review_dir(r"\review"), Path::new(r"\review"));
+    assert_eq!(resolve_review_dir(r"llm\diff"), Path::new(r"llm\diff"));
+}
+
+#[cfg(windows)]
+#[test]
+fn test_portable_path_windows_separators() {
+    assert_eq!(
+        super::save::portable_path(Path::new(r"C:\review\diff\agpod")),
+        "C:/review/diff/agpod"
+    );
+}
+
 #[test]
 fn test_compute_file_hash() {
     let content1 = "Hello, World!";
@@ -704,3 +699,1645 @@ index 0000000..def456
     // Clean up
     let _ = fs::remove_dir_all(test_path);
 }
+
+#[test]
+fn test_diff_config_fields_are_all_consumed() {
+    // No `..` on purpose: adding a field to agpod_core::DiffConfig breaks this
+    // test until the diff crate decides how to use it.
+    let DiffConfig {
+        output_dir,
+        large_file_changes_threshold,
+        large_file_lines_threshold,
+        max_consecutive_empty_lines,
+        exclude_file,
+        max_tokens_per_chunk,
+        chunk_format,
+        preserve_blank_lines_in_chunks,
+        omitted_footer,
+        lock_timeout_ms,
+        order_by,
+        ignore_space_change,
+        ignore_comments,
+        with_header,
+        signatures_in_summary,
+    } = DiffConfig::default();
+
+    assert_eq!(output_dir, "llm/diff");
+    assert_eq!(large_file_changes_threshold, 100);
+    assert_eq!(large_file_lines_threshold, 500);
+    assert_eq!(max_consecutive_empty_lines, 2);
+    assert_eq!(exclude_file, None);
+    assert_eq!(max_tokens_per_chunk, None);
+    assert_eq!(chunk_format, agpod_core::ChunkFormat::Diff);
+    assert!(preserve_blank_lines_in_chunks);
+    assert!(omitted_footer);
+    assert_eq!(lock_timeout_ms, 10_000);
+    assert_eq!(order_by, agpod_core::FileOrder::Path);
+    assert!(!ignore_space_change);
+    assert!(!ignore_comments);
+    assert!(!with_header);
+    assert!(!signatures_in_summary);
+}
+
+#[test]
+fn test_minimize_diff_honors_config_thresholds() {
+    let diff = "diff --git a/small.txt b/small.txt\n\
+index 1234567..abcdefg 100644\n\
+--- a/small.txt\n\
++++ b/small.txt\n\
+@@ -1,5 +1,7 @@\n\
++one\n\n\n\n\n+two\n+three\n+four";
+
+    let default_result = minimize_diff(diff);
+    assert!(default_result.contains("+one\n\n\n+two"));
+
+    let config = DiffConfig {
+        large_file_changes_threshold: 3,
+        ..DiffConfig::default()
+    };
+    let result = minimize_diff_with_config(diff, &config);
+    assert!(result.contains("Large file change: small.txt"));
+    assert!(!result.contains("+one"));
+
+    let config = DiffConfig {
+        max_consecutive_empty_lines: 0,
+        ..DiffConfig::default()
+    };
+    let result = minimize_diff_with_config(diff, &config);
+    assert!(result.contains("+one\n+two"));
+}
+
+const SNAPSHOT_DIFF: &str = r#"diff --git a/src/lib.rs b/src/lib.rs
+index 1234567..89abcde 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,3 +1,4 @@
+ pub fn add(a: i32, b: i32) -> i32 {
+-    a + b
++    // saturating to avoid overflow panics
++    a.saturating_add(b)
+ }
+diff --git a/README.md b/README.md
+deleted file mode 100644
+index 1111111..0000000
+--- a/README.md
++++ /dev/null
+@@ -1,2 +0,0 @@
+-# Example
+-Old readme
+diff --git a/old_name.rs b/new_name.rs
+similarity index 100%
+rename from old_name.rs
+rename to new_name.rs"#;
+
+#[test]
+fn test_minimize_diff_snapshot() {
+    insta::assert_snapshot!(minimize_diff(SNAPSHOT_DIFF));
+}
+
+#[test]
+fn test_file_summaries_snapshot() {
+    let summaries: Vec<String> = parse_git_diff(SNAPSHOT_DIFF)
+        .iter()
+        .map(|change| match change.change_type {
+            ChangeType::Deleted => format_deleted_file_summary(change),
+            _ => format_large_file_summary(change),
+        })
+        .collect();
+    insta::assert_snapshot!(summaries.join("\n"));
+}
+
+#[test]
+fn test_review_md_snapshot() {
+    let _guard = REVIEW_MD_LOCK.lock().unwrap();
+
+    let test_path = "test_review_snapshot";
+    let _ = fs::remove_dir_all(test_path);
+
+    save_diff_chunks(SNAPSHOT_DIFF, test_path, Some("See docs/design.md")).unwrap();
+    let review = fs::read_to_string(format!("{}/REVIEW.md", test_path)).unwrap();
+    let _ = fs::remove_dir_all(test_path);
+
+    // chunk hashes come from the std hasher, which is not stable across releases
+    let hash_re = regex::Regex::new(r"meta:hash: [0-9a-f]+").unwrap();
+    insta::assert_snapshot!(hash_re.replace_all(&review, "meta:hash: [hash]"));
+}
+
+/// The `diff --git` sections of `diff`, one per file
+fn split_file_sections(diff: &str) -> Vec<String> {
+    diff.split("diff --git ")
+        .filter(|section| !section.is_empty())
+        .map(|section| format!("diff --git {}\n", section.trim_end()))
+        .collect()
+}
+
+#[test]
+fn test_file_order_is_stable_across_input_order() {
+    let temp = tempfile::tempdir().unwrap();
+    let output_dir = temp.path().join("review");
+    let output = output_dir.to_str().unwrap();
+    let review_dir = resolve_review_dir(output);
+    let sections = split_file_sections(SNAPSHOT_DIFF);
+    assert_eq!(sections.len(), 3);
+
+    let mut outputs = Vec::new();
+    for order in [
+        [0, 1, 2],
+        [0, 2, 1],
+        [1, 0, 2],
+        [1, 2, 0],
+        [2, 0, 1],
+        [2, 1, 0],
+    ] {
+        let diff: String = order.iter().map(|&i| sections[i].as_str()).collect();
+        let _ = fs::remove_dir_all(&output_dir);
+        save_diff_chunks(&diff, output, None).unwrap();
+        let chunks: Vec<String> = ["chunk_aa.diff", "chunk_ab.diff", "chunk_ac.diff"]
+            .iter()
+            .map(|name| fs::read_to_string(review_dir.join(name)).unwrap())
+            .collect();
+        outputs.push((
+            minimize_diff(&diff),
+            fs::read_to_string(review_dir.join("REVIEW.md")).unwrap(),
+            chunks,
+        ));
+    }
+    assert!(outputs.windows(2).all(|pair| pair[0] == pair[1]));
+
+    let paths: Vec<_> = parse_git_diff(SNAPSHOT_DIFF)
+        .iter()
+        .map(|change| change.display_path().to_string())
+        .collect();
+    assert_eq!(paths, ["new_name.rs", "src/lib.rs", "README.md"]);
+}
+
+#[test]
+fn test_file_order_input_keeps_diff_order() {
+    let config = DiffConfig {
+        order_by: agpod_core::FileOrder::Input,
+        ..DiffConfig::default()
+    };
+    let paths: Vec<_> = parse_git_diff_with_config(SNAPSHOT_DIFF, &config)
+        .iter()
+        .map(|change| change.display_path().to_string())
+        .collect();
+    assert_eq!(paths, ["src/lib.rs", "README.md", "new_name.rs"]);
+}
+
+#[test]
+fn test_save_diff_chunks_reports_output_dir_path() {
+    let temp = tempfile::tempdir().unwrap();
+    // a regular file where the output directory's parent should be
+    let blocker = temp.path().join("not-a-dir");
+    fs::write(&blocker, "").unwrap();
+    let output_dir = blocker.join("review");
+    let output_dir = output_dir.to_str().unwrap();
+
+    let err = save_diff_chunks(SNAPSHOT_DIFF, output_dir, None).unwrap_err();
+    assert!(
+        matches!(err, agpod_core::Error::Io(IoError::CreateOutputDir { .. })),
+        "{err:?}"
+    );
+    assert!(
+        err.to_string().contains("not-a-dir"),
+        "error should name the path: {err}"
+    );
+}
+
+#[test]
+fn test_interrupted_write_keeps_original_review() {
+    let temp = tempfile::tempdir().unwrap();
+    let review_path = temp.path().join("REVIEW.md");
+    fs::write(
+        &review_path,
+        "## src/lib.rs\n- meta:status: reviewed@2024-01-01\n",
+    )
+    .unwrap();
+
+    let result = super::save::write_atomic(&review_path, |file| {
+        file.write_all(b"# Code Review Tracking\n")?;
+        Err(std::io::Error::other("interrupted"))
+    });
+
+    assert!(result.is_err());
+    assert_eq!(
+        fs::read_to_string(&review_path).unwrap(),
+        "## src/lib.rs\n- meta:status: reviewed@2024-01-01\n"
+    );
+    // the partial temporary file is cleaned up
+    assert_eq!(fs::read_dir(temp.path()).unwrap().count(), 1);
+
+    super::save::write_atomic(&review_path, |file| file.write_all(b"updated\n")).unwrap();
+    assert_eq!(fs::read_to_string(&review_path).unwrap(), "updated\n");
+}
+
+#[test]
+fn test_parse_user_sections() {
+    let review = "# Code Review Tracking\n\n\
+This file tracks the review status of code changes.\n\n\
+Sprint 12 review, ping @sam when done.\n\n\
+## Guidelines\n\
+- Update `meta:status` after reviewing each file\n\n\
+---\n\n\
+Start with the API changes.\n\n\
+## src/lib.rs\n\
+- meta:hash: abc\n\
+- meta:diff_chunk: chunk_aa.diff\n\
+- meta:status: pending\n\n\
+Looks fine.\n\n\
+---\n\n\
+## Open questions\n\n\
+- Why saturating?\n\n\
+## README.md\n\
+- meta:hash: def\n\
+- meta:diff_chunk: chunk_ab.diff\n\
+- meta:status: pending\n\n\
+---\n";
+
+    let sections = super::save::parse_user_sections(review);
+    assert_eq!(sections.preamble, "Sprint 12 review, ping @sam when done.");
+    assert_eq!(sections.before_files, vec!["Start with the API changes."]);
+    assert_eq!(
+        sections.after_files,
+        vec!["## Open questions\n\n- Why saturating?"]
+    );
+}
+
+#[test]
+fn test_save_diff_chunks_preserves_user_sections() {
+    let _guard = REVIEW_MD_LOCK.lock().unwrap();
+
+    let test_path = "test_user_sections";
+    let _ = fs::remove_dir_all(test_path);
+    let review_path = format!("{}/REVIEW.md", test_path);
+
+    save_diff_chunks(SNAPSHOT_DIFF, test_path, None).unwrap();
+    let review = fs::read_to_string(&review_path).unwrap();
+
+    // add prose, interleaved and trailing custom sections, and a file comment
+    let review = review
+        .replacen(
+            "This file tracks the review status of code changes.\n\n",
+            "This file tracks the review status of code changes.\n\nSprint 12 review.\n\n",
+            1,
+        )
+        .replacen(
+            "## README.md\n",
+            "## Open questions\n\n- Why saturating?\n\n## README.md\n",
+            1,
+        )
+        .replacen(
+            "- meta:diff_chunk: chunk_aa.diff\n- meta:status: pending\n\n<!-- Review comments go here -->\n",
+            "- meta:diff_chunk: chunk_aa.diff\n- meta:status: pending\n\nLooks fine.\n",
+            1,
+        )
+        + "## Follow-ups\n\n- file an issue for overflow tests\n";
+    fs::write(&review_path, &review).unwrap();
+
+    save_diff_chunks(SNAPSHOT_DIFF, test_path, None).unwrap();
+    let regenerated = fs::read_to_string(&review_path).unwrap();
+
+    assert!(regenerated.starts_with(
+        "# Code Review Tracking\n\nThis file tracks the review status of code changes.\n\nSprint 12 review.\n\n## Guidelines"
+    ));
+    assert!(regenerated.contains("- meta:status: pending\n\nLooks fine.\n"));
+    assert_eq!(regenerated.matches("## Open questions").count(), 1);
+    assert_eq!(regenerated.matches("## Follow-ups").count(), 1);
+    // custom sections follow the file sections
+    let last_file = regenerated.find("## new_name.rs").unwrap();
+    assert!(regenerated.find("## Open questions").unwrap() > last_file);
+    assert!(regenerated.find("## Follow-ups").unwrap() > last_file);
+    assert!(regenerated.ends_with("- file an issue for overflow tests\n\n"));
+
+    // a further run is stable
+    save_diff_chunks(SNAPSHOT_DIFF, test_path, None).unwrap();
+    assert_eq!(fs::read_to_string(&review_path).unwrap(), regenerated);
+
+    let _ = fs::remove_dir_all(test_path);
+}
+
+fn synthetic_diff(files: usize) -> String {
+    let mut diff = String::new();
+    for i in 0..files {
+        diff.push_str(&format!(
+            "diff --git a/src/file_{i}.rs b/src/file_{i}.rs\n\
+             index 1234567..89abcde 100644\n\
+             --- a/src/file_{i}.rs\n\
+             +++ b/src/file_{i}.rs\n\
+             @@ -1,2 +1,2 @@\n\
+             -fn old_{i}() {{}}\n\
+             +fn new_{i}() {{}}\n"
+        ));
+    }
+    diff
+}
+
+#[test]
+fn test_parse_large_diff_keeps_every_file() {
+    let diff = synthetic_diff(5_000);
+
+    let changes = parse_git_diff(&diff);
+
+    assert_eq!(changes.len(), 5_000);
+    // sorted by path, so the last file listed is not the last one sorted
+    assert_eq!(changes[4_999].new_path.as_deref(), Some("src/file_999.rs"));
+    // everything but the `diff --git` line is kept; `cargo bench` measures
+    // how long this takes
+    assert!(changes.iter().all(|change| change.content_lines.len() == 6));
+}
+
+#[test]
+fn test_collect_review_comments() {
+    let temp = tempfile::tempdir().unwrap();
+    let review = "# Code Review Tracking\n\n\
+## src/lib.rs\n\
+- meta:hash: abc\n\
+- meta:diff_chunk: chunk_aa.diff\n\
+- meta:status: reviewed@2024-05-01\n\n\
+Rename `add` to `saturating_add`.\n\n\
+---\n\n\
+## README.md\n\
+- meta:hash: def\n\
+- meta:diff_chunk: chunk_ab.diff\n\
+- meta:status: pending\n\n\
+<!-- Review comments go here -->\n\n\
+---\n";
+    fs::write(temp.path().join("REVIEW.md"), review).unwrap();
+    fs::write(
+        temp.path().join("chunk_aa.diff"),
+        "diff --git a/src/lib.rs b/src/lib.rs\n",
+    )
+    .unwrap();
+
+    let comments = collect_review_comments(temp.path(), false).unwrap();
+    assert_eq!(
+        comments,
+        vec![ReviewComment {
+            file: "src/lib.rs".to_string(),
+            status: "reviewed@2024-05-01".to_string(),
+            chunk: Some("chunk_aa.diff".to_string()),
+            comments: "Rename `add` to `saturating_add`.".to_string(),
+            diff: None,
+        }]
+    );
+
+    let with_diff = collect_review_comments(temp.path(), true).unwrap();
+    assert_eq!(
+        with_diff[0].diff.as_deref(),
+        Some("diff --git a/src/lib.rs b/src/lib.rs\n")
+    );
+    assert_eq!(
+        format_comments_markdown(&with_diff),
+        "## src/lib.rs\n\
+- status: reviewed@2024-05-01\n\
+- chunk: chunk_aa.diff\n\n\
+Rename `add` to `saturating_add`.\n\n\
+```diff\n\
+diff --git a/src/lib.rs b/src/lib.rs\n\
+```\n\n"
+    );
+}
+
+#[test]
+fn test_collect_review_comments_missing_review() {
+    let temp = tempfile::tempdir().unwrap();
+    let err = collect_review_comments(temp.path(), false).unwrap_err();
+    assert!(matches!(
+        err,
+        agpod_core::Error::Review(ReviewError::ReadReview { .. })
+    ));
+}
+
+#[test]
+fn test_build_review_payload() {
+    let review = "# Code Review Tracking\n\n\
+## src/lib.rs\n\
+- meta:hash: abc\n\
+- meta:status: pending\n\n\
+Rename `add` to `saturating_add`.\n\n\
+---\n\n\
+## README.md\n\
+- meta:hash: def\n\
+- meta:status: pending\n\n\
+<!-- Review comments go here -->\n\n\
+---\n\n\
+## src/main.rs\n\
+- meta:hash: 123\n\
+- meta:status: reviewed\n\n\
+---\n";
+
+    assert_eq!(
+        build_review_payload(review),
+        ReviewPayload {
+            body: "Review status: 3 files (2 pending, 1 reviewed)".to_string(),
+            event: "COMMENT",
+            comments: vec![ReviewPayloadComment {
+                path: "src/lib.rs".to_string(),
+                body: "Rename `add` to `saturating_add`.".to_string(),
+                subject_type: "file",
+            }],
+        }
+    );
+    assert!(build_review_payload("# Code Review Tracking\n")
+        .comments
+        .is_empty());
+}
+
+/// A REVIEW.md with one section per `(path, status, comments)`, then `notes`
+fn review_md(entries: &[(&str, &str, &str)], notes: &str) -> String {
+    let mut review = "# Code Review Tracking\n\n## Guidelines\n- generated\n\n---\n\n".to_string();
+    for (path, status, comments) in entries {
+        let entry = ReviewEntry {
+            hash: format!("hash-{}", path),
+            status: status.to_string(),
+            comments: comments.to_string(),
+            diff_chunk: Some(format!("chunk_{}.diff", path)),
+            truncated: false,
+            renamed_from: None,
+            quoted_path: None,
+        };
+        super::save::push_review_entry(&mut review, path, &entry);
+    }
+    super::save::push_user_block(&mut review, notes);
+    review
+}
+
+#[test]
+fn test_merge_reviews_takes_each_sides_changes() {
+    let base = review_md(
+        &[
+            ("a", "pending", ""),
+            ("b", "pending", ""),
+            ("c", "pending", ""),
+        ],
+        "",
+    );
+    let ours = review_md(
+        &[
+            ("a", "reviewed@2024-05-01", "Looks good."),
+            ("b", "pending", ""),
+        ],
+        "## Notes\nfrom ours",
+    );
+    let theirs = review_md(
+        &[
+            ("a", "pending", ""),
+            ("b", "reviewed@2024-05-02", "Nit: rename."),
+            ("c", "pending", ""),
+            ("d", "pending", ""),
+        ],
+        "## Later\nfrom theirs",
+    );
+
+    let merged = merge_reviews(Some(&base), &ours, &theirs);
+    // c was dropped by our regeneration and untouched by theirs
+    assert_eq!(
+        merged.content,
+        review_md(
+            &[
+                ("a", "reviewed@2024-05-01", "Looks good."),
+                ("b", "reviewed@2024-05-02", "Nit: rename."),
+                ("d", "pending", ""),
+            ],
+            "## Notes\nfrom ours\n\n## Later\nfrom theirs",
+        )
+    );
+    assert!(merged.both_commented.is_empty());
+}
+
+#[test]
+fn test_merge_reviews_keeps_both_comment_blocks() {
+    let ours = review_md(&[("a", "reviewed@2024-05-01", "Ours.")], "");
+    let theirs = review_md(&[("a", "reviewed@2024-05-03", "Theirs.")], "");
+
+    let merged = merge_reviews(None, &ours, &theirs);
+    assert_eq!(merged.both_commented, ["a"]);
+    let entries = parse_review_entries(&merged.content);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].1.status, "reviewed@2024-05-03");
+    assert_eq!(
+        entries[0].1.comments,
+        format!("{OURS_MARKER}\nOurs.\n\n{THEIRS_MARKER}\nTheirs.")
+    );
+
+    // merging a side with itself is a no-op
+    assert_eq!(merge_reviews(None, &ours, &ours).content, ours);
+}
+
+#[test]
+fn test_suggest_similar_branch_names() {
+    let branches = ["main", "master", "feature/login", "release"].map(String::from);
+    assert_eq!(
+        source::suggest("mastr", branches.to_vec()),
+        vec!["master".to_string()]
+    );
+    assert_eq!(
+        source::suggest("origin/main", branches.to_vec()),
+        vec!["main".to_string()]
+    );
+    assert_eq!(
+        source::suggest("login", branches.to_vec()),
+        vec!["feature/login".to_string()]
+    );
+    assert!(source::suggest("zzz", branches.to_vec()).is_empty());
+}
+
+const FULL_CONTEXT_DIFF: &str = "diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,4 +1,4 @@
+ fn add(a: i32, b: i32) -> i32 {
+-    a + b
++    a.saturating_add(b)
+ }
+-// old trailer
+\\ No newline at end of file
++// new trailer
+diff --git a/NEW.md b/NEW.md
+new file mode 100644
+--- /dev/null
++++ b/NEW.md
+@@ -0,0 +1,2 @@
++# New
++--- not a header
+";
+
+#[test]
+fn test_reconstruct_file_both_sides() {
+    assert_eq!(
+        reconstruct_file(FULL_CONTEXT_DIFF, "src/lib.rs", Side::Before).unwrap(),
+        "fn add(a: i32, b: i32) -> i32 {\n    a + b\n}\n// old trailer"
+    );
+    assert_eq!(
+        reconstruct_file(FULL_CONTEXT_DIFF, "src/lib.rs", Side::After).unwrap(),
+        "fn add(a: i32, b: i32) -> i32 {\n    a.saturating_add(b)\n}\n// new trailer\n"
+    );
+    assert_eq!(
+        reconstruct_file(FULL_CONTEXT_DIFF, "NEW.md", Side::After).unwrap(),
+        "# New\n--- not a header\n"
+    );
+}
+
+#[test]
+fn test_reconstruct_file_errors() {
+    assert!(matches!(
+        reconstruct_file(FULL_CONTEXT_DIFF, "missing.rs", Side::After),
+        Err(agpod_core::Error::Review(ReviewError::FileNotInDiff { .. }))
+    ));
+    assert!(matches!(
+        reconstruct_file(FULL_CONTEXT_DIFF, "NEW.md", Side::Before),
+        Err(agpod_core::Error::Review(ReviewError::Reconstruct { .. }))
+    ));
+
+    let partial = "diff --git a/a.txt b/a.txt
+--- a/a.txt
++++ b/a.txt
+@@ -1,2 +1,2 @@
+ one
+-two
++TWO
+@@ -10,2 +10,2 @@
+ ten
+-eleven
++ELEVEN
+";
+    let err = reconstruct_file(partial, "a.txt", Side::After).unwrap_err();
+    assert!(err.to_string().contains("lines 3-9 are not in the diff"));
+
+    let late_start = "diff --git a/a.txt b/a.txt
+--- a/a.txt
++++ b/a.txt
+@@ -5,1 +5,1 @@
+-five
++FIVE
+";
+    let err = reconstruct_file(late_start, "a.txt", Side::Before).unwrap_err();
+    assert!(err.to_string().contains("lines 1-4 are not in the diff"));
+}
+
+#[test]
+fn test_exclude_matcher_gitignore_semantics() {
+    let temp = tempfile::tempdir().unwrap();
+    let exclude_file = temp.path().join(".agpodignore");
+    fs::write(
+        &exclude_file,
+        "# lockfiles are noise\n*.lock\n!important.lock\nvendor/\n/generated.rs\n",
+    )
+    .unwrap();
+
+    let matcher = ExcludeMatcher::new(Some(&exclude_file), &[]).unwrap();
+    assert!(matcher.is_match("Cargo.lock"));
+    assert!(matcher.is_match("web/yarn.lock"));
+    assert!(!matcher.is_match("important.lock"));
+    assert!(matcher.is_match("vendor/lib/a.c"));
+    assert!(matcher.is_match("generated.rs"));
+    assert!(!matcher.is_match("src/generated.rs"));
+    assert!(!matcher.is_match("src/main.rs"));
+
+    // command-line patterns come last and win
+    let matcher =
+        ExcludeMatcher::new(Some(&exclude_file), &["important.lock".to_string()]).unwrap();
+    assert!(matcher.is_match("important.lock"));
+    let matcher = ExcludeMatcher::new(Some(&exclude_file), &["!Cargo.lock".to_string()]).unwrap();
+    assert!(!matcher.is_match("Cargo.lock"));
+
+    // a missing exclude file means nothing is excluded
+    let matcher = ExcludeMatcher::new(Some(&temp.path().join("missing")), &[]).unwrap();
+    assert!(!matcher.is_match("Cargo.lock"));
+
+    let err = ExcludeMatcher::new(None, &["src/{a,b".to_string()]).unwrap_err();
+    assert!(matches!(
+        err,
+        agpod_core::Error::Config(ConfigError::InvalidExcludePattern { .. })
+    ));
+}
+
+const EXCLUDE_DIFF: &str = "diff --git a/Cargo.lock b/Cargo.lock
+index 1111111..2222222 100644
+--- a/Cargo.lock
++++ b/Cargo.lock
+@@ -1 +1 @@
+-version = 3
++version = 4
+diff --git a/src/main.rs b/src/main.rs
+index 1111111..2222222 100644
+--- a/src/main.rs
++++ b/src/main.rs
+@@ -1 +1 @@
+-fn main() {}
++fn main() { run() }
+";
+
+#[test]
+fn test_minimize_diff_summarizes_excluded_files() {
+    let matcher = ExcludeMatcher::new(None, &["*.lock".to_string()]).unwrap();
+    let result = minimize_diff_with_exclude(EXCLUDE_DIFF, &DiffConfig::default(), &matcher);
+    assert!(result.starts_with("Excluded file: Cargo.lock\n\n"));
+    assert!(!result.contains("version = 4"));
+    assert!(result.contains("+fn main() { run() }"));
+}
+
+#[test]
+fn test_save_diff_chunks_skips_excluded_files() {
+    let temp = tempfile::tempdir().unwrap();
+    let output_dir = temp.path().join("review");
+    let matcher = ExcludeMatcher::new(None, &["*.lock".to_string()]).unwrap();
+
+    save_diff_chunks_with_config(
+        EXCLUDE_DIFF,
+        output_dir.to_str().unwrap(),
+        None,
+        &DiffConfig::default(),
+        &matcher,
+    )
+    .unwrap();
+
+    let review_dir = resolve_review_dir(output_dir.to_str().unwrap());
+    let review = fs::read_to_string(review_dir.join("REVIEW.md")).unwrap();
+    assert!(review.contains("## src/main.rs"));
+    assert!(!review.contains("Cargo.lock"));
+    assert!(review_dir.join("chunk_aa.diff").is_file());
+    assert!(!review_dir.join("chunk_ab.diff").exists());
+}
+
+#[test]
+fn test_summarize_chunk() {
+    let chunk = "diff --git a/a.txt b/a.txt
+index 1111111..2222222 100644
+--- a/a.txt
++++ b/a.txt
+@@ -1,3 +1,3 @@ fn first
+ keep
+-old
++new
+@@ -10,2 +10,4 @@
++one
++two
+";
+    assert_eq!(estimate_tokens("abcdefgh"), 2);
+    assert_eq!(estimate_tokens("abcdefghi"), 3);
+    assert_eq!(
+        summarize_chunk(chunk, 30, 10),
+        "diff --git a/a.txt b/a.txt
+index 1111111..2222222 100644
+--- a/a.txt
++++ b/a.txt
+# truncated: ~30 tokens over max_tokens_per_chunk (10); hunk headers and line counts only
+@@ -1,3 +1,3 @@ fn first
+# +1 -1
+@@ -10,2 +10,4 @@
+# +2 -0
+"
+    );
+}
+
+#[test]
+fn test_save_diff_chunks_truncates_large_chunks() {
+    let temp = tempfile::tempdir().unwrap();
+    let output_dir = temp.path().join("review");
+    let output = output_dir.to_str().unwrap();
+    let review_dir = resolve_review_dir(output);
+    let config = DiffConfig {
+        max_tokens_per_chunk: Some(20),
+        ..DiffConfig::default()
+    };
+    let exclude = ExcludeMatcher::default();
+
+    save_diff_chunks_with_config(EXCLUDE_DIFF, output, None, &config, &exclude).unwrap();
+    let review = fs::read_to_string(review_dir.join("REVIEW.md")).unwrap();
+    let entries = parse_existing_review(&review);
+    assert!(entries.values().all(|entry| entry.truncated));
+    assert_eq!(review.matches("- meta:truncated: true\n").count(), 2);
+    let chunk = fs::read_to_string(review_dir.join("chunk_ab.diff")).unwrap();
+    assert!(chunk.contains("@@ -1 +1 @@\n# +1 -1\n"));
+    assert!(!chunk.contains("fn main() { run() }"));
+
+    // the hash covers the full chunk, so lifting the limit keeps statuses
+    save_diff_chunks(EXCLUDE_DIFF, output, None).unwrap();
+    let review = fs::read_to_string(review_dir.join("REVIEW.md")).unwrap();
+    assert!(!review.contains("meta:truncated"));
+    assert_eq!(review.matches("- meta:status: pending").count(), 2);
+    let entries = parse_existing_review(&review);
+    assert!(entries.values().all(|entry| !entry.truncated));
+    let chunk = fs::read_to_string(review_dir.join("chunk_ab.diff")).unwrap();
+    assert!(chunk.contains("+fn main() { run() }"));
+}
+
+#[test]
+fn test_save_diff_chunks_patch_format() {
+    let temp = tempfile::tempdir().unwrap();
+    let output_dir = temp.path().join("review");
+    let output = output_dir.to_str().unwrap();
+    let review_dir = resolve_review_dir(output);
+    let exclude = ExcludeMatcher::default();
+
+    save_diff_chunks(EXCLUDE_DIFF, output, None).unwrap();
+    let review = fs::read_to_string(review_dir.join("REVIEW.md")).unwrap();
+    fs::write(
+        review_dir.join("REVIEW.md"),
+        review.replace(
+            "- meta:status: pending",
+            "- meta:status: reviewed@2024-05-01",
+        ),
+    )
+    .unwrap();
+
+    let config = DiffConfig {
+        chunk_format: agpod_core::ChunkFormat::Patch,
+        ..DiffConfig::default()
+    };
+    save_diff_chunks_with_config(EXCLUDE_DIFF, output, None, &config, &exclude).unwrap();
+
+    // old .diff chunks are cleaned up and statuses survive the format switch
+    assert!(!review_dir.join("chunk_aa.diff").exists());
+    let review = fs::read_to_string(review_dir.join("REVIEW.md")).unwrap();
+    assert!(review.contains("- meta:diff_chunk: chunk_ab.patch\n"));
+    assert_eq!(
+        review.matches("- meta:status: reviewed@2024-05-01").count(),
+        2
+    );
+
+    let patch = fs::read_to_string(review_dir.join("chunk_ab.patch")).unwrap();
+    assert_eq!(
+        patch,
+        "From 0000000000000000000000000000000000000000 Mon Sep 17 00:00:00 2001
+From: agpod <agpod@localhost>
+Subject: [agpod] modify src/main.rs
+
+---
+diff --git a/src/main.rs b/src/main.rs
+index 1111111..2222222 100644
+--- a/src/main.rs
++++ b/src/main.rs
+@@ -1 +1 @@
+-fn main() {}
++fn main() { run() }
+"
+    );
+}
+
+const PLAIN_UNIFIED_DIFF: &str = "--- old.txt\t2024-05-01 10:32:32.053610452 +0000
++++ new.txt\t2024-05-01 10:32:40.000000000 +0000
+@@ -1,3 +1,4 @@
+ alpha
+-beta
++BETA
+ gamma
++delta
+";
+
+#[test]
+fn test_parse_plain_unified_diff() {
+    assert!(check_diff_input(PLAIN_UNIFIED_DIFF).is_ok());
+
+    let file_changes = parse_git_diff(PLAIN_UNIFIED_DIFF);
+    assert_eq!(file_changes.len(), 1);
+    let change = &file_changes[0];
+    assert_eq!(change.old_path.as_deref(), Some("old.txt"));
+    assert_eq!(change.new_path.as_deref(), Some("new.txt"));
+    assert!(matches!(change.change_type, ChangeType::Modified));
+
+    let result = minimize_diff(PLAIN_UNIFIED_DIFF);
+    assert!(result.starts_with("diff --git a/old.txt b/new.txt\n--- old.txt\t"));
+    assert!(result.contains("+BETA\n gamma\n+delta\n"));
+
+    assert_eq!(
+        reconstruct_file(PLAIN_UNIFIED_DIFF, "new.txt", Side::After).unwrap(),
+        "alpha\nBETA\ngamma\ndelta\n"
+    );
+
+    let added = "--- /dev/null\n+++ notes.txt\n@@ -0,0 +1 @@\n+hello\n";
+    let change = &parse_git_diff(added)[0];
+    assert!(matches!(change.change_type, ChangeType::Added));
+    assert_eq!(change.old_path.as_deref(), Some("notes.txt"));
+}
+
+#[test]
+fn test_check_diff_input_rejects_non_diffs() {
+    assert!(check_diff_input("").is_ok());
+    assert!(check_diff_input("\n  \n").is_ok());
+    assert!(check_diff_input(SNAPSHOT_DIFF).is_ok());
+
+    let log = "commit 0123456789abcdef0123456789abcdef01234567\n\
+Author: agpod <agpod@localhost>\n\n    fix things\n";
+    let err = check_diff_input(log).unwrap_err().to_string();
+    assert!(err.contains("`git log` output"), "{err}");
+    assert!(check_diff_input("0123456 fix things\nabcdef0 add tests\n")
+        .unwrap_err()
+        .to_string()
+        .contains("`git log` output"));
+
+    // `git log -p` with a long message still contains a diff
+    let log_with_patch = format!("{log}{}\n{SNAPSHOT_DIFF}", "    more\n".repeat(60));
+    assert!(check_diff_input(&log_with_patch).is_ok());
+
+    let err = check_diff_input("just some notes\nabout the change\n").unwrap_err();
+    assert!(matches!(
+        err,
+        agpod_core::Error::Review(ReviewError::NotADiff { .. })
+    ));
+    assert!(err.to_string().contains("pipe `git diff`"));
+}
+
+// The same change diffed with `diff.noprefix` and `diff.mnemonicPrefix`
+// (`--cached`, `HEAD` and the work tree)
+const PREFIX_DEFAULT_DIFF: &str = include_str!("../tests/fixtures/prefix/default.diff");
+const PREFIX_FIXTURES: &[(&str, &str)] = &[
+    (
+        "noprefix",
+        include_str!("../tests/fixtures/prefix/noprefix.diff"),
+    ),
+    (
+        "mnemonic_cached",
+        include_str!("../tests/fixtures/prefix/mnemonic_cached.diff"),
+    ),
+    (
+        "mnemonic_head",
+        include_str!("../tests/fixtures/prefix/mnemonic_head.diff"),
+    ),
+];
+
+fn file_paths(diff: &str) -> Vec<(Option<String>, Option<String>)> {
+    parse_git_diff(diff)
+        .into_iter()
+        .map(|change| (change.old_path, change.new_path))
+        .collect()
+}
+
+#[test]
+fn test_parse_diff_header_prefixes() {
+    let expected = file_paths(PREFIX_DEFAULT_DIFF);
+    let path = |p: &str| Some(p.to_string());
+    assert_eq!(
+        expected,
+        vec![
+            (path("added.rs"), path("added.rs")),
+            (path("old.txt"), path("docs notes.txt")),
+            (path("src/lib.rs"), path("src/lib.rs")),
+        ]
+    );
+
+    let default_minimized = minimize_diff(PREFIX_DEFAULT_DIFF);
+    for (name, diff) in PREFIX_FIXTURES {
+        assert_eq!(file_paths(diff), expected, "{name}");
+        let minimized = minimize_diff(diff);
+        if *name == "mnemonic_head" {
+            // `git diff HEAD` also picks up an unstaged edit
+            assert_eq!(
+                minimized
+                    .replace("three", "two")
+                    .replace("139243a", "fd73c7d"),
+                default_minimized,
+                "{name}"
+            );
+        } else {
+            assert_eq!(minimized, default_minimized, "{name}");
+        }
+    }
+
+    let worktree = include_str!("../tests/fixtures/prefix/mnemonic_worktree.diff");
+    assert_eq!(
+        file_paths(worktree),
+        vec![(path("src/lib.rs"), path("src/lib.rs"))]
+    );
+    assert!(minimize_diff(worktree).contains("--- a/src/lib.rs\n+++ b/src/lib.rs\n"));
+
+    // a directory named like a prefix is not stripped without the pair
+    assert_eq!(
+        file_paths("diff --git a/x.rs a/x.rs\n"),
+        vec![(path("a/x.rs"), path("a/x.rs"))]
+    );
+    // the same path on both sides wins over the first separator
+    assert_eq!(
+        file_paths("diff --git a/x b/y b/x b/y\n"),
+        vec![(path("x b/y"), path("x b/y"))]
+    );
+}
+
+#[test]
+fn test_paths_with_spaces_keep_header_tab() {
+    let diff = "diff --git a/my notes.txt b/my notes.txt\n\
+index 1111111..2222222 100644\n\
+--- a/my notes.txt\t\n\
++++ b/my notes.txt\t\n\
+@@ -1 +1 @@\n\
+-old\n\
++new\n";
+    let headers = "--- a/my notes.txt\t\n+++ b/my notes.txt\t\n@@ -1 +1 @@\n";
+    assert!(minimize_diff(diff).contains(headers));
+    // prefixes are still normalized
+    let noprefix = diff
+        .replace("--- a/", "--- ")
+        .replace("+++ b/", "+++ ")
+        .replace("a/my notes.txt b/my notes.txt", "my notes.txt my notes.txt");
+    assert!(minimize_diff(&noprefix).contains(headers));
+
+    let temp = tempfile::tempdir().unwrap();
+    let output_dir = temp.path().join("review");
+    let output = output_dir.to_str().unwrap();
+    save_diff_chunks(diff, output, None).unwrap();
+    let chunk = fs::read_to_string(resolve_review_dir(output).join("chunk_aa.diff")).unwrap();
+    assert!(chunk.contains(headers));
+}
+
+const QUOTED_PATHS_DIFF: &str = include_str!("../tests/fixtures/quoted_paths.diff");
+
+#[test]
+fn test_quoted_paths_are_decoded() {
+    let path = |p: &str| Some(p.to_string());
+    assert_eq!(
+        file_paths(QUOTED_PATHS_DIFF),
+        vec![
+            // not UTF-8: the invalid byte is percent-encoded
+            (path("bad%FF.txt"), path("bad%FF.txt")),
+            (path("文件.txt"), path("文件.txt")),
+            (path("tab\tname.txt"), path("新.txt")),
+        ]
+    );
+
+    // headers keep git's quoting, so minimized diffs and chunks still apply
+    let minimized = minimize_diff(QUOTED_PATHS_DIFF);
+    assert!(minimized.contains("diff --git \"a/bad\\377.txt\" \"b/bad\\377.txt\"\n"));
+    assert!(minimized.contains(
+        "diff --git \"a/tab\\tname.txt\" \"b/\\346\\226\\260.txt\"\nsimilarity index 100%\n"
+    ));
+
+    let temp = tempfile::tempdir().unwrap();
+    let output_dir = temp.path().join("review");
+    let output = output_dir.to_str().unwrap();
+    save_diff_chunks(QUOTED_PATHS_DIFF, output, None).unwrap();
+    let review_dir = resolve_review_dir(output);
+    let review = fs::read_to_string(review_dir.join("REVIEW.md")).unwrap();
+    assert!(review.contains("## 文件.txt\n- meta:hash: "));
+    assert!(review.contains("- meta:quoted_path: \"\\346\\226\\207\\344\\273\\266.txt\"\n"));
+    assert!(review.contains("## bad%FF.txt\n"));
+    assert!(review.contains("- meta:quoted_path: \"bad\\377.txt\"\n"));
+    let entries = parse_review_entries(&review);
+    assert_eq!(
+        entries
+            .iter()
+            .map(|(file, _)| file.as_str())
+            .collect::<Vec<_>>(),
+        ["bad%FF.txt", "文件.txt", "新.txt"]
+    );
+
+    let section_start = QUOTED_PATHS_DIFF.find("diff --git \"a/\\346").unwrap();
+    let section_end = QUOTED_PATHS_DIFF.find("diff --git \"a/tab").unwrap();
+    let chunk = &entries[1].1.diff_chunk;
+    assert_eq!(
+        fs::read_to_string(review_dir.join(chunk.as_deref().unwrap())).unwrap(),
+        QUOTED_PATHS_DIFF[section_start..section_end]
+    );
+}
+
+#[test]
+fn test_save_diff_chunks_preserves_blank_lines() {
+    let diff = "diff --git a/notes.txt b/notes.txt
+index 1111111..2222222 100644
+--- a/notes.txt
++++ b/notes.txt
+@@ -1,6 +1,6 @@
+-first
++FIRST
+ 
+ 
+ 
+ 
+ last
+";
+    let temp = tempfile::tempdir().unwrap();
+    let output_dir = temp.path().join("review");
+    let output = output_dir.to_str().unwrap();
+    let chunk_path = resolve_review_dir(output).join("chunk_aa.diff");
+    let exclude = ExcludeMatcher::default();
+
+    // the minimized output squashes blank lines either way
+    assert!(minimize_diff(diff).contains("+FIRST\n \n \n last\n"));
+
+    save_diff_chunks(diff, output, None).unwrap();
+    assert_eq!(fs::read_to_string(&chunk_path).unwrap(), diff);
+
+    let config = DiffConfig {
+        preserve_blank_lines_in_chunks: false,
+        ..DiffConfig::default()
+    };
+    save_diff_chunks_with_config(diff, output, None, &config, &exclude).unwrap();
+    let chunk = fs::read_to_string(&chunk_path).unwrap();
+    assert!(chunk.ends_with("+FIRST\n \n \n last\n"));
+}
+
+#[test]
+fn test_minimize_diff_omitted_footer() {
+    let diff = format!(
+        "{EXCLUDE_DIFF}diff --git a/logo.png b/logo.png
+index 1111111..2222222 100644
+Binary files a/logo.png and b/logo.png differ
+diff --git a/old.md b/old.md
+deleted file mode 100644
+index 1111111..0000000
+--- a/old.md
++++ /dev/null
+@@ -1,2 +0,0 @@
+-# Old
+-text
+"
+    );
+    let exclude = ExcludeMatcher::new(None, &["*.lock".to_string()]).unwrap();
+    let result = minimize_diff_with_exclude(&diff, &DiffConfig::default(), &exclude);
+    assert!(result.ends_with(
+        "## Omitted or summarized files
+- Cargo.lock (excluded, +1 -1)
+- logo.png (binary, +0 -0)
+- old.md (deleted, +0 -2)
+"
+    ));
+
+    let config = DiffConfig {
+        omitted_footer: false,
+        ..DiffConfig::default()
+    };
+    let result = minimize_diff_with_exclude(&diff, &config, &exclude);
+    assert!(!result.contains("## Omitted"));
+
+    // nothing omitted, no footer
+    assert!(!minimize_diff(EXCLUDE_DIFF).contains("## Omitted"));
+}
+
+#[test]
+fn test_dir_lock_excludes_concurrent_holders() {
+    let temp = tempfile::tempdir().unwrap();
+    let output = temp.path().to_str().unwrap().to_string();
+    let review_dir = resolve_review_dir(&output);
+    fs::create_dir_all(&review_dir).unwrap();
+
+    let lock = DirLock::acquire(&review_dir, Duration::from_millis(10)).unwrap();
+    assert!(review_dir.join(LOCK_FILE_NAME).is_file());
+
+    let err = DirLock::acquire(&review_dir, Duration::from_millis(100)).unwrap_err();
+    assert!(matches!(err, agpod_core::Error::Io(IoError::Locked { .. })));
+    assert!(err
+        .to_string()
+        .starts_with("another agpod process holds the lock on"));
+
+    // saving waits for the holder and fails clearly once the timeout passes
+    let config = DiffConfig {
+        lock_timeout_ms: 100,
+        ..DiffConfig::default()
+    };
+    let exclude = ExcludeMatcher::default();
+    let err =
+        save_diff_chunks_with_config(EXCLUDE_DIFF, &output, None, &config, &exclude).unwrap_err();
+    assert!(matches!(err, agpod_core::Error::Io(IoError::Locked { .. })));
+
+    let release = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(100));
+        drop(lock);
+    });
+    let config = DiffConfig {
+        lock_timeout_ms: 5_000,
+        ..DiffConfig::default()
+    };
+    save_diff_chunks_with_config(EXCLUDE_DIFF, &output, None, &config, &exclude).unwrap();
+    release.join().unwrap();
+    assert!(review_dir.join("REVIEW.md").is_file());
+}
+
+const NOISE_DIFF: &str = "diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,3 +1,3 @@
+ fn main() {
+-    run(a,  b);
++    run(a, b);  
+ }
+@@ -10,4 +10,7 @@ fn helper() {
+-    let x = 1;
++    // explain x
++    /* spanning
++       lines */
++    let x = 2;
+     x
+ }
+diff --git a/tool.py b/tool.py
+index 1111111..2222222 100644
+--- a/tool.py
++++ b/tool.py
+@@ -1,2 +1,2 @@
+-# old note
++# new note
+ print(1)
+diff --git a/notes.txt b/notes.txt
+index 1111111..2222222 100644
+--- a/notes.txt
++++ b/notes.txt
+@@ -1 +1 @@
+-# heading
++# Heading
+";
+
+#[test]
+fn test_ignore_space_change_collapses_whitespace_only_hunks() {
+    let config = DiffConfig {
+        ignore_space_change: true,
+        ..DiffConfig::default()
+    };
+    let result = minimize_diff_with_config(NOISE_DIFF, &config);
+    assert!(result
+        .contains("diff --git a/src/lib.rs b/src/lib.rs\n# suppressed: 2 whitespace-only lines\n"));
+    assert!(!result.contains("@@ -1,3 +1,3 @@"));
+    assert!(!result.contains("run(a,  b)"));
+    assert!(result.contains("@@ -10,4 +10,7 @@ fn helper() {\n-    let x = 1;\n+    // explain x"));
+    // not a whitespace change
+    assert!(result.contains("-# heading\n+# Heading\n"));
+}
+
+#[test]
+fn test_ignore_space_change_keeps_real_changes_in_hunk() {
+    let diff = "diff --git a/a.rs b/a.rs
+--- a/a.rs
++++ b/a.rs
+@@ -1,2 +1,2 @@
+-let a  = 1;
++let a = 1;
+-let b = 1;
++let b = 2;
+";
+    let config = DiffConfig {
+        ignore_space_change: true,
+        ..DiffConfig::default()
+    };
+    // the whole run is not whitespace-only, so it stays as is
+    let result = minimize_diff_with_config(diff, &config);
+    assert!(result.contains("-let a  = 1;\n+let a = 1;\n-let b = 1;\n+let b = 2;\n"));
+    assert!(!result.contains("# suppressed"));
+}
+
+#[test]
+fn test_ignore_comments_drops_comment_lines() {
+    let config = DiffConfig {
+        ignore_comments: true,
+        ..DiffConfig::default()
+    };
+    let result = minimize_diff_with_config(NOISE_DIFF, &config);
+    assert!(
+        result.contains("diff --git a/src/lib.rs b/src/lib.rs\n# suppressed: 3 comment lines\n")
+    );
+    assert!(result.contains("-    let x = 1;\n+    let x = 2;\n     x\n"));
+    assert!(!result.contains("explain x"));
+    assert!(!result.contains("spanning"));
+    // the python hunk only changed a comment, so it is gone
+    assert!(result.contains("diff --git a/tool.py b/tool.py\n# suppressed: 2 comment lines\n"));
+    assert!(!result.contains("new note"));
+    // unknown extension: `#` is not a comment in a .txt file
+    assert!(result.contains("-# heading\n+# Heading\n"));
+}
+
+#[test]
+fn test_ignore_flags_leave_saved_chunks_alone() {
+    let temp = tempfile::tempdir().unwrap();
+    let output_dir = temp.path().join("review");
+    let output = output_dir.to_str().unwrap();
+    let config = DiffConfig {
+        ignore_space_change: true,
+        ignore_comments: true,
+        ..DiffConfig::default()
+    };
+
+    save_diff_chunks_with_config(
+        NOISE_DIFF,
+        output,
+        None,
+        &config,
+        &ExcludeMatcher::default(),
+    )
+    .unwrap();
+    let chunks: String = ["chunk_aa.diff", "chunk_ab.diff", "chunk_ac.diff"]
+        .iter()
+        .map(|name| fs::read_to_string(resolve_review_dir(output).join(name)).unwrap())
+        .collect();
+    assert!(chunks.contains("+    run(a, b);  \n"));
+    assert!(chunks.contains("+    // explain x\n"));
+    assert!(chunks.contains("+# new note\n"));
+    assert!(!chunks.contains("# suppressed"));
+}
+
+fn rename_diff(body: &str) -> String {
+    format!(
+        "diff --git a/src/old.rs b/src/new.rs\n\
+         similarity index 90%\n\
+         rename from src/old.rs\n\
+         rename to src/new.rs\n\
+         index 1111111..2222222 100644\n\
+         --- a/src/old.rs\n\
+         +++ b/src/new.rs\n\
+         {body}"
+    )
+}
+
+const RENAME_HUNK: &str = "@@ -1 +1 @@\n-fn old() {}\n+fn new() {}\n";
+
+#[test]
+fn test_rename_between_runs_keeps_review_entry() {
+    let temp = tempfile::tempdir().unwrap();
+    let output_dir = temp.path().join("review");
+    let output = output_dir.to_str().unwrap();
+    let review_path = resolve_review_dir(output).join("REVIEW.md");
+
+    let modified = format!(
+        "diff --git a/src/old.rs b/src/old.rs\n\
+         index 1111111..2222222 100644\n\
+         --- a/src/old.rs\n\
+         +++ b/src/old.rs\n\
+         {RENAME_HUNK}"
+    );
+    save_diff_chunks(&modified, output, None).unwrap();
+    let review = fs::read_to_string(&review_path).unwrap().replace(
+        "- meta:status: pending\n\n<!-- Review comments go here -->",
+        "- meta:status: reviewed@2024-05-01\n\nLooks good.",
+    );
+    fs::write(&review_path, review).unwrap();
+
+    // same hunks under the new name: status and comments move over
+    save_diff_chunks(&rename_diff(RENAME_HUNK), output, None).unwrap();
+    let review = fs::read_to_string(&review_path).unwrap();
+    let entries = parse_existing_review(&review);
+    assert_eq!(entries.len(), 1);
+    let entry = &entries["src/new.rs"];
+    assert_eq!(entry.status, "reviewed@2024-05-01");
+    assert_eq!(entry.comments, "Looks good.");
+    assert_eq!(entry.renamed_from.as_deref(), Some("src/old.rs"));
+    assert!(review.contains("- meta:renamed_from: src/old.rs\n- meta:status: reviewed@2024-05-01"));
+    assert!(!review.contains("## src/old.rs"));
+
+    // a rerun keeps the annotation and the status
+    save_diff_chunks(&rename_diff(RENAME_HUNK), output, None).unwrap();
+    let entries = parse_existing_review(&fs::read_to_string(&review_path).unwrap());
+    assert_eq!(entries["src/new.rs"].status, "reviewed@2024-05-01");
+    assert_eq!(
+        entries["src/new.rs"].renamed_from.as_deref(),
+        Some("src/old.rs")
+    );
+}
+
+#[test]
+fn test_rename_with_changed_hunks_is_outdated() {
+    let temp = tempfile::tempdir().unwrap();
+    let output_dir = temp.path().join("review");
+    let output = output_dir.to_str().unwrap();
+    let review_path = resolve_review_dir(output).join("REVIEW.md");
+
+    let modified = format!(
+        "diff --git a/src/old.rs b/src/old.rs\n\
+         index 1111111..2222222 100644\n\
+         --- a/src/old.rs\n\
+         +++ b/src/old.rs\n\
+         {RENAME_HUNK}"
+    );
+    save_diff_chunks(&modified, output, None).unwrap();
+    let review = fs::read_to_string(&review_path).unwrap().replace(
+        "- meta:status: pending\n\n<!-- Review comments go here -->",
+        "- meta:status: reviewed@2024-05-01\n\nLooks good.",
+    );
+    fs::write(&review_path, review).unwrap();
+
+    let changed = rename_diff("@@ -1 +1 @@\n-fn old() {}\n+fn newer() {}\n");
+    save_diff_chunks(&changed, output, None).unwrap();
+    let entries = parse_existing_review(&fs::read_to_string(&review_path).unwrap());
+    let entry = &entries["src/new.rs"];
+    assert_eq!(entry.status, "outdated");
+    assert_eq!(entry.comments, "Looks good.");
+    assert_eq!(entry.renamed_from.as_deref(), Some("src/old.rs"));
+}
+
+#[test]
+fn test_assign_chunk_suffixes_reuses_and_fills_gaps() {
+    let suffixes = assign_chunk_suffixes(&[
+        Some("chunk_ac.diff"),
+        None,
+        Some("chunk_aa.patch"),
+        // claimed by the first file already
+        Some("chunk_ac.diff"),
+        Some("notes.txt"),
+        None,
+    ]);
+    assert_eq!(suffixes, ["ac", "ab", "aa", "ad", "ae", "af"]);
+}
+
+fn modified_file_diff(path: &str) -> String {
+    format!(
+        "diff --git a/{path} b/{path}\n\
+         index 1111111..2222222 100644\n\
+         --- a/{path}\n\
+         +++ b/{path}\n\
+         @@ -1 +1 @@\n\
+         -old {path}\n\
+         +new {path}\n"
+    )
+}
+
+#[test]
+fn test_chunk_names_are_kept_across_runs() {
+    let temp = tempfile::tempdir().unwrap();
+    let output_dir = temp.path().join("review");
+    let output = output_dir.to_str().unwrap();
+    let review_dir = resolve_review_dir(output);
+    let chunk_of = |file: &str| {
+        let review = fs::read_to_string(review_dir.join("REVIEW.md")).unwrap();
+        parse_existing_review(&review)[file]
+            .diff_chunk
+            .clone()
+            .unwrap()
+    };
+
+    let diff: String = ["a.rs", "b.rs", "c.rs"].map(modified_file_diff).concat();
+    save_diff_chunks(&diff, output, None).unwrap();
+    assert_eq!(chunk_of("c.rs"), "chunk_ac.diff");
+
+    // b.rs is gone and 0.rs sorts first; it takes b.rs's free suffix
+    let diff: String = ["0.rs", "a.rs", "c.rs"].map(modified_file_diff).concat();
+    save_diff_chunks(&diff, output, None).unwrap();
+    assert_eq!(chunk_of("a.rs"), "chunk_aa.diff");
+    assert_eq!(chunk_of("c.rs"), "chunk_ac.diff");
+    assert_eq!(chunk_of("0.rs"), "chunk_ab.diff");
+    let chunk = fs::read_to_string(review_dir.join("chunk_ac.diff")).unwrap();
+    assert!(chunk.contains("+new c.rs"));
+
+    save_diff_chunks(&modified_file_diff("c.rs"), output, None).unwrap();
+    assert_eq!(chunk_of("c.rs"), "chunk_ac.diff");
+    assert!(!review_dir.join("chunk_aa.diff").exists());
+    assert!(!review_dir.join("chunk_ab.diff").exists());
+}
+
+#[test]
+fn test_plan_bundles_by_count_balances_tokens() {
+    let bundles = plan_bundles(&[50, 10, 40, 30, 20], Split::Count(2));
+    // 50 + 20 + 10 against 40 + 30
+    assert_eq!(bundles, [vec![0, 1, 4], vec![2, 3]]);
+
+    // fewer files than bundles leaves no empty bundle
+    assert_eq!(plan_bundles(&[5], Split::Count(3)), [vec![0]]);
+    assert!(plan_bundles(&[], Split::Count(2)).is_empty());
+}
+
+#[test]
+fn test_plan_bundles_by_tokens_never_splits_a_file() {
+    let bundles = plan_bundles(&[60, 30, 150, 40], Split::Tokens(100));
+    // the 150-token file is over the limit and gets a bundle of its own
+    assert_eq!(bundles, [vec![2], vec![0, 3], vec![1]]);
+}
+
+#[test]
+fn test_write_bundles_with_manifest() {
+    let temp = tempfile::tempdir().unwrap();
+    let output_dir = temp.path().join("review");
+    fs::create_dir_all(&output_dir).unwrap();
+    fs::write(output_dir.join("bundle_9.md"), "stale").unwrap();
+
+    let files = minimize_files(
+        SNAPSHOT_DIFF,
+        &DiffConfig::default(),
+        &ExcludeMatcher::default(),
+    );
+    let written =
+        write_bundles(&files, Split::Count(2), &output_dir, &DiffConfig::default()).unwrap();
+    assert_eq!(
+        written,
+        [
+            output_dir.join("bundle_1.md"),
+            output_dir.join("bundle_2.md")
+        ]
+    );
+    assert!(!output_dir.join("bundle_9.md").exists());
+
+    let bundles: Vec<String> = written
+        .iter()
+        .map(|path| fs::read_to_string(path).unwrap())
+        .collect();
+    assert!(bundles[0].starts_with("# Bundle 1 of 2 (~"));
+    assert!(
+        bundles[0].contains(" tokens)\n\n## Files\n- src/lib.rs\n\n---\n\ndiff --git a/src/lib.rs")
+    );
+    assert!(bundles[1].contains("## Files\n- new_name.rs\n- README.md\n\n---\n\n"));
+    // each bundle's footer covers its own files
+    assert!(bundles[1].ends_with("## Omitted or summarized files\n- README.md (deleted, +0 -2)\n"));
+    assert!(!bundles[0].contains("Omitted"));
+}
+
+#[test]
+fn test_diff_header_render() {
+    let mut header = DiffHeader {
+        repository: "agpod".to_string(),
+        branch: Some("main".to_string()),
+        commit: Some("abc1234".to_string()),
+        source: Some(
+            DiffSource::Git {
+                since_ref: Some("main".to_string()),
+                committed: false,
+            }
+            .to_string(),
+        ),
+        files: 3,
+        added: 12,
+        removed: 4,
+        context: Some("fixes #42".to_string()),
+    };
+    assert_eq!(
+        header.render(),
+        "- repository: agpod\n\
+         - branch: main @ abc1234\n\
+         - source: working tree vs merge-base of main and HEAD\n\
+         - files: 3 changed, +12 -4\n\
+         - context: fixes #42\n"
+    );
+
+    // multi-line context stays inside its list item
+    header.context = Some("fixes #42\n\n- keeps the old API\n".to_string());
+    assert!(header
+        .render()
+        .ends_with("- context: fixes #42\n\n  - keeps the old API\n"));
+
+    header.branch = None;
+    header.source = Some(DiffSource::Stdin.to_string());
+    assert!(header.render().contains("- source: stdin\n"));
+    header.source = None;
+    header.context = None;
+    assert_eq!(
+        header.render(),
+        "- repository: agpod\n- commit: abc1234\n- files: 3 changed, +12 -4\n"
+    );
+}
+
+#[test]
+fn test_with_header_is_regenerated_in_review_preamble() {
+    let temp = tempfile::tempdir().unwrap();
+    let output_dir = temp.path().join("review");
+    let output = output_dir.to_str().unwrap();
+    let review_path = resolve_review_dir(output).join("REVIEW.md");
+    let config = DiffConfig {
+        with_header: true,
+        ..DiffConfig::default()
+    };
+    let save = || {
+        save_diff_chunks_with_config(
+            SNAPSHOT_DIFF,
+            output,
+            Some("fixes #42"),
+            &config,
+            &ExcludeMatcher::default(),
+        )
+        .unwrap()
+    };
+
+    save();
+    let review = fs::read_to_string(&review_path).unwrap();
+    let header = format!(
+        "<!-- agpod:header -->\n- repository: {}\n",
+        get_project_identifier()
+    );
+    assert!(review.contains(&header));
+    assert!(review
+        .contains("- files: 3 changed, +2 -3\n- context: fixes #42\n<!-- /agpod:header -->\n"));
+
+    let review = review.replacen(
+        "<!-- /agpod:header -->\n\n",
+        "<!-- /agpod:header -->\n\nSprint notes.\n\n",
+        1,
+    );
+    fs::write(&review_path, review).unwrap();
+    save();
+    let review = fs::read_to_string(&review_path).unwrap();
+    assert_eq!(review.matches("<!-- agpod:header -->").count(), 1);
+    assert!(review.contains("<!-- /agpod:header -->\n\nSprint notes.\n\n## Context"));
+}
+
+const SIGNATURES_DIFF: &str = "diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,12 +1,13 @@
+ use std::collections::{
+-    HashMap,
++    BTreeMap,
+ };
+-use std::fs;
++use std::io;
+ 
+-pub fn load(path: &str) -> String {
++pub fn load(path: &Path) -> String {
+     body();
+ }
+-struct Old;
++pub(crate) struct New {
++    inner: u32,
++}
+ impl Loader for Disk {
+-    fn read(&self) {}
++    fn read(&self) -> bool {}
+ }
+diff --git a/tool.py b/tool.py
+index 3333333..4444444 100644
+--- a/tool.py
++++ b/tool.py
+@@ -1,4 +1,4 @@
+-from os import (path,
+-    sep)
++from os import (path,
++    sep, getcwd)
+-def run(args):
++async def run(args, env):
+     pass
+diff --git a/notes.txt b/notes.txt
+index 5555555..6666666 100644
+--- a/notes.txt
++++ b/notes.txt
+@@ -1 +1 @@
+-fn old()
++fn new()
+";
+
+#[test]
+fn test_signatures_in_summary_lists_changed_items() {
+    let config = DiffConfig {
+        large_file_changes_threshold: 1,
+        signatures_in_summary: true,
+        ..DiffConfig::default()
+    };
+    let result = minimize_diff_with_config(SIGNATURES_DIFF, &config);
+    assert!(result.contains(
+        "Content lines: 23\n\
+         Imports:\n  \
+           - HashMap,\n  \
+           + BTreeMap,\n  \
+           - use std::fs;\n  \
+           + use std::io;\n\
+         Signatures:\n  \
+           ~ pub fn load(path: &Path) -> String (was: pub fn load(path: &str) -> String)\n  \
+           - struct Old;\n  \
+           + pub(crate) struct New\n\n"
+    ));
+    // indented methods are not top-level
+    assert!(!result.contains("fn read"));
+    assert!(result.contains(
+        "Imports:\n  \
+           - from os import (path,\n  \
+           - sep)\n  \
+           + from os import (path,\n  \
+           + sep, getcwd)\n\
+         Signatures:\n  \
+           ~ async def run(args, env) (was: def run(args))\n\n"
+    ));
+    // no extractor for .txt files
+    assert!(result
+        .contains("Large file change: notes.txt\nChange type: modified\nContent lines: 6\n\n"));
+
+    let result = minimize_diff_with_config(
+        SIGNATURES_DIFF,
+        &DiffConfig {
+            signatures_in_summary: false,
+            ..config
+        },
+    );
+    assert!(!result.contains("Signatures:"));
+}
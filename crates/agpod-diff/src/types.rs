@@ -1,15 +1,85 @@
 //! Type definitions for diff processing
 
+use super::quote::prefixed;
+
 /// Represents a single file change in a git diff
 #[derive(Debug)]
 pub struct FileChange {
     pub old_path: Option<String>,
     pub new_path: Option<String>,
+    /// `old_path` as git quoted it, e.g. `"\346\226\207.txt"`; `None` when
+    /// git wrote it unquoted
+    pub quoted_old_path: Option<String>,
+    /// `new_path` as git quoted it
+    pub quoted_new_path: Option<String>,
     pub change_type: ChangeType,
     pub content_lines: Vec<String>,
     pub is_large: bool,
 }
 
+impl FileChange {
+    /// The new path, falling back to the old one
+    pub fn display_path(&self) -> &str {
+        self.new_path
+            .as_deref()
+            .or(self.old_path.as_deref())
+            .unwrap_or("unknown")
+    }
+
+    /// Git's quoted form of `display_path`, if git quoted it
+    pub fn quoted_display_path(&self) -> Option<&str> {
+        match self.new_path {
+            Some(_) => self.quoted_new_path.as_deref(),
+            None => self.quoted_old_path.as_deref(),
+        }
+    }
+
+    /// The `diff --git` line with default prefixes, quoting paths the way
+    /// git did so the header still applies
+    pub fn git_header(&self) -> String {
+        let path = self.display_path();
+        format!(
+            "diff --git {} {}",
+            prefixed(
+                "a/",
+                self.old_path.as_deref().unwrap_or(path),
+                self.quoted_old_path.as_deref()
+            ),
+            prefixed(
+                "b/",
+                self.new_path.as_deref().unwrap_or(path),
+                self.quoted_new_path.as_deref()
+            )
+        )
+    }
+
+    /// Added and removed line counts from the hunks
+    pub fn line_counts(&self) -> (usize, usize) {
+        let mut in_hunks = false;
+        let (mut added, mut removed) = (0, 0);
+        for line in &self.content_lines {
+            in_hunks |= line.starts_with("@@");
+            if !in_hunks {
+                continue;
+            }
+            if line.starts_with('+') {
+                added += 1;
+            } else if line.starts_with('-') {
+                removed += 1;
+            }
+        }
+        (added, removed)
+    }
+
+    /// Whether git reported the file as binary instead of showing hunks
+    pub fn is_binary(&self) -> bool {
+        self.content_lines.iter().any(|line| {
+            line == "GIT binary patch"
+                || (line.starts_with("Binary files ") && line.ends_with(" differ"))
+        })
+    }
+}
+
 /// Type of change detected in a git diff
 #[derive(Debug)]
 pub enum ChangeType {
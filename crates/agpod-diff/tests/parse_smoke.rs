@@ -0,0 +1,27 @@
+//! Parses a generated multi-megabyte diff and checks that every file and
+//! line is accounted for; `cargo bench` measures the speed.
+
+mod support;
+
+use agpod_diff::parse_git_diff;
+use support::{generate_diff, FixtureSpec};
+
+#[test]
+fn parsing_generated_diff_keeps_every_line() {
+    let spec = FixtureSpec::with_size(2 * 1024 * 1024, 7);
+    let diff = generate_diff(&spec);
+    assert!(diff.len() >= 2 * 1024 * 1024);
+    let small = FixtureSpec { files: 20, ..spec };
+    assert_eq!(generate_diff(&small), generate_diff(&small));
+
+    let files = parse_git_diff(&diff);
+
+    assert_eq!(files.len(), spec.files);
+    // `index`, `---` and `+++`, then a hunk header and eleven lines per
+    // hunk; the `diff --git` line itself is not kept
+    let lines_per_file = 3 + spec.hunks_per_file * 12;
+    assert!(files
+        .iter()
+        .all(|file| file.content_lines.len() == lines_per_file));
+    assert_eq!(files.len() * (1 + lines_per_file), diff.lines().count());
+}
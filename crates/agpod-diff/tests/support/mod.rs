@@ -0,0 +1,112 @@
+//! Deterministic synthetic diffs for the benchmarks and the parser smoke
+//! test: the same spec always produces byte-identical output.
+
+#![allow(dead_code)]
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fmt::Write;
+
+const EXTENSIONS: [&str; 5] = ["rs", "ts", "py", "md", "toml"];
+const CONTEXT_LINES: usize = 3;
+const VOCABULARY_SIZE: usize = 512;
+
+/// Shape of a generated diff
+#[derive(Debug, Clone, Copy)]
+pub struct FixtureSpec {
+    pub files: usize,
+    pub hunks_per_file: usize,
+    /// Length of each source line, without the diff marker
+    pub line_len: usize,
+    pub seed: u64,
+}
+
+impl FixtureSpec {
+    /// A spec producing roughly `bytes` of diff, scaling the file count
+    pub fn with_size(bytes: usize, seed: u64) -> Self {
+        let mut spec = Self {
+            files: 1,
+            hunks_per_file: 8,
+            line_len: 60,
+            seed,
+        };
+        let per_file = generate_diff(&spec).len();
+        spec.files = bytes.div_ceil(per_file).max(1);
+        spec
+    }
+}
+
+/// Generate a `git diff`-style diff: each file gets `hunks_per_file` hunks
+/// of three context lines, two removed, three added and three context
+pub fn generate_diff(spec: &FixtureSpec) -> String {
+    let mut rng = StdRng::seed_from_u64(spec.seed);
+    let words = vocabulary(&mut rng);
+    let mut diff = String::new();
+    for file in 0..spec.files {
+        let extension = EXTENSIONS[file % EXTENSIONS.len()];
+        let path = format!("src/module_{}/file_{}.{}", file / 50, file, extension);
+        writeln!(diff, "diff --git a/{path} b/{path}").unwrap();
+        writeln!(
+            diff,
+            "index {:07x}..{:07x} 100644",
+            rng.gen::<u32>() >> 4,
+            rng.gen::<u32>() >> 4
+        )
+        .unwrap();
+        writeln!(diff, "--- a/{path}\n+++ b/{path}").unwrap();
+
+        let mut old_start = 1 + rng.gen_range(0..20);
+        for hunk in 0..spec.hunks_per_file {
+            let new_start = old_start + hunk;
+            writeln!(
+                diff,
+                "@@ -{},{} +{},{} @@",
+                old_start,
+                2 * CONTEXT_LINES + 2,
+                new_start,
+                2 * CONTEXT_LINES + 3
+            )
+            .unwrap();
+            let lines = [
+                (' ', CONTEXT_LINES),
+                ('-', 2),
+                ('+', 3),
+                (' ', CONTEXT_LINES),
+            ];
+            for (marker, count) in lines {
+                for _ in 0..count {
+                    diff.push(marker);
+                    push_line(&mut diff, &mut rng, &words, spec.line_len);
+                }
+            }
+            old_start += 2 * CONTEXT_LINES + 2 + rng.gen_range(10..40);
+        }
+    }
+    diff
+}
+
+/// Random lowercase words to build lines from, so a line costs one random
+/// draw per word rather than per byte
+fn vocabulary(rng: &mut StdRng) -> Vec<String> {
+    (0..VOCABULARY_SIZE)
+        .map(|_| {
+            let len = rng.gen_range(2..10);
+            (0..len)
+                .map(|_| rng.gen_range(b'a'..=b'z') as char)
+                .collect()
+        })
+        .collect()
+}
+
+/// A line of words, `len` bytes long
+fn push_line(diff: &mut String, rng: &mut StdRng, words: &[String], len: usize) {
+    let start = diff.len();
+    while diff.len() - start < len {
+        if diff.len() > start {
+            diff.push(' ');
+        }
+        diff.push_str(&words[rng.gen_range(0..words.len())]);
+    }
+    diff.truncate(start + len);
+    diff.push('\n');
+}
@@ -0,0 +1,57 @@
+//! Minimal glob matching for branch names.
+
+/// Match `text` against a glob `pattern` where `*` matches any run of
+/// characters (including `/`) and `?` matches exactly one character.
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    // position of the last `*` seen and the text index it is currently absorbing up to
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some('?') => {
+                p += 1;
+                t += 1;
+            }
+            Some(c) if *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, absorbed)) => {
+                    p = star + 1;
+                    t = absorbed + 1;
+                    backtrack = Some((star, absorbed + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("release/*", "release/1.2"));
+        assert!(glob_match("release/*", "release/"));
+        assert!(!glob_match("release/*", "hotfix/1.2"));
+        assert!(glob_match("*", "main"));
+        assert!(glob_match("feat*-ui", "feature/new-ui"));
+        assert!(glob_match("v?.?", "v1.2"));
+        assert!(!glob_match("v?.?", "v1.23"));
+        assert!(glob_match("main", "main"));
+        assert!(!glob_match("main", "main2"));
+    }
+}
@@ -1,21 +1,50 @@
+mod cache;
+mod glob;
+mod paths;
+mod remote;
+mod scan;
+
+use agpod_core::{format_relative_time_in, is_deterministic, Config, Locale};
 use anyhow::Result;
-use clap::Args;
-use git2::Repository;
-use std::collections::HashMap;
-use std::path::PathBuf;
+use cache::{LruCache, DEFAULT_CACHE_CAPACITY};
+use chrono::{DateTime, FixedOffset, Utc};
+use clap::{Args, ValueEnum};
+use git2::{DescribeFormatOptions, DescribeOptions, ErrorCode, Repository};
+use scan::{RepoRootWalker, DEFAULT_SCAN_IGNORES};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::io::{self, AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::Semaphore;
 use tokio_stream::wrappers::LinesStream;
 use tokio_stream::StreamExt;
 
+/// Default number of first-parent commits searched for jj bookmarks.
+#[cfg(feature = "jujutsu")]
+const DEFAULT_JJ_DEPTH: usize = 10;
+
+/// Length of the jj change id shown when no bookmark is found.
+#[cfg(feature = "jujutsu")]
+const JJ_SHORT_CHANGE_ID_LEN: usize = 8;
+
 #[derive(Debug, Args)]
 pub struct VcsPathInfoArgs {
-    /// Format the output with {path}, {branch} placeholder
+    /// Format the output with {path}, {branch}, {bookmarks}, {root}, {remote}, {repo},
+    /// {last_commit_time}, {last_commit_iso}, {last_commit_subject}, {stashes}, {tag}
+    /// placeholders, or {0}, {1}, ... for the original line fields
     #[arg(short = 'f', long)]
     format: Option<String>,
 
-    /// nth segment of line is the path, line segments separated by whitespace
-    #[arg(short = 'n', long)]
-    nth: Option<usize>,
+    /// nth segment of line is the path; negative values count from the end (-1 = last)
+    #[arg(short = 'n', long, allow_negative_numbers = true)]
+    nth: Option<isize>,
+
+    /// Field delimiter (`tab` for tab characters); defaults to runs of whitespace
+    #[arg(short = 'd', long)]
+    delimiter: Option<String>,
 
     /// Filter out non git repo path
     #[arg(long)]
@@ -24,14 +53,173 @@ pub struct VcsPathInfoArgs {
     /// Filter out bare repo
     #[arg(long)]
     no_bare: bool,
+
+    /// Disable the in-process cache of repository lookups
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Walk up from subdirectory paths to the containing repository; exposes {root}
+    #[arg(long)]
+    discover: bool,
+
+    /// Scan a directory tree for repositories instead of reading paths from stdin
+    #[arg(long, value_name = "DIR")]
+    scan: Option<PathBuf>,
+
+    /// Maximum directory depth to descend while scanning
+    #[arg(long, value_name = "N", requires = "scan")]
+    max_depth: Option<usize>,
+
+    /// Directory names to skip while scanning (default: node_modules, target)
+    #[arg(long = "scan-ignore", value_name = "NAME", requires = "scan")]
+    scan_ignores: Vec<String>,
+
+    /// Only emit repositories whose branch (or any jj bookmark) matches this glob
+    #[arg(long, value_name = "GLOB")]
+    branch_filter: Option<String>,
+
+    /// Invert --branch-filter, emitting lines whose branch does not match
+    #[arg(long, requires = "branch_filter")]
+    invert: bool,
+
+    /// Number of commits to search for jj bookmarks before falling back to the
+    /// change id (default: 10, or `[vcs_path] jj_depth`)
+    #[arg(long, value_name = "N")]
+    jj_depth: Option<usize>,
+
+    /// Sort output lines; buffers all input before printing instead of streaming
+    #[arg(long, value_enum)]
+    sort: Option<SortKey>,
+
+    /// Colorize the branch and dim non-repo paths; `auto` only colors a terminal
+    /// and honors NO_COLOR
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
+    /// Color used for the {branch} substitution
+    #[arg(long, value_enum, default_value_t = AnsiColor::Green)]
+    branch_color: AnsiColor,
+
+    /// Per-line repository lookup timeout in milliseconds; slower paths are
+    /// treated as non-repos. 0 disables the timeout
+    #[arg(long, value_name = "MS", default_value_t = 500)]
+    timeout_ms: u64,
+
+    /// Print `~` and relative input paths in their expanded absolute form in
+    /// {path} instead of as given
+    #[arg(long)]
+    expand_output: bool,
+
+    /// Remove this leading directory from {path} in the output
+    #[arg(long, value_name = "PATH")]
+    strip_prefix: Option<PathBuf>,
+
+    /// Abbreviate the home directory to `~` in {path}; applied after --strip-prefix
+    #[arg(long)]
+    home_tilde: bool,
+
+    /// Suffix appended to {tag} when the working tree has uncommitted changes
+    #[arg(long, value_name = "SUFFIX")]
+    tag_dirty_suffix: Option<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum AnsiColor {
+    Black,
+    Red,
+    #[default]
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+}
+
+impl AnsiColor {
+    fn code(self) -> u8 {
+        match self {
+            AnsiColor::Black => 30,
+            AnsiColor::Red => 31,
+            AnsiColor::Green => 32,
+            AnsiColor::Yellow => 33,
+            AnsiColor::Blue => 34,
+            AnsiColor::Magenta => 35,
+            AnsiColor::Cyan => 36,
+            AnsiColor::White => 37,
+        }
+    }
+}
+
+const ANSI_DIM: u8 = 2;
+
+fn paint(text: &str, code: u8) -> String {
+    if text.is_empty() {
+        return String::new();
+    }
+    format!("\x1b[{code}m{text}\x1b[0m")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum SortKey {
+    /// Most recent HEAD commit first; non-repo lines last
+    LastCommit,
+}
+
+#[derive(Debug, Clone, Default)]
 struct CliOptions {
     format: Option<String>,
-    nth: usize,
+    nth: isize,
+    delimiter: Option<String>,
     filter: bool,
     no_bare: bool,
+    no_cache: bool,
+    discover: bool,
+    scan: Option<PathBuf>,
+    max_depth: Option<usize>,
+    scan_ignores: Vec<String>,
+    branch_filter: Option<String>,
+    invert: bool,
+    jj_depth: Option<usize>,
+    sort: Option<SortKey>,
+    /// Effective color setting, resolved against the output terminal.
+    color: bool,
+    branch_color: AnsiColor,
+    /// Per-line lookup timeout; `None` runs lookups inline without one.
+    timeout: Option<Duration>,
+    expand_output: bool,
+    strip_prefix: Option<PathBuf>,
+    home_tilde: bool,
+    tag_dirty_suffix: Option<String>,
+    /// Base directories for expanding `~` and relative input paths.
+    home: Option<PathBuf>,
+    cwd: Option<PathBuf>,
+    /// Language of `{last_commit_time}`, from the top-level `locale` setting.
+    locale: Locale,
+}
+
+impl CliOptions {
+    /// Whether the user format string references `{name}`; used to skip lookups
+    /// that would otherwise be wasted.
+    fn uses_placeholder(&self, name: &str) -> bool {
+        self.format
+            .as_deref()
+            .is_some_and(|format| format.contains(&format!("{{{name}}}")))
+    }
+
+    fn needs_last_commit(&self) -> bool {
+        self.sort == Some(SortKey::LastCommit)
+            || ["last_commit_time", "last_commit_iso", "last_commit_subject"]
+                .iter()
+                .any(|name| self.uses_placeholder(name))
+    }
 }
 
 impl From<VcsPathInfoArgs> for CliOptions {
@@ -39,80 +227,426 @@ impl From<VcsPathInfoArgs> for CliOptions {
         CliOptions {
             format: args.format,
             nth: args.nth.unwrap_or(0),
+            delimiter: args.delimiter.map(|d| match d.as_str() {
+                "tab" | "\\t" => "\t".to_owned(),
+                _ => d,
+            }),
             filter: args.filter,
             no_bare: args.no_bare,
+            no_cache: args.no_cache,
+            discover: args.discover,
+            scan: args.scan,
+            max_depth: args.max_depth,
+            scan_ignores: if args.scan_ignores.is_empty() {
+                DEFAULT_SCAN_IGNORES.iter().map(|s| s.to_string()).collect()
+            } else {
+                args.scan_ignores
+            },
+            branch_filter: args.branch_filter,
+            invert: args.invert,
+            jj_depth: args.jj_depth,
+            sort: args.sort,
+            color: match args.color {
+                ColorChoice::Always => true,
+                ColorChoice::Never => false,
+                ColorChoice::Auto => {
+                    std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal()
+                }
+            },
+            branch_color: args.branch_color,
+            timeout: (args.timeout_ms > 0).then(|| Duration::from_millis(args.timeout_ms)),
+            expand_output: args.expand_output,
+            strip_prefix: args.strip_prefix,
+            home_tilde: args.home_tilde,
+            tag_dirty_suffix: args.tag_dirty_suffix,
+            home: dirs::home_dir(),
+            cwd: std::env::current_dir().ok(),
+            locale: Locale::default(),
         }
     }
 }
 
 #[derive(Debug)]
 struct VcsInfo<'a> {
-    path_index: usize,
+    path_index: Option<usize>,
     segments: Vec<&'a str>,
-    branch: Option<String>,
+    /// The path field with `~` and relative paths expanded, as used for lookup.
+    expanded_path: Option<String>,
+    repo: Option<RepoInfo>,
 }
 
 impl<'a> VcsInfo<'a> {
-    fn new(segments: Vec<&'a str>, path_index: usize) -> Self {
+    fn new(segments: Vec<&'a str>, nth: isize) -> Self {
+        // negative nth counts from the end, -1 being the last field
+        let path_index = if nth < 0 {
+            segments.len().checked_sub(nth.unsigned_abs())
+        } else {
+            Some(nth as usize)
+        };
         VcsInfo {
             path_index,
             segments,
-            branch: None,
+            expanded_path: None,
+            repo: None,
         }
     }
 
     fn path_str(&self) -> Option<&str> {
-        self.segments.get(self.path_index).copied()
+        self.path_index
+            .and_then(|index| self.segments.get(index))
+            .copied()
     }
 
-    async fn update_branch(&mut self, opts: &CliOptions) {
-        let path = self.path_str();
-        if path.is_none() {
+    /// The path as shown in output: as given, or expanded with `--expand-output`,
+    /// then shortened by `--strip-prefix` and `--home-tilde`.
+    fn display_path(&self, opts: &CliOptions) -> String {
+        let path = match &self.expanded_path {
+            Some(expanded) if opts.expand_output => expanded,
+            _ => self.path_str().unwrap_or(""),
+        };
+        if opts.strip_prefix.is_none() && !opts.home_tilde {
+            return path.to_owned();
+        }
+        let home = opts.home.as_deref().filter(|_| opts.home_tilde);
+        paths::shorten_for_display(path, opts.strip_prefix.as_deref(), home)
+    }
+
+    fn branch(&self) -> Option<&str> {
+        self.repo.as_ref().and_then(|repo| repo.branch.as_deref())
+    }
+
+    async fn update_repo(&mut self, resolver: &RepoResolver, opts: &CliOptions) {
+        let Some(path) = self.path_str() else {
             return;
+        };
+        let expanded = paths::expand_input_path(path, opts.home.as_deref(), opts.cwd.as_deref());
+        self.repo = resolver.resolve(&expanded).await;
+        self.expanded_path = Some(expanded.to_string_lossy().into_owned());
+    }
+}
+
+/// Repository details resolved for one input path.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct RepoInfo {
+    branch: Option<String>,
+    /// Individual jj bookmarks; `branch` holds them joined for display, or the
+    /// `@<change id>` fallback when none are within the search depth.
+    bookmarks: Vec<String>,
+    root: String,
+    remote: Option<String>,
+    last_commit: Option<CommitInfo>,
+    /// Number of stash entries; only looked up for `{stashes}`.
+    stashes: Option<usize>,
+    /// Nearest tag reachable from HEAD; only looked up for `{tag}`.
+    tag: Option<String>,
+}
+
+impl RepoInfo {
+    /// Branch names a filter may match against.
+    fn branch_names(&self) -> impl Iterator<Item = &str> {
+        let branch = self.bookmarks.is_empty().then_some(self.branch.as_deref());
+        self.bookmarks
+            .iter()
+            .map(String::as_str)
+            .chain(branch.flatten())
+    }
+}
+
+/// HEAD commit details; absent for repositories without commits.
+#[derive(Debug, Clone, PartialEq)]
+struct CommitInfo {
+    /// Seconds since the Unix epoch.
+    time: i64,
+    /// Committer timezone offset in minutes.
+    offset_minutes: i32,
+    subject: String,
+}
+
+impl CommitInfo {
+    fn from_head(repo: &Repository) -> Option<Self> {
+        let commit = repo.head().ok()?.peel_to_commit().ok()?;
+        let time = commit.time();
+        Some(CommitInfo {
+            time: time.seconds(),
+            offset_minutes: time.offset_minutes(),
+            subject: commit.summary().unwrap_or("").to_owned(),
+        })
+    }
+
+    /// Commit time in the committer's timezone.
+    fn local_time(&self) -> Option<DateTime<FixedOffset>> {
+        let offset = FixedOffset::east_opt(self.offset_minutes * 60)?;
+        Some(DateTime::from_timestamp(self.time, 0)?.with_timezone(&offset))
+    }
+
+    fn iso(&self) -> String {
+        self.local_time()
+            .map(|time| time.to_rfc3339())
+            .unwrap_or_default()
+    }
+
+    /// Relative time such as `3 days ago`; in deterministic mode the commit
+    /// date instead, since relative times change from run to run.
+    fn relative(&self, locale: Locale) -> String {
+        if is_deterministic() {
+            return self.date();
         }
-        let path = PathBuf::from(path.unwrap());
+        format_relative_time_in(Utc::now().timestamp() - self.time, locale)
+    }
 
-        #[cfg(feature = "jujutsu")]
-        {
-            // First, try to detect if this is a jujutsu repository
-            let jj_dir = path.join(".jj");
-            if jj_dir.exists() && jj_dir.is_dir() {
-                // This is a jujutsu repository
-                if let Some(bookmarks) = get_jj_bookmarks(&path) {
-                    self.branch = Some(bookmarks);
-                    return;
-                }
+    fn date(&self) -> String {
+        self.local_time()
+            .map(|time| time.format("%Y-%m-%d").to_string())
+            .unwrap_or_default()
+    }
+}
+
+type RepoLookup = Arc<dyn Fn(&Path, &CliOptions) -> Option<RepoInfo> + Send + Sync>;
+
+/// Upper bound on lookups running on the blocking pool at once. Lookups that
+/// hang past their timeout keep holding a slot, so a dead mount can stall at
+/// most this many threads.
+const MAX_IN_FLIGHT_LOOKUPS: usize = 16;
+
+/// Resolves repository details for a path, memoizing results per
+/// canonicalized path unless caching is disabled.
+///
+/// With a timeout configured, each lookup runs on the blocking pool and is
+/// abandoned when it overruns; the path is then reported as a non-repo.
+struct RepoResolver {
+    shared: Arc<ResolverShared>,
+    in_flight: Arc<Semaphore>,
+    timed_out: Mutex<HashSet<PathBuf>>,
+}
+
+struct ResolverShared {
+    opts: CliOptions,
+    cache: Option<Mutex<LruCache<PathBuf, Option<RepoInfo>>>>,
+    lookup: RepoLookup,
+}
+
+impl RepoResolver {
+    fn new(opts: &CliOptions) -> Self {
+        Self::with_lookup(opts, Arc::new(lookup_repo))
+    }
+
+    fn with_lookup(opts: &CliOptions, lookup: RepoLookup) -> Self {
+        let cache = (!opts.no_cache).then(|| Mutex::new(LruCache::new(DEFAULT_CACHE_CAPACITY)));
+        RepoResolver {
+            shared: Arc::new(ResolverShared {
+                opts: opts.clone(),
+                cache,
+                lookup,
+            }),
+            in_flight: Arc::new(Semaphore::new(MAX_IN_FLIGHT_LOOKUPS)),
+            timed_out: Mutex::new(HashSet::new()),
+        }
+    }
+
+    async fn resolve(&self, path: &Path) -> Option<RepoInfo> {
+        let Some(timeout) = self.shared.opts.timeout else {
+            return self.shared.resolve(path);
+        };
+
+        let shared = Arc::clone(&self.shared);
+        let owned_path = path.to_path_buf();
+        let lookup = async {
+            // waiting for a free slot counts against the timeout too, so a
+            // pool full of hung lookups cannot stall the pipeline
+            let permit = Arc::clone(&self.in_flight).acquire_owned().await.ok()?;
+            let task = tokio::task::spawn_blocking(move || {
+                let _permit = permit;
+                shared.resolve(&owned_path)
+            });
+            task.await.ok()
+        };
+
+        match tokio::time::timeout(timeout, lookup).await {
+            Ok(repo) => repo.flatten(),
+            Err(_) => {
+                self.warn_timeout(path, timeout);
+                None
             }
         }
+    }
 
-        // Fall back to git detection
-        let repo = Repository::open(&path).ok();
-        let Some(repo) = repo else {
-            return;
+    fn warn_timeout(&self, path: &Path, timeout: Duration) {
+        let first = self
+            .timed_out
+            .lock()
+            .map(|mut seen| seen.insert(path.to_path_buf()))
+            .unwrap_or(false);
+        if first {
+            eprintln!(
+                "Warning: repository lookup for {} timed out after {}ms; treating it as a non-repo",
+                path.display(),
+                timeout.as_millis()
+            );
+        }
+    }
+}
+
+impl ResolverShared {
+    fn resolve(&self, path: &Path) -> Option<RepoInfo> {
+        let Some(cache) = &self.cache else {
+            return (self.lookup)(path, &self.opts);
         };
 
-        if opts.no_bare && repo.is_bare() {
-            return;
+        let key = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if let Some(repo) = cache.lock().ok().and_then(|mut cache| cache.get(&key)) {
+            return repo;
         }
 
-        // filter out useless folder at <repo>/worktrees/<folder>
-        if Some(path.as_path()) != repo.workdir() {
-            return;
+        let repo = (self.lookup)(path, &self.opts);
+        if let Ok(mut cache) = cache.lock() {
+            cache.insert(key, repo.clone());
+        }
+        repo
+    }
+}
+
+fn lookup_repo(path: &Path, opts: &CliOptions) -> Option<RepoInfo> {
+    #[cfg(feature = "jujutsu")]
+    {
+        // First, try to detect if this is a jujutsu repository
+        if let Some(jj_root) = find_jj_root(path, opts.discover) {
+            let depth = opts.jj_depth.unwrap_or(DEFAULT_JJ_DEPTH);
+            if let Some(info) = get_jj_info(&jj_root, depth) {
+                return Some(RepoInfo {
+                    branch: Some(info.branch()),
+                    bookmarks: info.bookmarks,
+                    root: display_root(&jj_root),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+
+    // Fall back to git detection
+    let mut repo = if opts.discover {
+        Repository::discover(path).ok()?
+    } else {
+        Repository::open(path).ok()?
+    };
+
+    if opts.no_bare && repo.is_bare() {
+        return None;
+    }
+
+    if opts.discover {
+        // discovered subpaths are fine, but never report paths inside the git dir
+        // such as <repo>/.git/worktrees/<folder>
+        let path = path.canonicalize().ok()?;
+        let git_dir = repo.path().canonicalize().ok()?;
+        if path.starts_with(git_dir) {
+            return None;
         }
+    } else if Some(path) != repo.workdir() {
+        // filter out useless folder at <repo>/worktrees/<folder>
+        return None;
+    }
+
+    let branch = match repo.head() {
+        Ok(head) => head.shorthand().map(|s| s.to_owned()),
+        // a repository without commits yet is still a repository
+        Err(e) if e.code() == ErrorCode::UnbornBranch => None,
+        Err(_) => return None,
+    };
+    let root = display_root(repo.workdir().unwrap_or_else(|| repo.path()));
+    let remote = (opts.uses_placeholder("remote") || opts.uses_placeholder("repo"))
+        .then(|| remote::remote_url(&repo))
+        .flatten();
+    let last_commit = opts
+        .needs_last_commit()
+        .then(|| CommitInfo::from_head(&repo))
+        .flatten();
+    let tag = opts
+        .uses_placeholder("tag")
+        .then(|| nearest_tag(&repo, opts.tag_dirty_suffix.as_deref()))
+        .flatten();
+    let stashes = opts
+        .uses_placeholder("stashes")
+        .then(|| count_stashes(&mut repo));
+
+    Some(RepoInfo {
+        branch,
+        root,
+        remote,
+        last_commit,
+        stashes,
+        tag,
+        ..Default::default()
+    })
+}
+
+/// Name of the nearest tag reachable from HEAD, including lightweight tags,
+/// like `git describe --tags --abbrev=0 [--dirty=<suffix>]`.
+fn nearest_tag(repo: &Repository, dirty_suffix: Option<&str>) -> Option<String> {
+    let describe = repo.describe(DescribeOptions::new().describe_tags()).ok()?;
+    let mut format = DescribeFormatOptions::new();
+    format.abbreviated_size(0);
+    if let Some(suffix) = dirty_suffix {
+        format.dirty_suffix(suffix);
+    }
+    describe.format(Some(&format)).ok()
+}
+
+fn count_stashes(repo: &mut Repository) -> usize {
+    let mut count = 0;
+    // a failed walk just reports the entries seen so far
+    let _ = repo.stash_foreach(|_, _, _| {
+        count += 1;
+        true
+    });
+    count
+}
+
+fn display_root(root: &Path) -> String {
+    let root = root.to_string_lossy();
+    let trimmed = root.trim_end_matches(std::path::MAIN_SEPARATOR);
+    if trimmed.is_empty() {
+        root.into_owned()
+    } else {
+        trimmed.to_owned()
+    }
+}
+
+#[cfg(feature = "jujutsu")]
+fn find_jj_root(path: &Path, discover: bool) -> Option<PathBuf> {
+    let is_jj_root = |dir: &Path| dir.join(".jj").is_dir();
+    if !discover {
+        return is_jj_root(path).then(|| path.to_path_buf());
+    }
+    let path = path.canonicalize().ok()?;
+    path.ancestors()
+        .find(|dir| is_jj_root(dir))
+        .map(Path::to_path_buf)
+}
+
+/// Bookmarks near the jj working copy plus its short change id for fallback display.
+#[cfg(feature = "jujutsu")]
+struct JjInfo {
+    bookmarks: Vec<String>,
+    change_id: String,
+}
 
-        let branch = repo
-            .head()
-            .map(|head| head.shorthand().map(|s| s.to_owned()))
-            .ok()
-            .flatten();
-        self.branch = branch
+#[cfg(feature = "jujutsu")]
+impl JjInfo {
+    /// Joined bookmarks, or `@<change id>` when no bookmark is within reach.
+    fn branch(&self) -> String {
+        if self.bookmarks.is_empty() {
+            format!("@{}", self.change_id)
+        } else {
+            self.bookmarks.join(", ")
+        }
     }
 }
 
 #[cfg(feature = "jujutsu")]
-fn get_jj_bookmarks(path: &std::path::Path) -> Option<String> {
+fn get_jj_info(path: &std::path::Path, max_commits: usize) -> Option<JjInfo> {
     use indexmap::IndexSet;
     use jj_lib::local_working_copy::LocalWorkingCopyFactory;
+    use jj_lib::repo::Repo;
     use jj_lib::settings::UserSettings;
     use jj_lib::workspace::Workspace;
     use std::collections::HashSet;
@@ -133,38 +667,50 @@ fn get_jj_bookmarks(path: &std::path::Path) -> Option<String> {
 
     // Get the working copy commit ID as the starting point
     let wc_commit_id = repo.view().get_wc_commit_id(workspace.workspace_name())?;
+    let wc_commit = repo.store().get_commit(wc_commit_id).ok()?;
+    let change_id = wc_commit
+        .change_id()
+        .reverse_hex()
+        .chars()
+        .take(JJ_SHORT_CHANGE_ID_LEN)
+        .collect();
 
     // Traverse commits following first-parent chain
     let mut visited = HashSet::new();
     let mut bookmark_names = IndexSet::new();
 
     // Start traversal from working copy commit
-    traverse_first_parent(&repo, wc_commit_id, &mut visited, &mut bookmark_names);
+    traverse_first_parent(
+        &repo,
+        wc_commit_id,
+        max_commits,
+        &mut visited,
+        &mut bookmark_names,
+    );
 
-    if bookmark_names.is_empty() {
-        None
-    } else {
-        // Return sorted, deduplicated bookmark names
-        Some(bookmark_names.into_iter().collect::<Vec<_>>().join(", "))
-    }
+    // Deduplicated bookmark names in traversal order
+    Some(JjInfo {
+        bookmarks: bookmark_names.into_iter().collect(),
+        change_id,
+    })
 }
 
 #[cfg(feature = "jujutsu")]
 fn traverse_first_parent(
     repo: &jj_lib::repo::ReadonlyRepo,
     start_id: &jj_lib::backend::CommitId,
+    max_commits: usize,
     visited: &mut std::collections::HashSet<jj_lib::backend::CommitId>,
     bookmark_names: &mut indexmap::IndexSet<String>,
 ) {
     use jj_lib::repo::Repo;
 
-    const MAX_COMMITS: usize = 10;
     let mut current_id = start_id.clone();
     let mut commit_count = 0;
 
     loop {
         // Stop if we've checked enough commits
-        if commit_count >= MAX_COMMITS {
+        if commit_count >= max_commits {
             break;
         }
 
@@ -247,12 +793,21 @@ fn create_minimal_jj_config() -> Option<jj_lib::config::StackedConfig> {
 }
 
 pub async fn run(args: VcsPathInfoArgs) -> Result<()> {
-    let opts: CliOptions = args.into();
-    read_io_paths(&opts).await
+    let mut opts: CliOptions = args.into();
+    let config = Config::load();
+    if opts.jj_depth.is_none() {
+        opts.jj_depth = config.vcs_path.and_then(|cfg| cfg.jj_depth);
+    }
+    opts.locale = config.locale.unwrap_or_default();
+    let resolver = RepoResolver::new(&opts);
+    match opts.scan.as_deref() {
+        Some(dir) => scan_paths(&resolver, &opts, dir).await,
+        None => read_io_paths(&resolver, &opts).await,
+    }
 }
 
-async fn read_io_paths(opts: &CliOptions) -> Result<()> {
-    let mut writer = io::stdout();
+async fn read_io_paths(resolver: &RepoResolver, opts: &CliOptions) -> Result<()> {
+    let mut sink = OutputSink::new(opts);
 
     let stdin = io::stdin();
     let reader = BufReader::new(stdin);
@@ -261,26 +816,106 @@ async fn read_io_paths(opts: &CliOptions) -> Result<()> {
 
     while let Some(v) = lines_stream.next().await {
         let line = v?;
-        let Some(result_line) = process_line(opts, &line).await else {
+        let segments = split_line(&line, opts.delimiter.as_deref());
+        let Some(output) = resolve_fields(resolver, opts, segments, opts.nth).await else {
             continue;
         };
+        if !sink.push(output).await? {
+            return Ok(());
+        }
+    }
 
-        // Handle BrokenPipe errors gracefully - this happens when the downstream
-        // process closes the pipe early (e.g., head -n 1, fzf)
-        if let Err(e) = writer.write_all(result_line.as_bytes()).await {
-            if e.kind() == io::ErrorKind::BrokenPipe {
-                return Ok(());
+    sink.finish().await
+}
+
+async fn scan_paths(resolver: &RepoResolver, opts: &CliOptions, dir: &Path) -> Result<()> {
+    let mut sink = OutputSink::new(opts);
+
+    let walker = RepoRootWalker::new(dir, opts.max_depth, opts.scan_ignores.clone());
+    for repo_root in walker {
+        let path = repo_root.to_string_lossy();
+        let Some(output) = resolve_fields(resolver, opts, vec![&path], 0).await else {
+            continue;
+        };
+        if !sink.push(output).await? {
+            return Ok(());
+        }
+    }
+
+    sink.finish().await
+}
+
+/// One formatted output line plus the data it may be sorted by.
+#[derive(Debug)]
+struct OutputLine {
+    text: String,
+    last_commit_time: Option<i64>,
+}
+
+/// Streams lines to stdout, or buffers them when a sort order is requested.
+///
+/// Sorting needs every line before the first can be printed, so memory grows
+/// with the input size and downstream tools like fzf see nothing until EOF.
+struct OutputSink {
+    writer: io::Stdout,
+    buffered: Option<(SortKey, Vec<OutputLine>)>,
+}
+
+impl OutputSink {
+    fn new(opts: &CliOptions) -> Self {
+        OutputSink {
+            writer: io::stdout(),
+            buffered: opts.sort.map(|key| (key, Vec::new())),
+        }
+    }
+
+    /// Returns `false` once the downstream has closed the pipe.
+    async fn push(&mut self, line: OutputLine) -> Result<bool> {
+        match &mut self.buffered {
+            Some((_, lines)) => {
+                lines.push(line);
+                Ok(true)
+            }
+            None => write_line(&mut self.writer, &line.text).await,
+        }
+    }
+
+    async fn finish(mut self) -> Result<()> {
+        if let Some((key, mut lines)) = self.buffered.take() {
+            sort_lines(&mut lines, key);
+            for line in lines {
+                if !write_line(&mut self.writer, &line.text).await? {
+                    return Ok(());
+                }
             }
-            return Err(e.into());
         }
-        if let Err(e) = writer.write_all(b"\n").await {
+        finish_output(&mut self.writer).await
+    }
+}
+
+fn sort_lines(lines: &mut [OutputLine], key: SortKey) {
+    match key {
+        // stable sort keeps input order for ties and for lines without commits
+        SortKey::LastCommit => lines.sort_by_key(|line| Reverse(line.last_commit_time)),
+    }
+}
+
+/// Write one output line, returning `false` when the downstream closed the pipe.
+async fn write_line(writer: &mut io::Stdout, line: &str) -> Result<bool> {
+    // Handle BrokenPipe errors gracefully - this happens when the downstream
+    // process closes the pipe early (e.g., head -n 1, fzf)
+    for bytes in [line.as_bytes(), b"\n"] {
+        if let Err(e) = writer.write_all(bytes).await {
             if e.kind() == io::ErrorKind::BrokenPipe {
-                return Ok(());
+                return Ok(false);
             }
             return Err(e.into());
         }
     }
+    Ok(true)
+}
 
+async fn finish_output(writer: &mut io::Stdout) -> Result<()> {
     // Ignore BrokenPipe on shutdown as well
     if let Err(e) = writer.shutdown().await {
         if e.kind() != io::ErrorKind::BrokenPipe {
@@ -291,34 +926,155 @@ async fn read_io_paths(opts: &CliOptions) -> Result<()> {
     Ok(())
 }
 
-async fn process_line(opts: &CliOptions, line: &str) -> Option<String> {
-    // trim line then separate line by space
-    let segments = line.trim().split(' ').collect::<Vec<&str>>();
-    let mut vcsinfo = VcsInfo::new(segments, opts.nth);
-    vcsinfo.update_branch(opts).await;
+fn split_line<'a>(line: &'a str, delimiter: Option<&str>) -> Vec<&'a str> {
+    match delimiter {
+        Some(delimiter) if !delimiter.is_empty() => line
+            .trim_end_matches(['\r', '\n'])
+            .split(delimiter)
+            .map(str::trim)
+            .collect(),
+        _ => line.split_whitespace().collect(),
+    }
+}
+
+async fn resolve_fields(
+    resolver: &RepoResolver,
+    opts: &CliOptions,
+    segments: Vec<&str>,
+    nth: isize,
+) -> Option<OutputLine> {
+    let mut vcsinfo = VcsInfo::new(segments, nth);
+    vcsinfo.update_repo(resolver, opts).await;
 
-    if vcsinfo.branch.is_none() && opts.filter {
+    if let Some(pattern) = opts.branch_filter.as_deref() {
+        let matched = vcsinfo.repo.as_ref().is_some_and(|repo| {
+            repo.branch_names()
+                .any(|name| glob::glob_match(pattern, name))
+        });
+        if matched == opts.invert {
+            return None;
+        }
+    }
+
+    if vcsinfo.repo.is_none() && opts.filter {
         return None;
     }
 
-    if vcsinfo.branch.is_none() {
-        return Some(vcsinfo.path_str().unwrap_or("").to_owned());
+    if vcsinfo.repo.is_none() {
+        let path = vcsinfo.display_path(opts);
+        let text = if opts.color {
+            paint(&path, ANSI_DIM)
+        } else {
+            path
+        };
+        return Some(OutputLine {
+            text,
+            last_commit_time: None,
+        });
     }
 
+    let last_commit = vcsinfo
+        .repo
+        .as_ref()
+        .and_then(|repo| repo.last_commit.as_ref());
+    let text = format_fields(&vcsinfo, opts)?;
+    Some(OutputLine {
+        text,
+        last_commit_time: last_commit.map(|commit| commit.time),
+    })
+}
+
+fn format_fields(vcsinfo: &VcsInfo<'_>, opts: &CliOptions) -> Option<String> {
     let mut vars = HashMap::<String, &str>::new();
-    vars.insert("path".to_owned(), vcsinfo.path_str().unwrap_or(""));
-    vars.insert("branch".to_owned(), vcsinfo.branch.as_deref().unwrap_or(""));
+    for (index, segment) in vcsinfo.segments.iter().enumerate() {
+        vars.insert(index.to_string(), segment);
+    }
+    let path = vcsinfo.display_path(opts);
+    vars.insert("path".to_owned(), &path);
+    // color is applied to the substituted value so format strings stay plain
+    let branch = vcsinfo.branch().unwrap_or("");
+    let branch = if opts.color {
+        paint(branch, opts.branch_color.code())
+    } else {
+        branch.to_owned()
+    };
+    vars.insert("branch".to_owned(), &branch);
+    vars.insert(
+        "root".to_owned(),
+        vcsinfo.repo.as_ref().map_or("", |repo| repo.root.as_str()),
+    );
+    let bookmarks = vcsinfo
+        .repo
+        .as_ref()
+        .map(|repo| repo.bookmarks.join(", "))
+        .unwrap_or_default();
+    vars.insert("bookmarks".to_owned(), &bookmarks);
+    let remote = vcsinfo
+        .repo
+        .as_ref()
+        .and_then(|repo| repo.remote.as_deref());
+    let slug = remote.and_then(remote::repo_slug);
+    vars.insert("remote".to_owned(), remote.unwrap_or(""));
+    vars.insert("repo".to_owned(), slug.as_deref().unwrap_or(""));
+
+    let last_commit = vcsinfo
+        .repo
+        .as_ref()
+        .and_then(|repo| repo.last_commit.as_ref());
+    let last_commit_time = last_commit
+        .map(|commit| commit.relative(opts.locale))
+        .unwrap_or_default();
+    let last_commit_iso = last_commit.map(CommitInfo::iso).unwrap_or_default();
+    vars.insert("last_commit_time".to_owned(), &last_commit_time);
+    vars.insert("last_commit_iso".to_owned(), &last_commit_iso);
+    vars.insert(
+        "last_commit_subject".to_owned(),
+        last_commit.map_or("", |commit| commit.subject.as_str()),
+    );
+
+    let stashes = vcsinfo
+        .repo
+        .as_ref()
+        .and_then(|repo| repo.stashes)
+        .map(|count| count.to_string())
+        .unwrap_or_default();
+    vars.insert("stashes".to_owned(), &stashes);
+    vars.insert(
+        "tag".to_owned(),
+        vcsinfo
+            .repo
+            .as_ref()
+            .and_then(|repo| repo.tag.as_deref())
+            .unwrap_or(""),
+    );
 
     let fmt = opts.format.as_deref().unwrap_or("{path} {branch}");
     format_string(fmt, &vars)
 }
 
+/// Expand `{name}` placeholders left to right in one pass, so values are
+/// never expanded again; unknown placeholders are kept as written
 fn format_string(template: &str, vars: &HashMap<String, &str>) -> Option<String> {
-    let mut result = template.to_string();
-    for (key, value) in vars {
-        let placeholder = format!("{{{}}}", key);
-        result = result.replace(&placeholder, value);
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        result.push_str(&rest[..open]);
+        rest = &rest[open..];
+        let value = rest
+            .find('}')
+            .and_then(|close| Some((close, *vars.get(&rest[1..close])?)));
+        match value {
+            Some((close, value)) => {
+                result.push_str(value);
+                rest = &rest[close + 1..];
+            }
+            None => {
+                result.push('{');
+                rest = &rest[1..];
+            }
+        }
     }
+    result.push_str(rest);
     Some(result)
 }
 
@@ -326,6 +1082,17 @@ fn format_string(template: &str, vars: &HashMap<String, &str>) -> Option<String>
 mod tests {
     use super::*;
 
+    async fn process_line(
+        resolver: &RepoResolver,
+        opts: &CliOptions,
+        line: &str,
+    ) -> Option<String> {
+        let segments = split_line(line, opts.delimiter.as_deref());
+        resolve_fields(resolver, opts, segments, opts.nth)
+            .await
+            .map(|output| output.text)
+    }
+
     #[test]
     fn test_format_string() {
         let mut vars = HashMap::new();
@@ -336,6 +1103,19 @@ mod tests {
         assert_eq!(result, Some("/home/user/project [main]".to_string()));
     }
 
+    #[test]
+    fn test_format_string_does_not_expand_values() {
+        let mut vars = HashMap::new();
+        vars.insert("path".to_string(), "/repo/{branch}");
+        vars.insert("branch".to_string(), "x{path}");
+
+        let result = format_string("{path} {branch} {unknown} {{path}}", &vars);
+        assert_eq!(
+            result,
+            Some("/repo/{branch} x{path} {unknown} {/repo/{branch}}".to_string())
+        );
+    }
+
     #[test]
     fn test_format_string_default() {
         let mut vars = HashMap::new();
@@ -348,27 +1128,574 @@ mod tests {
 
     #[tokio::test]
     async fn test_process_line_no_git() {
-        let opts = CliOptions {
-            format: None,
-            nth: 0,
-            filter: false,
-            no_bare: false,
-        };
+        let opts = CliOptions::default();
 
-        let result = process_line(&opts, "/tmp/nonexistent").await;
+        let resolver = RepoResolver::new(&opts);
+        let result = process_line(&resolver, &opts, "/tmp/nonexistent").await;
         assert_eq!(result, Some("/tmp/nonexistent".to_string()));
     }
 
     #[tokio::test]
     async fn test_process_line_with_filter() {
         let opts = CliOptions {
-            format: None,
-            nth: 0,
             filter: true,
-            no_bare: false,
+            ..Default::default()
         };
 
-        let result = process_line(&opts, "/tmp/nonexistent").await;
+        let resolver = RepoResolver::new(&opts);
+        let result = process_line(&resolver, &opts, "/tmp/nonexistent").await;
         assert_eq!(result, None);
     }
+
+    #[test]
+    fn test_split_line_whitespace_and_delimiter() {
+        assert_eq!(
+            split_line("  12.5\t/home/user/project  ", None),
+            vec!["12.5", "/home/user/project"]
+        );
+        assert_eq!(split_line("a  b", None), vec!["a", "b"]);
+        assert_eq!(
+            split_line("12.5\t/home/user/my project", Some("\t")),
+            vec!["12.5", "/home/user/my project"]
+        );
+    }
+
+    #[test]
+    fn test_negative_nth_selects_from_end() {
+        let info = VcsInfo::new(vec!["12.5", "/tmp/a", "/tmp/b"], -1);
+        assert_eq!(info.path_str(), Some("/tmp/b"));
+        let info = VcsInfo::new(vec!["12.5", "/tmp/a", "/tmp/b"], -3);
+        assert_eq!(info.path_str(), Some("12.5"));
+        let info = VcsInfo::new(vec!["12.5"], -2);
+        assert_eq!(info.path_str(), None);
+    }
+
+    #[tokio::test]
+    async fn test_process_line_tab_delimited_last_field() {
+        let opts = CliOptions {
+            nth: -1,
+            delimiter: Some("\t".to_owned()),
+            ..Default::default()
+        };
+
+        let resolver = RepoResolver::new(&opts);
+        let result = process_line(&resolver, &opts, "42.0\t/tmp/nonexistent dir").await;
+        assert_eq!(result, Some("/tmp/nonexistent dir".to_string()));
+    }
+
+    #[test]
+    fn test_format_string_field_placeholders() {
+        let mut vars = HashMap::new();
+        vars.insert("0".to_string(), "42.0");
+        vars.insert("1".to_string(), "/home/user/project");
+        vars.insert("path".to_string(), "/home/user/project");
+        vars.insert("branch".to_string(), "main");
+
+        let result = format_string("{branch} {path} ({0})", &vars);
+        assert_eq!(result, Some("main /home/user/project (42.0)".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_lines_open_repository_once() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let opts = CliOptions::default();
+        let opens = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&opens);
+        let resolver = RepoResolver::with_lookup(
+            &opts,
+            Arc::new(move |_, _| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Some(RepoInfo {
+                    branch: Some("main".to_owned()),
+                    root: "/tmp/agpod-cached-repo".to_owned(),
+                    ..Default::default()
+                })
+            }),
+        );
+
+        for _ in 0..1_000 {
+            let result = process_line(&resolver, &opts, "/tmp/agpod-cached-repo").await;
+            assert_eq!(result, Some("/tmp/agpod-cached-repo main".to_string()));
+        }
+        assert_eq!(opens.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_no_cache_resolves_every_line() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let opts = CliOptions {
+            no_cache: true,
+            ..Default::default()
+        };
+        let opens = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&opens);
+        let resolver = RepoResolver::with_lookup(
+            &opts,
+            Arc::new(move |_, _| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                None
+            }),
+        );
+
+        for _ in 0..10 {
+            process_line(&resolver, &opts, "/tmp/agpod-cached-repo").await;
+        }
+        assert_eq!(opens.load(Ordering::SeqCst), 10);
+    }
+
+    #[tokio::test]
+    async fn test_slow_lookup_times_out_as_non_repo() {
+        let opts = CliOptions {
+            timeout: Some(Duration::from_millis(20)),
+            ..Default::default()
+        };
+        let resolver = RepoResolver::with_lookup(
+            &opts,
+            Arc::new(|_, _| {
+                std::thread::sleep(Duration::from_millis(300));
+                Some(RepoInfo {
+                    branch: Some("main".to_owned()),
+                    ..Default::default()
+                })
+            }),
+        );
+
+        let started = std::time::Instant::now();
+        for _ in 0..3 {
+            assert_eq!(
+                process_line(&resolver, &opts, "/tmp/agpod-hung-mount").await,
+                Some("/tmp/agpod-hung-mount".to_string())
+            );
+        }
+        assert!(started.elapsed() < Duration::from_millis(300));
+        // the warning is only printed the first time a path times out
+        assert_eq!(resolver.timed_out.lock().unwrap().len(), 1);
+
+        let filtered = CliOptions {
+            filter: true,
+            ..opts.clone()
+        };
+        assert_eq!(
+            process_line(&resolver, &filtered, "/tmp/agpod-hung-mount").await,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fast_lookup_within_timeout() {
+        let opts = CliOptions {
+            timeout: Some(Duration::from_secs(5)),
+            ..Default::default()
+        };
+        let resolver = RepoResolver::with_lookup(
+            &opts,
+            Arc::new(|_, _| {
+                Some(RepoInfo {
+                    branch: Some("main".to_owned()),
+                    ..Default::default()
+                })
+            }),
+        );
+        assert_eq!(
+            process_line(&resolver, &opts, "/tmp/agpod-repo").await,
+            Some("/tmp/agpod-repo main".to_string())
+        );
+    }
+
+    fn init_repo_with_commit(dir: &Path) -> Repository {
+        let repo = Repository::init(dir).unwrap();
+        {
+            let signature = git2::Signature::now("agpod", "agpod@localhost").unwrap();
+            let tree_id = repo.index().unwrap().write_tree().unwrap();
+            let tree = repo.find_tree(tree_id).unwrap();
+            repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])
+                .unwrap();
+        }
+        repo
+    }
+
+    #[tokio::test]
+    async fn test_tilde_and_relative_paths_resolve() {
+        let home = tempfile::tempdir().unwrap();
+        let home = home.path().canonicalize().unwrap();
+        let project = home.join("projects").join("foo");
+        std::fs::create_dir_all(&project).unwrap();
+        let repo = init_repo_with_commit(&project);
+        let branch = repo.head().unwrap().shorthand().unwrap().to_owned();
+
+        let opts = CliOptions {
+            home: Some(home.clone()),
+            cwd: Some(home.join("projects")),
+            ..Default::default()
+        };
+        let resolver = RepoResolver::new(&opts);
+        assert_eq!(
+            process_line(&resolver, &opts, "~/projects/foo").await,
+            Some(format!("~/projects/foo {branch}"))
+        );
+        assert_eq!(
+            process_line(&resolver, &opts, "./foo").await,
+            Some(format!("./foo {branch}"))
+        );
+        assert_eq!(
+            process_line(&resolver, &opts, "~/missing").await,
+            Some("~/missing".to_owned())
+        );
+
+        let opts = CliOptions {
+            expand_output: true,
+            ..opts
+        };
+        assert_eq!(
+            process_line(&resolver, &opts, "~/projects/foo").await,
+            Some(format!("{} {branch}", project.display()))
+        );
+        assert_eq!(
+            process_line(&resolver, &opts, "~/missing").await,
+            Some(home.join("missing").display().to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_strip_prefix_and_home_tilde_only_change_display() {
+        let home = tempfile::tempdir().unwrap();
+        let home = home.path().canonicalize().unwrap();
+        let project = home.join("projects").join("foo");
+        std::fs::create_dir_all(&project).unwrap();
+        let repo = init_repo_with_commit(&project);
+        let branch = repo.head().unwrap().shorthand().unwrap().to_owned();
+        let path = project.to_str().unwrap();
+
+        let opts = CliOptions {
+            home: Some(home.clone()),
+            home_tilde: true,
+            filter: true,
+            ..Default::default()
+        };
+        let resolver = RepoResolver::new(&opts);
+        assert_eq!(
+            process_line(&resolver, &opts, path).await,
+            Some(format!("~/projects/foo {branch}"))
+        );
+
+        let opts = CliOptions {
+            strip_prefix: Some(home.join("projects")),
+            ..opts
+        };
+        assert_eq!(
+            process_line(&resolver, &opts, path).await,
+            Some(format!("foo {branch}"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stash_and_tag_placeholders() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().canonicalize().unwrap();
+        let path = root.to_str().unwrap();
+        let mut repo = init_repo_with_commit(&root);
+
+        let opts = CliOptions {
+            format: Some("{tag}|{stashes}".to_owned()),
+            tag_dirty_suffix: Some("-dirty".to_owned()),
+            no_cache: true,
+            ..Default::default()
+        };
+        let resolver = RepoResolver::new(&opts);
+        assert_eq!(
+            process_line(&resolver, &opts, path).await,
+            Some("|0".to_owned())
+        );
+
+        // commit a tracked file, tag it, then stash a modification of it
+        let signature = git2::Signature::now("agpod", "agpod@localhost").unwrap();
+        std::fs::write(root.join("file.txt"), "one\n").unwrap();
+        {
+            let mut index = repo.index().unwrap();
+            index.add_path(Path::new("file.txt")).unwrap();
+            index.write().unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let parent = repo.head().unwrap().peel_to_commit().unwrap();
+            let commit = repo
+                .commit(
+                    Some("HEAD"),
+                    &signature,
+                    &signature,
+                    "add file",
+                    &tree,
+                    &[&parent],
+                )
+                .unwrap();
+            repo.tag_lightweight("v1.0", &repo.find_object(commit, None).unwrap(), false)
+                .unwrap();
+        }
+        std::fs::write(root.join("file.txt"), "two\n").unwrap();
+        repo.stash_save(&signature, "wip", None).unwrap();
+
+        assert_eq!(
+            process_line(&resolver, &opts, path).await,
+            Some("v1.0|1".to_owned())
+        );
+
+        std::fs::write(root.join("file.txt"), "three\n").unwrap();
+        assert_eq!(
+            process_line(&resolver, &opts, path).await,
+            Some("v1.0-dirty|1".to_owned())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_repository_without_commits_is_a_repo_line() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().canonicalize().unwrap();
+        Repository::init(&root).unwrap();
+        let path = root.to_str().unwrap();
+
+        let opts = CliOptions {
+            format: Some("{path}|{branch}|{last_commit_time}|{root}".to_owned()),
+            filter: true,
+            ..Default::default()
+        };
+        let resolver = RepoResolver::new(&opts);
+        assert_eq!(
+            process_line(&resolver, &opts, path).await,
+            Some(format!("{path}|||{path}"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_discover_reports_parent_repository() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().canonicalize().unwrap();
+        let repo = init_repo_with_commit(&root);
+        let branch = repo.head().unwrap().shorthand().unwrap().to_owned();
+        let subdir = root.join("src").join("components");
+        std::fs::create_dir_all(&subdir).unwrap();
+        let subdir = subdir.to_str().unwrap();
+
+        let opts = CliOptions {
+            format: Some("{path}|{branch}|{root}".to_owned()),
+            ..Default::default()
+        };
+        let resolver = RepoResolver::new(&opts);
+        assert_eq!(
+            process_line(&resolver, &opts, subdir).await,
+            Some(subdir.to_owned())
+        );
+
+        let opts = CliOptions {
+            format: Some("{path}|{branch}|{root}".to_owned()),
+            discover: true,
+            ..Default::default()
+        };
+        let resolver = RepoResolver::new(&opts);
+        assert_eq!(
+            process_line(&resolver, &opts, subdir).await,
+            Some(format!("{subdir}|{branch}|{}", root.display()))
+        );
+
+        let git_dir = root.join(".git");
+        assert_eq!(
+            process_line(&resolver, &opts, git_dir.to_str().unwrap()).await,
+            Some(git_dir.to_str().unwrap().to_owned())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remote_placeholders() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().canonicalize().unwrap();
+        let repo = init_repo_with_commit(&root);
+        let branch = repo.head().unwrap().shorthand().unwrap().to_owned();
+        let path = root.to_str().unwrap();
+
+        let opts = CliOptions {
+            format: Some("{branch}|{remote}|{repo}".to_owned()),
+            no_cache: true,
+            ..Default::default()
+        };
+        let resolver = RepoResolver::new(&opts);
+        assert_eq!(
+            process_line(&resolver, &opts, path).await,
+            Some(format!("{branch}||"))
+        );
+
+        repo.remote("origin", "git@github.com:towry/agpod.git")
+            .unwrap();
+        assert_eq!(
+            process_line(&resolver, &opts, path).await,
+            Some(format!(
+                "{branch}|git@github.com:towry/agpod.git|towry/agpod"
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_last_commit_placeholders() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().canonicalize().unwrap();
+        let opts = CliOptions {
+            format: Some("{last_commit_time}|{last_commit_subject}|{last_commit_iso}".to_owned()),
+            no_cache: true,
+            ..Default::default()
+        };
+        let resolver = RepoResolver::new(&opts);
+
+        // repositories without commits have no branch to report yet
+        let repo = Repository::init(&root).unwrap();
+        assert_eq!(
+            resolver
+                .resolve(&root)
+                .await
+                .and_then(|info| info.last_commit),
+            None
+        );
+
+        let signature = git2::Signature::new(
+            "agpod",
+            "agpod@localhost",
+            &git2::Time::new(Utc::now().timestamp() - 3 * 86400, 120),
+        )
+        .unwrap();
+        let tree_id = repo.index().unwrap().write_tree().unwrap();
+        let tree = repo.find_tree(tree_id).unwrap();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            "feat: add things\n\nbody",
+            &tree,
+            &[],
+        )
+        .unwrap();
+
+        let line = process_line(&resolver, &opts, root.to_str().unwrap())
+            .await
+            .unwrap();
+        let parts: Vec<&str> = line.split('|').collect();
+        assert_eq!(parts[0], "3 days ago");
+        assert_eq!(parts[1], "feat: add things");
+        assert!(parts[2].ends_with("+02:00"), "{}", parts[2]);
+    }
+
+    #[test]
+    fn test_commit_date_uses_committer_timezone() {
+        let commit = CommitInfo {
+            // 2024-03-01T23:30:00Z
+            time: 1_709_335_800,
+            offset_minutes: 120,
+            subject: String::new(),
+        };
+        assert_eq!(commit.date(), "2024-03-02");
+        assert_eq!(commit.iso(), "2024-03-02T01:30:00+02:00");
+    }
+
+    #[test]
+    fn test_sort_lines_by_last_commit() {
+        let line = |text: &str, time: Option<i64>| OutputLine {
+            text: text.to_owned(),
+            last_commit_time: time,
+        };
+        let mut lines = vec![
+            line("old", Some(10)),
+            line("plain", None),
+            line("new", Some(30)),
+            line("mid", Some(20)),
+        ];
+        sort_lines(&mut lines, SortKey::LastCommit);
+        let order: Vec<&str> = lines.iter().map(|line| line.text.as_str()).collect();
+        assert_eq!(order, vec!["new", "mid", "old", "plain"]);
+    }
+
+    #[tokio::test]
+    async fn test_branch_filter_matches_any_bookmark() {
+        let lookup = |_: &Path, _: &CliOptions| {
+            Some(RepoInfo {
+                branch: Some("main, release/1.0".to_owned()),
+                bookmarks: vec!["main".to_owned(), "release/1.0".to_owned()],
+                ..Default::default()
+            })
+        };
+        let opts = CliOptions {
+            branch_filter: Some("release/*".to_owned()),
+            ..Default::default()
+        };
+        let resolver = RepoResolver::with_lookup(&opts, Arc::new(lookup));
+        assert_eq!(
+            process_line(&resolver, &opts, "/tmp/jj-repo").await,
+            Some("/tmp/jj-repo main, release/1.0".to_owned())
+        );
+
+        let opts = CliOptions {
+            branch_filter: Some("release/*".to_owned()),
+            invert: true,
+            ..Default::default()
+        };
+        let resolver = RepoResolver::with_lookup(&opts, Arc::new(lookup));
+        assert_eq!(process_line(&resolver, &opts, "/tmp/jj-repo").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_branch_filter_on_git_branch() {
+        let lookup = |_: &Path, _: &CliOptions| {
+            Some(RepoInfo {
+                branch: Some("feature/x".to_owned()),
+                ..Default::default()
+            })
+        };
+        let opts = CliOptions {
+            branch_filter: Some("release/*".to_owned()),
+            ..Default::default()
+        };
+        let resolver = RepoResolver::with_lookup(&opts, Arc::new(lookup));
+        assert_eq!(process_line(&resolver, &opts, "/tmp/git-repo").await, None);
+
+        let opts = CliOptions {
+            branch_filter: Some("release/*".to_owned()),
+            invert: true,
+            ..Default::default()
+        };
+        let resolver = RepoResolver::with_lookup(&opts, Arc::new(lookup));
+        assert_eq!(
+            process_line(&resolver, &opts, "/tmp/git-repo").await,
+            Some("/tmp/git-repo feature/x".to_owned())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_color_wraps_branch_and_dims_non_repo() {
+        let lookup = |path: &Path, _: &CliOptions| {
+            (path == Path::new("/tmp/repo")).then(|| RepoInfo {
+                branch: Some("main".to_owned()),
+                ..Default::default()
+            })
+        };
+        let opts = CliOptions {
+            format: Some("{path} [{branch}]".to_owned()),
+            color: true,
+            branch_color: AnsiColor::Cyan,
+            ..Default::default()
+        };
+        let resolver = RepoResolver::with_lookup(&opts, Arc::new(lookup));
+        assert_eq!(
+            process_line(&resolver, &opts, "/tmp/repo").await,
+            Some("/tmp/repo [\x1b[36mmain\x1b[0m]".to_owned())
+        );
+        assert_eq!(
+            process_line(&resolver, &opts, "/tmp/plain").await,
+            Some("\x1b[2m/tmp/plain\x1b[0m".to_owned())
+        );
+
+        let opts = CliOptions {
+            format: Some("{path} [{branch}]".to_owned()),
+            ..Default::default()
+        };
+        let resolver = RepoResolver::with_lookup(&opts, Arc::new(lookup));
+        assert_eq!(
+            process_line(&resolver, &opts, "/tmp/repo").await,
+            Some("/tmp/repo [main]".to_owned())
+        );
+    }
 }
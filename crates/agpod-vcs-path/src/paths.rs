@@ -0,0 +1,110 @@
+//! Expansion of input paths before repository lookup.
+
+use std::path::{Component, Path, PathBuf};
+
+/// Expand a leading `~` against `home` and resolve relative paths against `cwd`.
+///
+/// `~user` forms are not expanded, and paths are left as-is when the needed
+/// base directory is unknown. `.` components are dropped; `..` is kept since
+/// collapsing it lexically would be wrong across symlinks.
+pub(crate) fn expand_input_path(path: &str, home: Option<&Path>, cwd: Option<&Path>) -> PathBuf {
+    let tilde_rest = match path {
+        "~" => Some(""),
+        _ => path.strip_prefix("~/"),
+    };
+    let expanded = match (tilde_rest, home) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    };
+    let absolute = match cwd {
+        Some(cwd) if expanded.is_relative() => cwd.join(expanded),
+        _ => expanded,
+    };
+    absolute
+        .components()
+        .filter(|component| !matches!(component, Component::CurDir))
+        .collect()
+}
+
+/// Shorten a path for display: remove `strip_prefix` first, then abbreviate
+/// the home directory to `~`.
+///
+/// Both match whole path components, so `/home/al` is not treated as a
+/// prefix of `/home/alice`.
+pub(crate) fn shorten_for_display(
+    path: &str,
+    strip_prefix: Option<&Path>,
+    home: Option<&Path>,
+) -> String {
+    let mut display = Path::new(path);
+    if let Some(rest) = strip_prefix.and_then(|prefix| display.strip_prefix(prefix).ok()) {
+        if rest.as_os_str().is_empty() {
+            return ".".to_owned();
+        }
+        display = rest;
+    }
+    match home.and_then(|home| display.strip_prefix(home).ok()) {
+        Some(rest) if rest.as_os_str().is_empty() => "~".to_owned(),
+        Some(rest) => format!("~/{}", rest.display()),
+        None => display.display().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_input_path() {
+        let home = Path::new("/home/agpod");
+        let cwd = Path::new("/work");
+        let expand = |path| expand_input_path(path, Some(home), Some(cwd));
+
+        assert_eq!(expand("~"), PathBuf::from("/home/agpod"));
+        assert_eq!(
+            expand("~/projects/foo"),
+            PathBuf::from("/home/agpod/projects/foo")
+        );
+        assert_eq!(expand("./bar"), PathBuf::from("/work/bar"));
+        assert_eq!(expand("bar/./baz"), PathBuf::from("/work/bar/baz"));
+        assert_eq!(expand("../bar"), PathBuf::from("/work/../bar"));
+        assert_eq!(expand("/abs/path"), PathBuf::from("/abs/path"));
+        assert_eq!(
+            expand_input_path("~/foo", None, None),
+            PathBuf::from("~/foo")
+        );
+    }
+
+    #[test]
+    fn test_shorten_for_display() {
+        let home = Some(Path::new("/home/agpod"));
+        let prefix = Some(Path::new("/home/agpod/projects"));
+
+        assert_eq!(
+            shorten_for_display("/home/agpod/projects/foo", prefix, None),
+            "foo"
+        );
+        assert_eq!(
+            shorten_for_display("/home/agpod/projects", prefix, None),
+            "."
+        );
+        assert_eq!(
+            shorten_for_display("/home/agpod/work/bar", None, home),
+            "~/work/bar"
+        );
+        assert_eq!(shorten_for_display("/home/agpod", None, home), "~");
+        assert_eq!(
+            shorten_for_display("/home/agpodder/bar", None, home),
+            "/home/agpodder/bar"
+        );
+        // strip runs first, so a stripped path is no longer under home
+        assert_eq!(
+            shorten_for_display("/home/agpod/projects/foo", prefix, home),
+            "foo"
+        );
+        assert_eq!(
+            shorten_for_display("/home/agpod/work/bar", prefix, home),
+            "~/work/bar"
+        );
+    }
+}
@@ -0,0 +1,118 @@
+//! Remote URL lookup and `owner/name` slug parsing.
+
+use git2::Repository;
+
+/// Fetch URL of `origin`, or of the first configured remote when there is no origin.
+pub(crate) fn remote_url(repo: &Repository) -> Option<String> {
+    let remote = match repo.find_remote("origin") {
+        Ok(remote) => remote,
+        Err(_) => {
+            let names = repo.remotes().ok()?;
+            let name = names.iter().flatten().next()?;
+            repo.find_remote(name).ok()?
+        }
+    };
+    remote.url().map(str::to_owned)
+}
+
+/// Parse the `owner/name` slug from a remote URL.
+///
+/// Handles `https://host/owner/name(.git)`, `ssh://[user@]host[:port]/owner/name.git`
+/// and scp-like `user@host:owner/name.git` forms. Nested groups (GitLab) are kept,
+/// e.g. `group/sub/name`.
+pub(crate) fn repo_slug(url: &str) -> Option<String> {
+    let url = url.trim();
+    let path = if let Some((_, rest)) = url.split_once("://") {
+        // strip the authority part (user@host:port)
+        rest.split_once('/')?.1
+    } else if let Some((host, path)) = url.split_once(':') {
+        // scp-like syntax never contains a slash before the colon
+        if host.contains('/') {
+            return None;
+        }
+        path
+    } else {
+        return None;
+    };
+
+    let path = path.trim_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    if segments.len() < 2 {
+        return None;
+    }
+    Some(segments.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repo_slug_https() {
+        assert_eq!(
+            repo_slug("https://github.com/towry/agpod.git").as_deref(),
+            Some("towry/agpod")
+        );
+        assert_eq!(
+            repo_slug("https://github.com/towry/agpod/").as_deref(),
+            Some("towry/agpod")
+        );
+        assert_eq!(
+            repo_slug("https://gitlab.com/group/sub/project.git").as_deref(),
+            Some("group/sub/project")
+        );
+    }
+
+    #[test]
+    fn test_repo_slug_ssh() {
+        assert_eq!(
+            repo_slug("ssh://git@github.com/towry/agpod.git").as_deref(),
+            Some("towry/agpod")
+        );
+        assert_eq!(
+            repo_slug("ssh://git@gitlab.example.com:2222/team/app.git").as_deref(),
+            Some("team/app")
+        );
+    }
+
+    #[test]
+    fn test_repo_slug_scp_like() {
+        assert_eq!(
+            repo_slug("git@github.com:towry/agpod.git").as_deref(),
+            Some("towry/agpod")
+        );
+        assert_eq!(
+            repo_slug("git@gitlab.com:group/sub/project").as_deref(),
+            Some("group/sub/project")
+        );
+    }
+
+    #[test]
+    fn test_repo_slug_rejects_unparseable() {
+        assert_eq!(repo_slug("/srv/git/agpod.git"), None);
+        assert_eq!(repo_slug("https://github.com/agpod"), None);
+        assert_eq!(repo_slug(""), None);
+    }
+
+    #[test]
+    fn test_remote_url_prefers_origin() {
+        let temp = tempfile::tempdir().unwrap();
+        let repo = Repository::init(temp.path()).unwrap();
+        assert_eq!(remote_url(&repo), None);
+
+        repo.remote("upstream", "https://github.com/up/agpod.git")
+            .unwrap();
+        assert_eq!(
+            remote_url(&repo).as_deref(),
+            Some("https://github.com/up/agpod.git")
+        );
+
+        repo.remote("origin", "git@github.com:towry/agpod.git")
+            .unwrap();
+        assert_eq!(
+            remote_url(&repo).as_deref(),
+            Some("git@github.com:towry/agpod.git")
+        );
+    }
+}
@@ -0,0 +1,100 @@
+//! Directory scanning for repository roots.
+
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Directory names skipped while scanning unless overridden.
+pub(crate) const DEFAULT_SCAN_IGNORES: &[&str] = &["node_modules", "target"];
+
+/// Walks a directory tree yielding Git/Jujutsu repository roots, without
+/// descending into a repository once it has been found.
+pub(crate) struct RepoRootWalker {
+    entries: walkdir::IntoIter,
+    ignores: Vec<String>,
+}
+
+impl RepoRootWalker {
+    pub(crate) fn new(dir: &Path, max_depth: Option<usize>, ignores: Vec<String>) -> Self {
+        let mut walk = WalkDir::new(dir).sort_by_file_name();
+        if let Some(max_depth) = max_depth {
+            walk = walk.max_depth(max_depth);
+        }
+        Self {
+            entries: walk.into_iter(),
+            ignores,
+        }
+    }
+
+    fn is_ignored(&self, name: &str) -> bool {
+        self.ignores.iter().any(|ignore| ignore == name)
+    }
+}
+
+impl Iterator for RepoRootWalker {
+    type Item = PathBuf;
+
+    fn next(&mut self) -> Option<PathBuf> {
+        loop {
+            // unreadable entries are skipped rather than aborting the scan
+            let Ok(entry) = self.entries.next()? else {
+                continue;
+            };
+            if !entry.file_type().is_dir() {
+                continue;
+            }
+            if entry.depth() > 0 && self.is_ignored(&entry.file_name().to_string_lossy()) {
+                self.entries.skip_current_dir();
+                continue;
+            }
+            if is_repo_root(entry.path()) {
+                self.entries.skip_current_dir();
+                return Some(entry.into_path());
+            }
+        }
+    }
+}
+
+fn is_repo_root(dir: &Path) -> bool {
+    dir.join(".git").exists() || dir.join(".jj").is_dir()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn default_ignores() -> Vec<String> {
+        DEFAULT_SCAN_IGNORES.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_walker_finds_repo_roots_without_descending() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        for dir in [
+            "a/.git",
+            "a/nested/.git",
+            "group/b/.git",
+            "group/node_modules/pkg/.git",
+            "deep/one/two/c/.git",
+        ] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+
+        let found: Vec<PathBuf> = RepoRootWalker::new(root, None, default_ignores()).collect();
+        assert_eq!(
+            found,
+            vec![
+                root.join("a"),
+                root.join("deep/one/two/c"),
+                root.join("group/b")
+            ]
+        );
+
+        let shallow: Vec<PathBuf> = RepoRootWalker::new(root, Some(2), default_ignores()).collect();
+        assert_eq!(shallow, vec![root.join("a"), root.join("group/b")]);
+
+        let no_ignores: Vec<PathBuf> = RepoRootWalker::new(root, Some(3), Vec::new()).collect();
+        assert!(no_ignores.contains(&root.join("group/node_modules/pkg")));
+    }
+}
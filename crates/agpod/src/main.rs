@@ -1,15 +1,36 @@
 use agpod_case as case;
-use agpod_core::init_logging;
+use agpod_core::{
+    error_kind, format_timings, init_logging_with_timings, resolve_config_paths, set_deterministic,
+    Config, ConfigError, Error, ErrorKind, Progress,
+};
 use agpod_diff as diff;
 use agpod_vcs_path as vcs_path;
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use std::path::PathBuf;
 use tracing::warn;
 
+mod schema;
+#[cfg(feature = "serve")]
+mod serve;
+
 #[derive(Parser)]
 #[command(name = "agpod")]
 #[command(version = env!("CARGO_PKG_VERSION"))]
 #[command(about = env!("CARGO_PKG_DESCRIPTION"), long_about = None)]
 struct Cli {
+    /// Pin time-dependent and machine-specific output for reproducible tests
+    /// (also enabled by AGPOD_DETERMINISTIC=1)
+    #[arg(long, global = true, hide = true)]
+    deterministic: bool,
+
+    /// Print how long each phase took to stderr when the command finishes
+    #[arg(long, global = true)]
+    timings: bool,
+
+    /// Report progress of long operations as JSON lines on stderr
+    #[arg(long, global = true)]
+    progress_json: bool,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -18,6 +39,9 @@ struct Cli {
 enum Commands {
     /// Minimize git diff for LLM context (reads from stdin)
     Diff {
+        #[command(subcommand)]
+        command: Option<DiffCommands>,
+
         /// Save diff chunks to separate files
         #[arg(long)]
         save: bool,
@@ -29,6 +53,49 @@ enum Commands {
         /// Add context information to REVIEW.md (e.g., reference documentation)
         #[arg(long)]
         context: Option<String>,
+
+        /// Leave out files matching a gitignore-style pattern (repeatable);
+        /// applied after `[diff] exclude_file`
+        #[arg(long, value_name = "PATTERN")]
+        exclude: Vec<String>,
+
+        /// Save chunks in full even when over `[diff] max_tokens_per_chunk`
+        #[arg(long)]
+        no_truncate_chunks: bool,
+
+        /// Hide changes in the amount of whitespace from the minimized output
+        #[arg(long)]
+        ignore_space_change: bool,
+
+        /// Hide added and removed comment-only lines from the minimized output
+        #[arg(long)]
+        ignore_comments: bool,
+
+        /// Start the output and REVIEW.md with repository, branch, diff source and change totals
+        #[arg(long)]
+        with_header: bool,
+
+        /// Write the minimized output as N bundles of roughly equal size
+        /// (bundle_1.md ...) into the output directory
+        #[arg(long, value_name = "N", conflicts_with = "split_tokens", value_parser = clap::value_parser!(u64).range(1..))]
+        split: Option<u64>,
+
+        /// Like --split, with as few bundles as fit T estimated tokens each
+        #[arg(long, value_name = "T", value_parser = clap::value_parser!(u64).range(1..))]
+        split_tokens: Option<u64>,
+
+        /// Read the diff from `git diff HEAD` instead of stdin
+        #[arg(long)]
+        from_git: bool,
+
+        /// With --from-git, diff from the merge base of REF and HEAD, e.g.
+        /// `--since-ref origin/main` for everything on the current branch
+        #[arg(long, value_name = "REF", requires = "from_git")]
+        since_ref: Option<String>,
+
+        /// With --since-ref, stop at HEAD instead of the working tree
+        #[arg(long, requires = "since_ref")]
+        committed: bool,
     },
     /// Track exploration cases: open/close/redirect goals, record findings, manage steps. Use `--json` for machine output. All args are `--key value` (no positional).
     Case(Box<case::CaseArgs>),
@@ -36,6 +103,114 @@ enum Commands {
     CaseServer(CaseServerArgs),
     /// Format paths with VCS (Git/Jujutsu) branch/bookmark information
     VcsPathInfo(vcs_path::VcsPathInfoArgs),
+    /// Describe all commands, flags and `--json` outputs for tool integration
+    Schema {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Serve diff operations to agent frameworks and editors (MCP, HTTP)
+    #[cfg(feature = "serve")]
+    Serve(serve::ServeArgs),
+}
+
+#[derive(Subcommand)]
+enum DiffCommands {
+    /// Print files with review comments from a saved REVIEW.md
+    Comments {
+        /// Directory the chunks were saved to (default: `[diff] output_dir`)
+        #[arg(long)]
+        save_path: Option<String>,
+
+        /// Output as JSON; an empty result prints `[]`
+        #[arg(long, conflicts_with = "markdown")]
+        json: bool,
+
+        /// Output as Markdown (default); an empty result prints nothing
+        #[arg(long)]
+        markdown: bool,
+
+        /// Inline each file's chunk diff under its comments
+        #[arg(long)]
+        with_diff: bool,
+    },
+    /// Publish files' review comments from a saved REVIEW.md as one GitHub
+    /// pull request review (needs an authenticated `gh`)
+    PublishReview {
+        /// Pull request number
+        #[arg(long)]
+        pr: u64,
+
+        /// Directory the chunks were saved to (default: `[diff] output_dir`)
+        #[arg(long)]
+        save_path: Option<String>,
+
+        /// Print the review payload instead of sending it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Work with saved REVIEW.md files
+    Review {
+        #[command(subcommand)]
+        command: ReviewCommands,
+    },
+    /// Reconstruct one file's content from a diff with full context
+    Show {
+        /// Path of the file in the diff
+        path: String,
+
+        /// Print the content before the change
+        #[arg(long, conflicts_with = "after")]
+        before: bool,
+
+        /// Print the content after the change (default)
+        #[arg(long)]
+        after: bool,
+
+        /// Read the diff from `git diff HEAD` instead of stdin
+        #[arg(long)]
+        from_git: bool,
+
+        /// Write the content to a file instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ReviewCommands {
+    /// Merge two edited copies of REVIEW.md entry by entry
+    Merge {
+        /// Our version
+        #[arg(
+            long,
+            required_unless_present = "git_merge_driver",
+            requires = "theirs"
+        )]
+        ours: Option<PathBuf>,
+
+        /// Their version
+        #[arg(long, requires = "ours")]
+        theirs: Option<PathBuf>,
+
+        /// The common ancestor, to tell which side changed an entry
+        #[arg(long)]
+        base: Option<PathBuf>,
+
+        /// Write the merged file here instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+
+        /// Run as a git merge driver with git's %O %A %B; the result
+        /// replaces OURS
+        #[arg(
+            long,
+            num_args = 3,
+            value_names = ["BASE", "OURS", "THEIRS"],
+            conflicts_with_all = ["ours", "theirs", "base", "output"]
+        )]
+        git_merge_driver: Option<Vec<PathBuf>>,
+    },
 }
 
 #[derive(Args)]
@@ -51,32 +226,153 @@ struct CaseServerArgs {
 
 #[tokio::main]
 async fn main() {
-    if let Err(error) = init_logging("agpod") {
+    let cli = Cli::parse();
+    if let Err(error) = init_logging_with_timings("agpod", cli.timings) {
         eprintln!("Warning: failed to initialize logging: {error}");
     }
 
-    let cli = Cli::parse();
+    if cli.deterministic {
+        set_deterministic(true);
+    }
     warn!("agpod started");
 
-    match cli.command {
+    let code = match cli.command {
+        Some(Commands::Diff {
+            command:
+                Some(DiffCommands::Comments {
+                    save_path,
+                    json,
+                    markdown: _,
+                    with_diff,
+                }),
+            ..
+        }) => report(
+            load_diff_config()
+                .map_err(anyhow::Error::from)
+                .and_then(|config| {
+                    let save_path = save_path.unwrap_or(config.output_dir);
+                    let format = if json {
+                        diff::CommentsFormat::Json
+                    } else {
+                        diff::CommentsFormat::Markdown
+                    };
+                    diff::print_review_comments(&save_path, format, with_diff)
+                }),
+        ),
+        Some(Commands::Diff {
+            command:
+                Some(DiffCommands::PublishReview {
+                    pr,
+                    save_path,
+                    dry_run,
+                }),
+            ..
+        }) => report(
+            load_diff_config()
+                .map_err(anyhow::Error::from)
+                .and_then(|config| {
+                    let save_path = save_path.unwrap_or(config.output_dir);
+                    diff::publish_review(&save_path, pr, dry_run)
+                }),
+        ),
+        Some(Commands::Diff {
+            command:
+                Some(DiffCommands::Review {
+                    command:
+                        ReviewCommands::Merge {
+                            ours,
+                            theirs,
+                            base,
+                            output,
+                            git_merge_driver,
+                        },
+                }),
+            ..
+        }) => {
+            let result = match (git_merge_driver.as_deref(), ours, theirs) {
+                (Some([base, ours, theirs]), _, _) => {
+                    diff::merge_review_files(Some(base), ours, theirs, Some(ours))
+                }
+                (_, Some(ours), Some(theirs)) => {
+                    diff::merge_review_files(base.as_deref(), &ours, &theirs, output.as_deref())
+                }
+                _ => unreachable!("clap requires --ours and --theirs without --git-merge-driver"),
+            };
+            report(result.map(|_| ()).map_err(anyhow::Error::from))
+        }
+        Some(Commands::Diff {
+            command:
+                Some(DiffCommands::Show {
+                    path,
+                    before,
+                    after: _,
+                    from_git,
+                    output,
+                }),
+            ..
+        }) => {
+            let side = if before {
+                diff::Side::Before
+            } else {
+                diff::Side::After
+            };
+            report(diff::show_file(&path, side, from_git, output.as_deref()))
+        }
         Some(Commands::Diff {
+            command: None,
             save,
             save_path,
             context,
+            exclude,
+            no_truncate_chunks,
+            ignore_space_change,
+            ignore_comments,
+            with_header,
+            split,
+            split_tokens,
+            from_git,
+            since_ref,
+            committed,
         }) => {
             // Process git diff from stdin
-            match diff::process_git_diff(save, save_path, context) {
-                Ok(()) => {}
-                Err(e) => {
-                    eprintln!("Error: {}", e);
-                    std::process::exit(1);
-                }
-            }
+            report(
+                load_diff_config()
+                    .map_err(anyhow::Error::from)
+                    .and_then(|mut config| {
+                        if no_truncate_chunks {
+                            config.max_tokens_per_chunk = None;
+                        }
+                        config.ignore_space_change |= ignore_space_change;
+                        config.ignore_comments |= ignore_comments;
+                        config.with_header |= with_header;
+                        let split = split
+                            .map(|count| diff::Split::Count(count as usize))
+                            .or(split_tokens.map(|tokens| diff::Split::Tokens(tokens as usize)));
+                        let source = if from_git {
+                            diff::DiffSource::Git {
+                                since_ref,
+                                committed,
+                            }
+                        } else {
+                            diff::DiffSource::Stdin
+                        };
+                        let progress = if cli.progress_json {
+                            Progress::json()
+                        } else {
+                            Progress::none()
+                        };
+                        diff::process_git_diff(
+                            &source, save, save_path, context, &exclude, split, &config, &progress,
+                        )
+                    }),
+            )
         }
         Some(Commands::Case(args)) => {
             if let Err(e) = case::run(*args).await {
                 eprintln!("Error: {}", e);
-                std::process::exit(1);
+                1
+            } else {
+                0
             }
         }
         Some(Commands::CaseServer(args)) => {
@@ -84,29 +380,101 @@ async fn main() {
                 data_dir: args.data_dir.as_deref(),
                 server_addr: args.server_addr.as_deref(),
             });
-            let server = match case::CaseServer::new(config).await {
-                Ok(server) => server,
-                Err(e) => {
-                    eprintln!("Error: {}", e);
-                    std::process::exit(1);
-                }
+            let served = match case::CaseServer::new(config).await {
+                Ok(server) => server.serve().await,
+                Err(e) => Err(e),
             };
-            if let Err(e) = server.serve().await {
+            if let Err(e) = served {
                 eprintln!("Error: {}", e);
-                std::process::exit(1);
+                1
+            } else {
+                0
             }
         }
         Some(Commands::VcsPathInfo(args)) => {
             if let Err(e) = vcs_path::run(args).await {
                 eprintln!("Error: {}", e);
-                std::process::exit(1);
+                1
+            } else {
+                0
+            }
+        }
+        Some(Commands::Schema { json }) => {
+            let schema = schema::Schema::from_command(&Cli::command());
+            if json {
+                match serde_json::to_string_pretty(&schema) {
+                    Ok(text) => {
+                        println!("{}", text);
+                        0
+                    }
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        1
+                    }
+                }
+            } else {
+                print!("{}", schema.render_text());
+                0
             }
         }
+        #[cfg(feature = "serve")]
+        Some(Commands::Serve(args)) => match load_diff_config() {
+            Ok(config) => {
+                if let Err(e) = serve::run(args, config).await {
+                    eprintln!("Error: {}", e);
+                    1
+                } else {
+                    0
+                }
+            }
+            Err(e) => report(Err(e.into())),
+        },
         None => {
             // No command provided, print help
-            use clap::CommandFactory;
             let _ = Cli::command().print_help();
             println!(); // Add a newline after help
+            0
         }
+    };
+
+    // printed before exiting so a failed run still reports its timings
+    if let Some(timings) = format_timings() {
+        eprint!("{}", timings);
+    }
+    if code != 0 {
+        std::process::exit(code);
+    }
+}
+
+/// Print a failed command's error and map it to its exit code, or 0
+fn report(result: anyhow::Result<()>) -> i32 {
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            exit_code(&e)
+        }
+    }
+}
+
+/// `[diff]` settings with `~` and `$VAR` expanded in its paths
+fn load_diff_config() -> Result<diff::DiffConfig, Error> {
+    let config = resolve_config_paths(Config::load()).map_err(ConfigError::from)?;
+    Ok(config.diff.unwrap_or_default())
+}
+
+const EXIT_CONFIG: i32 = 3;
+const EXIT_GIT: i32 = 4;
+const EXIT_REVIEW: i32 = 5;
+const EXIT_IO: i32 = 6;
+
+/// Exit status for a failed command: one per error domain, 1 otherwise
+fn exit_code(error: &anyhow::Error) -> i32 {
+    match error_kind(error.as_ref()) {
+        Some(ErrorKind::Config) => EXIT_CONFIG,
+        Some(ErrorKind::Git) => EXIT_GIT,
+        Some(ErrorKind::Review) => EXIT_REVIEW,
+        Some(ErrorKind::Io) => EXIT_IO,
+        Some(ErrorKind::Other) | None => 1,
     }
 }
@@ -0,0 +1,258 @@
+//! `agpod schema`: a machine-readable manifest of commands, flags and the
+//! JSON printed by `--json` modes, for generating agent tool definitions.
+//!
+//! Commands and flags come from the clap model at runtime so they cannot
+//! drift; output schemas are maintained by hand in `output_schemas`.
+
+use clap::{Arg, ArgAction, Command};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::any::TypeId;
+use std::path::PathBuf;
+
+#[derive(Debug, Serialize)]
+pub struct Schema {
+    pub name: String,
+    pub version: String,
+    /// Every visible command, parents before their subcommands
+    pub commands: Vec<CommandSchema>,
+    pub outputs: Vec<OutputSchema>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CommandSchema {
+    /// Words after `agpod`, e.g. `diff comments`
+    pub path: String,
+    pub about: Option<String>,
+    pub args: Vec<ArgSchema>,
+    pub subcommands: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ArgSchema {
+    pub name: String,
+    pub long: Option<String>,
+    pub short: Option<char>,
+    /// `boolean`, `count`, `integer`, `path`, `string` or `enum`
+    #[serde(rename = "type")]
+    pub value_type: &'static str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub values: Vec<String>,
+    pub value_name: Option<String>,
+    pub required: bool,
+    /// Whether the flag can be given more than once
+    pub multiple: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub default: Vec<String>,
+    pub env: Option<String>,
+    /// Whether the flag is also accepted by subcommands
+    pub global: bool,
+    pub help: Option<String>,
+}
+
+/// JSON printed by a command's `--json` mode
+#[derive(Debug, Serialize)]
+pub struct OutputSchema {
+    pub command: String,
+    pub flag: String,
+    /// A JSON Schema of the printed document
+    pub schema: Value,
+}
+
+impl Schema {
+    /// Describe `command`, the root `agpod` command
+    pub fn from_command(command: &Command) -> Self {
+        let mut commands = Vec::new();
+        for subcommand in visible_subcommands(command) {
+            collect_commands(subcommand, &[], &mut commands);
+        }
+        Self {
+            name: command.get_name().to_string(),
+            version: command.get_version().unwrap_or_default().to_string(),
+            commands,
+            outputs: output_schemas(),
+        }
+    }
+
+    /// A plain-text overview, one command per paragraph
+    pub fn render_text(&self) -> String {
+        let mut text = format!("{} {}\n", self.name, self.version);
+        for command in &self.commands {
+            text.push('\n');
+            text.push_str(&format!("{} {}", self.name, command.path));
+            if let Some(about) = &command.about {
+                text.push_str(&format!(" - {}", about));
+            }
+            text.push('\n');
+            for arg in &command.args {
+                let flag = match (&arg.long, arg.short) {
+                    (Some(long), _) => format!("--{}", long),
+                    (None, Some(short)) => format!("-{}", short),
+                    (None, None) => format!("<{}>", arg.name),
+                };
+                text.push_str(&format!("  {} ({})", flag, arg.value_type));
+                if let Some(help) = &arg.help {
+                    text.push_str(&format!("  {}", help));
+                }
+                text.push('\n');
+            }
+        }
+        for output in &self.outputs {
+            text.push_str(&format!(
+                "\n{} {} {} prints JSON; see `{} schema --json`\n",
+                self.name, output.command, output.flag, self.name
+            ));
+        }
+        text
+    }
+}
+
+fn visible_subcommands(command: &Command) -> impl Iterator<Item = &Command> {
+    command
+        .get_subcommands()
+        .filter(|subcommand| !subcommand.is_hide_set())
+}
+
+fn collect_commands(command: &Command, parents: &[&str], commands: &mut Vec<CommandSchema>) {
+    let mut path = parents.to_vec();
+    path.push(command.get_name());
+
+    commands.push(CommandSchema {
+        path: path.join(" "),
+        about: command.get_about().map(|about| about.to_string()),
+        args: command
+            .get_arguments()
+            .filter(|arg| !arg.is_hide_set())
+            .map(arg_schema)
+            .collect(),
+        subcommands: visible_subcommands(command)
+            .map(|subcommand| subcommand.get_name().to_string())
+            .collect(),
+    });
+    for subcommand in visible_subcommands(command) {
+        collect_commands(subcommand, &path, commands);
+    }
+}
+
+fn arg_schema(arg: &Arg) -> ArgSchema {
+    let values: Vec<String> = arg
+        .get_possible_values()
+        .iter()
+        .filter(|value| !value.is_hide_set())
+        .map(|value| value.get_name().to_string())
+        .collect();
+    let type_id = arg.get_value_parser().type_id();
+    let value_type = match arg.get_action() {
+        ArgAction::SetTrue | ArgAction::SetFalse => "boolean",
+        ArgAction::Count => "count",
+        _ if !values.is_empty() => "enum",
+        _ if [
+            TypeId::of::<u8>(),
+            TypeId::of::<u16>(),
+            TypeId::of::<u32>(),
+            TypeId::of::<u64>(),
+            TypeId::of::<usize>(),
+            TypeId::of::<i8>(),
+            TypeId::of::<i16>(),
+            TypeId::of::<i32>(),
+            TypeId::of::<i64>(),
+            TypeId::of::<isize>(),
+        ]
+        .iter()
+        .any(|id| type_id == *id) =>
+        {
+            "integer"
+        }
+        _ if type_id == TypeId::of::<PathBuf>() => "path",
+        _ => "string",
+    };
+    let takes_value = !matches!(
+        arg.get_action(),
+        ArgAction::SetTrue | ArgAction::SetFalse | ArgAction::Count
+    );
+
+    ArgSchema {
+        name: arg.get_id().to_string(),
+        long: arg.get_long().map(str::to_string),
+        short: arg.get_short(),
+        value_type,
+        values: if value_type == "enum" {
+            values
+        } else {
+            Vec::new()
+        },
+        value_name: takes_value
+            .then(|| arg.get_value_names())
+            .flatten()
+            .and_then(|names| names.first())
+            .map(|name| name.to_string()),
+        required: arg.is_required_set(),
+        multiple: matches!(arg.get_action(), ArgAction::Append),
+        default: if takes_value {
+            arg.get_default_values()
+                .iter()
+                .map(|value| value.to_string_lossy().into_owned())
+                .collect()
+        } else {
+            Vec::new()
+        },
+        env: arg.get_env().map(|env| env.to_string_lossy().into_owned()),
+        global: arg.is_global_set(),
+        help: arg.get_help().map(|help| help.to_string()),
+    }
+}
+
+/// Schemas of the documents printed by `--json` modes
+fn output_schemas() -> Vec<OutputSchema> {
+    vec![
+        OutputSchema {
+            command: "diff comments".to_string(),
+            flag: "--json".to_string(),
+            // agpod_diff::ReviewComment
+            schema: json!({
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "file": { "type": "string" },
+                        "status": { "type": "string" },
+                        "chunk": { "type": ["string", "null"] },
+                        "comments": { "type": "string" },
+                        "diff": {
+                            "type": "string",
+                            "description": "chunk content, only with --with-diff"
+                        }
+                    },
+                    "required": ["file", "status", "chunk", "comments"]
+                }
+            }),
+        },
+        OutputSchema {
+            command: "case".to_string(),
+            flag: "--json".to_string(),
+            // every case subcommand prints one object with an `ok` flag;
+            // failures carry agpod_case::output::error_json's fields
+            schema: json!({
+                "type": "object",
+                "properties": {
+                    "ok": { "type": "boolean" },
+                    "message": { "type": "string" },
+                    "error": {
+                        "type": "string",
+                        "description": "error code, only when ok is false"
+                    },
+                    "next": {
+                        "type": "object",
+                        "properties": {
+                            "suggested_command": { "type": "string" },
+                            "why": { "type": "string" }
+                        },
+                        "required": ["suggested_command", "why"]
+                    }
+                },
+                "required": ["ok"],
+                "additionalProperties": true
+            }),
+        },
+    ]
+}
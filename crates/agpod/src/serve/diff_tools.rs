@@ -0,0 +1,128 @@
+//! Diff operations shared by the serve transports, taking JSON arguments
+//! and returning JSON results. The argument docs double as the MCP tools'
+//! parameter descriptions.
+
+use agpod_core::DiffConfig;
+use agpod_diff as diff;
+use rmcp::schemars;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fs;
+use std::io;
+
+/// Arguments of `minimize_diff`
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct MinimizeArgs {
+    /// Unified diff, e.g. `git diff` output
+    pub diff: String,
+    /// Gitignore-style patterns of files to leave out
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(default)]
+    pub ignore_space_change: bool,
+    #[serde(default)]
+    pub ignore_comments: bool,
+}
+
+/// Arguments of `save_diff_chunks`
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct SaveArgs {
+    /// Unified diff, e.g. `git diff` output
+    pub diff: String,
+    /// Directory for chunks and REVIEW.md (default: `[diff] output_dir`)
+    pub output_dir: Option<String>,
+    /// Text for REVIEW.md's Context section
+    pub context: Option<String>,
+    /// Gitignore-style patterns of files to leave out
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+/// Arguments of `get_review_status`
+#[derive(Debug, Default, Deserialize, schemars::JsonSchema)]
+pub struct ReviewStatusArgs {
+    /// Directory for chunks and REVIEW.md (default: `[diff] output_dir`)
+    pub output_dir: Option<String>,
+}
+
+/// The minimized diff, as `agpod diff` prints it
+pub fn minimize(args: MinimizeArgs, config: &DiffConfig) -> Result<String, String> {
+    diff::check_diff_input(&args.diff).map_err(|e| e.to_string())?;
+    let config = DiffConfig {
+        ignore_space_change: config.ignore_space_change || args.ignore_space_change,
+        ignore_comments: config.ignore_comments || args.ignore_comments,
+        ..config.clone()
+    };
+    let exclude =
+        diff::ExcludeMatcher::from_config(&config, &args.exclude).map_err(|e| e.to_string())?;
+    Ok(diff::minimize_diff_with_exclude(
+        &args.diff, &config, &exclude,
+    ))
+}
+
+/// Save chunks and REVIEW.md as `agpod diff --save` does
+pub fn save(args: SaveArgs, config: &DiffConfig) -> Result<Value, String> {
+    diff::check_diff_input(&args.diff).map_err(|e| e.to_string())?;
+    let exclude =
+        diff::ExcludeMatcher::from_config(config, &args.exclude).map_err(|e| e.to_string())?;
+    let output_dir = args.output_dir.as_deref().unwrap_or(&config.output_dir);
+    let saved = diff::save_diff_chunks_with_config(
+        &args.diff,
+        output_dir,
+        args.context.as_deref(),
+        config,
+        &exclude,
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(json!({
+        "output_dir": saved.output_dir,
+        "review_path": saved.review_path,
+        "chunks": saved.chunks,
+        "truncated_chunks": saved.truncated_chunks,
+    }))
+}
+
+/// Status of every file section in a saved REVIEW.md, in document order;
+/// no REVIEW.md yet is an empty list
+pub fn review_status(args: ReviewStatusArgs, config: &DiffConfig) -> Result<Value, String> {
+    let output_dir = args.output_dir.as_deref().unwrap_or(&config.output_dir);
+    let review_path = diff::resolve_review_dir(output_dir).join("REVIEW.md");
+    let content = match fs::read_to_string(&review_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("failed to read {}: {}", review_path.display(), e)),
+    };
+    let entries: Vec<Value> = diff::parse_review_entries(&content)
+        .into_iter()
+        .map(|(file, entry)| {
+            json!({
+                "file": file,
+                "status": entry.status,
+                "chunk": entry.diff_chunk,
+                "truncated": entry.truncated,
+                "renamed_from": entry.renamed_from,
+                "has_comments": !entry.comments.is_empty(),
+            })
+        })
+        .collect();
+    Ok(Value::Array(entries))
+}
+
+/// Arguments of the commented files listing, as `agpod diff comments`
+#[derive(Debug, Default, Deserialize, schemars::JsonSchema)]
+pub struct ReviewCommentsArgs {
+    /// Directory for chunks and REVIEW.md (default: `[diff] output_dir`)
+    pub output_dir: Option<String>,
+    /// Include each file's chunk diff
+    #[serde(default)]
+    pub with_diff: bool,
+}
+
+/// Files with review comments, as `agpod diff comments --json` prints them
+pub fn review_comments(args: ReviewCommentsArgs, config: &DiffConfig) -> Result<Value, String> {
+    let output_dir = args.output_dir.as_deref().unwrap_or(&config.output_dir);
+    let comments =
+        diff::collect_review_comments(&diff::resolve_review_dir(output_dir), args.with_diff)
+            .map_err(|e| e.to_string())?;
+    serde_json::to_value(comments).map_err(|e| e.to_string())
+}
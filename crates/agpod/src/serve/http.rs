@@ -0,0 +1,348 @@
+//! A small HTTP/1.1 JSON API for editor integrations: one request per
+//! connection, JSON responses, optional bearer token.
+//!
+//! Without a token only requests whose `Host` names the loopback address
+//! are served, so a web page cannot reach the API through DNS rebinding.
+//!
+//! Routes:
+//! - `POST /diff/minimize`: body is the raw diff; query `exclude` (repeatable),
+//!   `ignore_space_change`, `ignore_comments`; returns `{"output": ...}`
+//! - `GET /diff/review`: query `output_dir`, `with_diff`; returns what
+//!   `agpod diff comments --json` prints
+
+use super::diff_tools::{self, MinimizeArgs, ReviewCommentsArgs};
+use agpod_core::DiffConfig;
+use serde_json::{json, Value};
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::warn;
+
+/// Requests with a larger head or body are rejected
+const MAX_HEAD_BYTES: usize = 64 * 1024;
+const MAX_BODY_BYTES: usize = 64 * 1024 * 1024;
+
+/// Time a client gets to send its whole request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+struct Request {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    authorization: Option<String>,
+    host: Option<String>,
+    body: Vec<u8>,
+}
+
+#[derive(Debug)]
+struct Response {
+    status: u16,
+    body: Value,
+}
+
+impl Response {
+    fn ok(body: Value) -> Self {
+        Self { status: 200, body }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: json!({ "error": message.into() }),
+        }
+    }
+}
+
+/// Accept connections on `listener` until the process is stopped
+pub async fn serve(
+    listener: TcpListener,
+    token: Option<String>,
+    config: DiffConfig,
+) -> io::Result<()> {
+    let port = listener.local_addr()?.port();
+    let state = Arc::new((token, config));
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let state = Arc::clone(&state);
+        tokio::spawn(async move {
+            let (token, config) = &*state;
+            if let Err(e) = handle_connection(stream, token.as_deref(), config, port).await {
+                warn!("http connection from {} failed: {}", peer, e);
+            }
+        });
+    }
+}
+
+async fn handle_connection(
+    stream: TcpStream,
+    token: Option<&str>,
+    config: &DiffConfig,
+    port: u16,
+) -> io::Result<()> {
+    let mut stream = BufReader::new(stream);
+    let response = match tokio::time::timeout(REQUEST_TIMEOUT, read_request(&mut stream)).await {
+        Ok(request) => match request? {
+            Ok(request) => route(&request, token, config, port),
+            Err(response) => response,
+        },
+        Err(_) => Response::error(408, "timed out reading the request"),
+    };
+    let body = response.body.to_string();
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        reason(response.status),
+        body.len()
+    );
+    let stream = stream.get_mut();
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Read one request; a malformed one yields the error response to send
+async fn read_request(stream: &mut BufReader<TcpStream>) -> io::Result<Result<Request, Response>> {
+    let mut head_bytes = 0;
+    let mut line = String::new();
+    if !read_head_line(stream, &mut line, &mut head_bytes).await? {
+        return Ok(Err(Response::error(431, "request head too large")));
+    }
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target), Some(_version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return Ok(Err(Response::error(400, "malformed request line")));
+    };
+    let method = method.to_string();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let path = path.to_string();
+    let query = parse_query(query);
+
+    let mut content_length = 0;
+    let mut authorization = None;
+    let mut host = None;
+    loop {
+        line.clear();
+        if !read_head_line(stream, &mut line, &mut head_bytes).await? {
+            return Ok(Err(Response::error(431, "request head too large")));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            return Ok(Err(Response::error(400, "malformed header")));
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            match value.parse() {
+                Ok(length) => content_length = length,
+                Err(_) => return Ok(Err(Response::error(400, "invalid Content-Length"))),
+            }
+        } else if name.eq_ignore_ascii_case("authorization") {
+            authorization = Some(value.to_string());
+        } else if name.eq_ignore_ascii_case("host") {
+            host = Some(value.to_string());
+        }
+    }
+    if content_length > MAX_BODY_BYTES {
+        return Ok(Err(Response::error(413, "request body too large")));
+    }
+
+    // grown as bytes arrive rather than sized by the client's claim
+    let mut body = Vec::new();
+    stream
+        .take(content_length as u64)
+        .read_to_end(&mut body)
+        .await?;
+    if body.len() < content_length {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(Ok(Request {
+        method,
+        path,
+        query,
+        authorization,
+        host,
+        body,
+    }))
+}
+
+/// Read one line of the request head into `line`, never buffering past
+/// `MAX_HEAD_BYTES` in total; false once a line would cross that limit
+async fn read_head_line(
+    stream: &mut BufReader<TcpStream>,
+    line: &mut String,
+    head_bytes: &mut usize,
+) -> io::Result<bool> {
+    let limit = MAX_HEAD_BYTES - *head_bytes;
+    let read = (&mut *stream).take(limit as u64).read_line(line).await?;
+    *head_bytes += read;
+    Ok(read < limit || line.ends_with('\n'))
+}
+
+fn route(request: &Request, token: Option<&str>, config: &DiffConfig, port: u16) -> Response {
+    match token {
+        Some(token) => {
+            let expected = format!("Bearer {}", token);
+            let given = request.authorization.as_deref().unwrap_or_default();
+            if !constant_time_eq(given.as_bytes(), expected.as_bytes()) {
+                return Response::error(401, "missing or invalid bearer token");
+            }
+        }
+        None if !is_loopback_host(request.host.as_deref(), port) => {
+            return Response::error(403, "Host must be a loopback address");
+        }
+        None => {}
+    }
+
+    match (request.method.as_str(), request.path.as_str()) {
+        ("POST", "/diff/minimize") => minimize(request, config),
+        ("GET", "/diff/review") => review(request, config),
+        (_, "/diff/minimize" | "/diff/review") => Response::error(405, "method not allowed"),
+        _ => Response::error(404, format!("no route for {}", request.path)),
+    }
+}
+
+fn minimize(request: &Request, config: &DiffConfig) -> Response {
+    let Ok(diff) = String::from_utf8(request.body.clone()) else {
+        return Response::error(400, "diff is not valid UTF-8");
+    };
+    let args = match (
+        query_flag(request, "ignore_space_change"),
+        query_flag(request, "ignore_comments"),
+    ) {
+        (Ok(ignore_space_change), Ok(ignore_comments)) => MinimizeArgs {
+            diff,
+            exclude: query_values(request, "exclude"),
+            ignore_space_change,
+            ignore_comments,
+        },
+        (Err(response), _) | (_, Err(response)) => return response,
+    };
+    match diff_tools::minimize(args, config) {
+        Ok(output) => Response::ok(json!({ "output": output })),
+        Err(message) => Response::error(400, message),
+    }
+}
+
+fn review(request: &Request, config: &DiffConfig) -> Response {
+    let with_diff = match query_flag(request, "with_diff") {
+        Ok(with_diff) => with_diff,
+        Err(response) => return response,
+    };
+    let args = ReviewCommentsArgs {
+        output_dir: query_values(request, "output_dir").pop(),
+        with_diff,
+    };
+    match diff_tools::review_comments(args, config) {
+        Ok(comments) => Response::ok(comments),
+        Err(message) => Response::error(500, message),
+    }
+}
+
+/// Compare without stopping at the first mismatch, so response times do
+/// not reveal how much of a guessed token was right
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Whether `host` is `127.0.0.1`, `localhost` or `[::1]` with `port`, which
+/// a browser leaves out for port 80
+fn is_loopback_host(host: Option<&str>, port: u16) -> bool {
+    let Some(host) = host else {
+        return false;
+    };
+    let name = match host.rsplit_once(':') {
+        Some((name, host_port)) if !host_port.ends_with(']') => {
+            if host_port.parse() != Ok(port) {
+                return false;
+            }
+            name
+        }
+        _ if port == 80 => host,
+        _ => return false,
+    };
+    matches!(
+        name.to_ascii_lowercase().as_str(),
+        "127.0.0.1" | "localhost" | "[::1]"
+    )
+}
+
+fn query_values(request: &Request, name: &str) -> Vec<String> {
+    request
+        .query
+        .iter()
+        .filter(|(key, _)| key == name)
+        .map(|(_, value)| value.clone())
+        .collect()
+}
+
+/// A boolean query parameter; a bare `?name` counts as true
+fn query_flag(request: &Request, name: &str) -> Result<bool, Response> {
+    match query_values(request, name).pop().as_deref() {
+        None | Some("false" | "0") => Ok(false),
+        Some("" | "true" | "1") => Ok(true),
+        Some(value) => Err(Response::error(
+            400,
+            format!("invalid value for {}: {}", name, value),
+        )),
+    }
+}
+
+fn parse_query(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect()
+}
+
+/// Decode `%XX` escapes and `+` as space; invalid escapes are kept as-is
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => match (
+                bytes.get(i + 1).and_then(|&b| hex_digit(b)),
+                bytes.get(i + 2).and_then(|&b| hex_digit(b)),
+            ) {
+                (Some(high), Some(low)) => {
+                    decoded.push(high << 4 | low);
+                    i += 2;
+                }
+                _ => decoded.push(b'%'),
+            },
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn hex_digit(byte: u8) -> Option<u8> {
+    (byte as char).to_digit(16).map(|digit| digit as u8)
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        403 => "Forbidden",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        413 => "Payload Too Large",
+        431 => "Request Header Fields Too Large",
+        _ => "Internal Server Error",
+    }
+}
@@ -0,0 +1,172 @@
+//! Model Context Protocol on rmcp's server, over stdio. Only the tools
+//! capability is offered; the tools wrap `diff_tools`.
+
+use super::diff_tools::{self, MinimizeArgs, ReviewCommentsArgs, ReviewStatusArgs, SaveArgs};
+use agpod_core::DiffConfig;
+use rmcp::{
+    handler::server::{router::tool::ToolRouter, wrapper::Parameters},
+    model::{CallToolResult, Content, Implementation, ServerCapabilities, ServerInfo},
+    tool, tool_handler, tool_router, ErrorData, ServerHandler, ServiceExt,
+};
+use serde_json::Value;
+use std::io;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+#[derive(Clone)]
+struct DiffServer {
+    config: DiffConfig,
+    tool_router: ToolRouter<Self>,
+}
+
+impl DiffServer {
+    fn new(config: DiffConfig) -> Self {
+        Self {
+            config,
+            tool_router: Self::tool_router(),
+        }
+    }
+}
+
+/// Answer requests from `reader` on `writer` until the client disconnects
+pub async fn serve<R, W>(reader: R, writer: W, config: &DiffConfig) -> io::Result<()>
+where
+    R: AsyncRead + Send + Unpin + 'static,
+    W: AsyncWrite + Send + Unpin + 'static,
+{
+    let server = DiffServer::new(config.clone())
+        .serve((reader, writer))
+        .await
+        .map_err(io::Error::other)?;
+    server.waiting().await.map_err(io::Error::other)?;
+    Ok(())
+}
+
+/// Failures of the tool itself are reported in the result with `isError`
+/// so the model can see them, not as protocol errors
+fn tool_result(outcome: Result<String, String>) -> Result<CallToolResult, ErrorData> {
+    Ok(match outcome {
+        Ok(text) => CallToolResult::success(vec![Content::text(text)]),
+        Err(message) => CallToolResult::error(vec![Content::text(message)]),
+    })
+}
+
+fn json_text(outcome: Result<Value, String>) -> Result<String, String> {
+    outcome.map(|value| value.to_string())
+}
+
+#[tool_router]
+impl DiffServer {
+    #[tool(
+        description = "Minimize a git diff for LLM context: summarize large, deleted and excluded files and squash blank lines"
+    )]
+    async fn minimize_diff(
+        &self,
+        Parameters(args): Parameters<MinimizeArgs>,
+    ) -> Result<CallToolResult, ErrorData> {
+        tool_result(diff_tools::minimize(args, &self.config))
+    }
+
+    #[tool(
+        description = "Save one chunk per file plus a REVIEW.md that tracks review status across runs"
+    )]
+    async fn save_diff_chunks(
+        &self,
+        Parameters(args): Parameters<SaveArgs>,
+    ) -> Result<CallToolResult, ErrorData> {
+        tool_result(json_text(diff_tools::save(args, &self.config)))
+    }
+
+    #[tool(description = "List the files in a saved REVIEW.md with their review status")]
+    async fn get_review_status(
+        &self,
+        Parameters(args): Parameters<ReviewStatusArgs>,
+    ) -> Result<CallToolResult, ErrorData> {
+        tool_result(json_text(diff_tools::review_status(args, &self.config)))
+    }
+
+    #[tool(description = "List the files with review comments in a saved REVIEW.md")]
+    async fn get_review_comments(
+        &self,
+        Parameters(args): Parameters<ReviewCommentsArgs>,
+    ) -> Result<CallToolResult, ErrorData> {
+        tool_result(json_text(diff_tools::review_comments(args, &self.config)))
+    }
+}
+
+#[tool_handler(router = self.tool_router)]
+impl ServerHandler for DiffServer {
+    fn get_info(&self) -> ServerInfo {
+        ServerInfo::new(ServerCapabilities::builder().enable_tools().build())
+            .with_server_info(Implementation::new("agpod", env!("CARGO_PKG_VERSION")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tokio::io::{duplex, split, AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    const DIFF: &str = "diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1 +1 @@
+-fn old() {}
++fn new() {}
+";
+
+    #[tokio::test]
+    async fn test_serve_round_trips_requests() {
+        let requests = [
+            json!({"jsonrpc": "2.0", "id": 1, "method": "initialize",
+                "params": {"protocolVersion": "2025-03-26", "capabilities": {},
+                    "clientInfo": {"name": "test", "version": "0"}}}),
+            json!({"jsonrpc": "2.0", "method": "notifications/initialized"}),
+            json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"}),
+            json!({"jsonrpc": "2.0", "id": 3, "method": "tools/call",
+                "params": {"name": "minimize_diff", "arguments": {"diff": DIFF}}}),
+        ];
+        let (client, server) = duplex(64 * 1024);
+        let (server_reader, server_writer) = split(server);
+        let (client_reader, mut client_writer) = split(client);
+        let config = DiffConfig::default();
+
+        // one request at a time, so each response line answers the
+        // request just sent
+        let client = async {
+            let mut lines = BufReader::new(client_reader).lines();
+            let mut responses = Vec::new();
+            for request in &requests {
+                let line = format!("{}\n", request);
+                client_writer.write_all(line.as_bytes()).await.unwrap();
+                if request.get("id").is_some() {
+                    let line = lines.next_line().await.unwrap().unwrap();
+                    responses.push(serde_json::from_str::<Value>(&line).unwrap());
+                }
+            }
+            client_writer.shutdown().await.unwrap();
+            responses
+        };
+        let (served, responses) =
+            tokio::join!(serve(server_reader, server_writer, &config), client);
+        served.unwrap();
+
+        assert_eq!(responses[0]["id"], 1);
+        assert_eq!(responses[0]["result"]["serverInfo"]["name"], "agpod");
+        assert_eq!(responses[1]["id"], 2);
+        let minimize = responses[1]["result"]["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|tool| tool["name"] == "minimize_diff")
+            .unwrap();
+        assert_eq!(minimize["inputSchema"]["required"], json!(["diff"]));
+        assert_eq!(responses[2]["id"], 3);
+        assert_eq!(responses[2]["result"]["isError"], false);
+        let text = responses[2]["result"]["content"][0]["text"]
+            .as_str()
+            .unwrap();
+        assert!(text.contains("+fn new() {}"));
+    }
+}
@@ -0,0 +1,59 @@
+//! `agpod serve`: agpod operations for agent frameworks and editors.
+//!
+//! MCP is built with the `serve` feature on rmcp, the SDK `agpod-mcp`
+//! already uses. HTTP is built with the `server` feature; its layer is
+//! small and hand-written so it only adds tokio's `net`.
+
+mod diff_tools;
+#[cfg(feature = "server")]
+mod http;
+mod mcp;
+
+use agpod_core::DiffConfig;
+use clap::{ArgGroup, Args};
+#[cfg(feature = "server")]
+use std::net::SocketAddr;
+
+#[derive(Args)]
+#[command(group(ArgGroup::new("transport").required(true)))]
+pub struct ServeArgs {
+    /// Speak the Model Context Protocol over stdin/stdout
+    #[arg(long, group = "transport")]
+    mcp: bool,
+
+    /// Serve a JSON HTTP API on ADDR
+    #[cfg(feature = "server")]
+    #[arg(long, group = "transport", value_name = "ADDR", num_args = 0..=1, default_missing_value = "127.0.0.1:7345")]
+    http: Option<SocketAddr>,
+
+    /// Require `Authorization: Bearer TOKEN` on every HTTP request; needed
+    /// to bind a non-loopback address
+    #[cfg(feature = "server")]
+    #[arg(
+        long,
+        env = "AGPOD_SERVE_TOKEN",
+        requires = "http",
+        hide_env_values = true
+    )]
+    token: Option<String>,
+}
+
+/// Serve until the client disconnects
+pub async fn run(args: ServeArgs, config: DiffConfig) -> std::io::Result<()> {
+    if args.mcp {
+        mcp::serve(tokio::io::stdin(), tokio::io::stdout(), &config).await?;
+    }
+    #[cfg(feature = "server")]
+    if let Some(addr) = args.http {
+        if !addr.ip().is_loopback() && args.token.is_none() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("refusing to serve {} without --token", addr),
+            ));
+        }
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        eprintln!("agpod: listening on http://{}", listener.local_addr()?);
+        http::serve(listener, args.token, config).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,890 @@
+//! End-to-end tests running the `agpod` binary.
+
+mod support;
+
+use predicates::prelude::*;
+use std::fs;
+use support::{TestEnv, TestRepo};
+
+const TWO_FILES_DIFF: &str = include_str!("fixtures/two_files.diff");
+
+#[test]
+fn diff_minimizes_stdin() {
+    let env = TestEnv::new();
+    env.agpod(env.home())
+        .arg("diff")
+        .write_stdin(TWO_FILES_DIFF)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "diff --git a/src/lib.rs b/src/lib.rs",
+        ))
+        .stdout(predicate::str::contains("+    a.saturating_add(b)"))
+        .stdout(predicate::str::contains("Deleted file: README.md"))
+        .stdout(predicate::str::contains("Old readme").not());
+}
+
+#[test]
+fn diff_reads_non_utf8_stdin_lossily() {
+    let env = TestEnv::new();
+    // "caf\xe9" is Latin-1 for "café"
+    let mut diff = b"diff --git a/menu.txt b/menu.txt\n\
+index 1111111..2222222 100644\n\
+--- a/menu.txt\n\
++++ b/menu.txt\n\
+@@ -1 +1 @@\n\
+-tea\n\
++caf"
+        .to_vec();
+    diff.extend_from_slice(b"\xe9\n");
+
+    env.agpod(env.home())
+        .arg("diff")
+        .write_stdin(diff)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("diff --git a/menu.txt b/menu.txt"))
+        .stdout(predicate::str::contains("+caf\u{fffd}\n"));
+}
+
+#[test]
+fn diff_ignore_comments_notes_suppressed_lines() {
+    let env = TestEnv::new();
+    env.agpod(env.home())
+        .args(["diff", "--ignore-comments"])
+        .write_stdin(TWO_FILES_DIFF)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "diff --git a/src/lib.rs b/src/lib.rs\n# suppressed: 1 comment line\n",
+        ))
+        .stdout(predicate::str::contains("overflow panics").not())
+        .stdout(predicate::str::contains("+    a.saturating_add(b)"));
+}
+
+#[test]
+fn diff_rejects_git_log_input() {
+    let env = TestEnv::new();
+    env.agpod(env.home())
+        .arg("diff")
+        .write_stdin("commit 0123456789abcdef0123456789abcdef01234567\nAuthor: agpod\n")
+        .assert()
+        .code(5)
+        .stderr(predicate::str::starts_with(
+            "Error: input is not a diff: it looks like `git log` output",
+        ));
+}
+
+#[test]
+fn diff_save_writes_chunks_and_review() {
+    let env = TestEnv::new();
+    let work = tempfile::tempdir().unwrap();
+
+    env.agpod(work.path())
+        .args(["diff", "--save", "--save-path", "review"])
+        .write_stdin(TWO_FILES_DIFF)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("generated: review/"))
+        .stdout(predicate::str::contains("REVIEW.md: "));
+
+    let out = work.path().join("review");
+    assert!(out.join("chunk_aa.diff").is_file());
+    assert!(out.join("chunk_ab.diff").is_file());
+
+    let review = fs::read_to_string(out.join("REVIEW.md")).unwrap();
+    assert!(review.starts_with("# Code Review Tracking\n"));
+    assert!(review.contains("## Guidelines"));
+    for (file, chunk) in [
+        ("src/lib.rs", "chunk_aa.diff"),
+        ("README.md", "chunk_ab.diff"),
+    ] {
+        let section = format!("## {file}\n- meta:hash: ");
+        assert!(review.contains(&section), "missing section for {file}");
+        assert!(review.contains(&format!("- meta:diff_chunk: {chunk}\n")));
+    }
+    assert_eq!(review.matches("- meta:status: pending").count(), 2);
+}
+
+#[test]
+fn deterministic_diff_save_keeps_review_path_relative() {
+    let env = TestEnv::new();
+    let work = tempfile::tempdir().unwrap();
+
+    env.agpod(work.path())
+        .args(["--deterministic", "diff", "--save", "--save-path", "review"])
+        .write_stdin(TWO_FILES_DIFF)
+        .assert()
+        .success()
+        .stdout("generated: review/\nREVIEW.md: review/REVIEW.md\n");
+
+    env.agpod(work.path())
+        .args(["diff", "--save", "--save-path", "review"])
+        .env("AGPOD_DETERMINISTIC", "1")
+        .write_stdin(TWO_FILES_DIFF)
+        .assert()
+        .success()
+        .stdout("generated: review/\nREVIEW.md: review/REVIEW.md\n");
+}
+
+#[test]
+fn progress_json_reports_save_progress_on_stderr() {
+    let env = TestEnv::new();
+    let work = tempfile::tempdir().unwrap();
+
+    env.agpod(work.path())
+        .args(["diff", "--save", "--save-path", "review", "--progress-json"])
+        .write_stdin(TWO_FILES_DIFF)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("phase").not())
+        .stderr(
+            "{\"phase\":\"parse\",\"files_done\":2,\"files_total\":2}\n\
+             {\"phase\":\"save\",\"files_done\":1,\"files_total\":2}\n\
+             {\"phase\":\"save\",\"files_done\":2,\"files_total\":2}\n",
+        );
+}
+
+#[test]
+fn timings_breaks_down_phases_on_stderr() {
+    let env = TestEnv::new();
+    let work = tempfile::tempdir().unwrap();
+
+    let output = env
+        .agpod(work.path())
+        .args(["diff", "--save", "--save-path", "review", "--timings"])
+        .write_stdin(TWO_FILES_DIFF)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("timings:").not())
+        .get_output()
+        .stderr
+        .clone();
+    let stderr = String::from_utf8(output).unwrap();
+    assert!(stderr.starts_with("timings:\n"), "{stderr}");
+    for phase in ["config_load", "read_input", "diff_parse", "review_write"] {
+        assert!(stderr.contains(&format!("  {phase} ")), "missing {phase}");
+    }
+    assert!(
+        stderr.contains(" ms (2x)\n"),
+        "chunk writes are summed: {stderr}"
+    );
+    assert!(stderr
+        .trim_end()
+        .lines()
+        .last()
+        .unwrap()
+        .starts_with("  total "));
+
+    // a failing command still reports where its time went
+    env.agpod(work.path())
+        .args(["diff", "--timings"])
+        .write_stdin("commit abc\n")
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("Error: "))
+        .stderr(predicate::str::contains("\ntimings:\n"))
+        .stderr(predicate::str::contains("  read_input "));
+
+    // so does one whose config cannot be loaded
+    env.write_config("[diff]\noutput_dir = \"~nobody/review\"\n");
+    env.agpod(work.path())
+        .args(["diff", "--timings"])
+        .write_stdin(TWO_FILES_DIFF)
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("Error: "))
+        .stderr(predicate::str::contains("\ntimings:\n"));
+}
+
+#[test]
+fn diff_save_uses_output_dir_from_config() {
+    let env = TestEnv::new();
+    env.write_config("[diff]\noutput_dir = \"from-config\"\n");
+    let work = tempfile::tempdir().unwrap();
+
+    env.agpod(work.path())
+        .args(["diff", "--save"])
+        .write_stdin(TWO_FILES_DIFF)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("generated: from-config/"));
+
+    assert!(work.path().join("from-config/REVIEW.md").is_file());
+}
+
+#[test]
+fn diff_exclude_file_and_patterns() {
+    let env = TestEnv::new();
+    env.write_config("[diff]\nexclude_file = \".agpodignore\"\n");
+    let work = tempfile::tempdir().unwrap();
+    fs::write(work.path().join(".agpodignore"), "*.md\n").unwrap();
+
+    env.agpod(work.path())
+        .arg("diff")
+        .write_stdin(TWO_FILES_DIFF)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Excluded file: README.md"))
+        .stdout(predicate::str::contains("+    a.saturating_add(b)"));
+
+    env.agpod(work.path())
+        .args(["diff", "--exclude", "src/", "--exclude", "!README.md"])
+        .write_stdin(TWO_FILES_DIFF)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Excluded file: src/lib.rs"))
+        .stdout(predicate::str::contains("Deleted file: README.md"));
+}
+
+#[test]
+fn diff_save_truncates_chunks_over_token_limit() {
+    let env = TestEnv::new();
+    env.write_config("[diff]\nmax_tokens_per_chunk = 10\n");
+    let work = tempfile::tempdir().unwrap();
+
+    env.agpod(work.path())
+        .args(["diff", "--save", "--save-path", "review"])
+        .write_stdin(TWO_FILES_DIFF)
+        .assert()
+        .success()
+        .stderr("truncated 2 of 2 chunks over max_tokens_per_chunk (10)\n");
+    let review = fs::read_to_string(work.path().join("review/REVIEW.md")).unwrap();
+    assert_eq!(review.matches("- meta:truncated: true").count(), 2);
+
+    env.agpod(work.path())
+        .args([
+            "diff",
+            "--save",
+            "--save-path",
+            "review",
+            "--no-truncate-chunks",
+        ])
+        .write_stdin(TWO_FILES_DIFF)
+        .assert()
+        .success()
+        .stderr("");
+    let chunk = fs::read_to_string(work.path().join("review/chunk_aa.diff")).unwrap();
+    assert!(chunk.contains("a.saturating_add(b)"));
+}
+
+#[test]
+fn diff_split_writes_bundles() {
+    let env = TestEnv::new();
+    let work = tempfile::tempdir().unwrap();
+
+    env.agpod(work.path())
+        .args(["diff", "--split", "2", "--save-path", "review"])
+        .write_stdin(TWO_FILES_DIFF)
+        .assert()
+        .success()
+        .stdout("bundle: review/bundle_1.md\nbundle: review/bundle_2.md\n");
+
+    let first = fs::read_to_string(work.path().join("review/bundle_1.md")).unwrap();
+    assert!(first.contains("## Files\n- src/lib.rs\n"));
+
+    env.agpod(work.path())
+        .args(["diff", "--split", "0"])
+        .write_stdin(TWO_FILES_DIFF)
+        .assert()
+        .failure();
+}
+
+#[test]
+fn diff_with_header_describes_repository() {
+    let env = TestEnv::new();
+    let repo = TestRepo::new();
+    repo.checkout_new_branch("feature/header");
+
+    env.agpod(repo.path())
+        .args(["diff", "--with-header", "--context", "fixes #42"])
+        .write_stdin(TWO_FILES_DIFF)
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(
+            "^- repository: .+\n- branch: feature/header @ [0-9a-f]{7,}\n- source: stdin\n- files: 2 changed, \\+2 -3\n- context: fixes #42\n\ndiff --git ",
+        ).unwrap());
+}
+
+#[test]
+fn diff_comments_lists_commented_files() {
+    let env = TestEnv::new();
+    let work = tempfile::tempdir().unwrap();
+
+    env.agpod(work.path())
+        .args(["diff", "--save", "--save-path", "review"])
+        .write_stdin(TWO_FILES_DIFF)
+        .assert()
+        .success();
+
+    // nothing to address yet
+    env.agpod(work.path())
+        .args(["diff", "comments", "--save-path", "review", "--json"])
+        .assert()
+        .success()
+        .stdout("[]\n");
+    env.agpod(work.path())
+        .args(["diff", "comments", "--save-path", "review"])
+        .assert()
+        .success()
+        .stdout("");
+
+    let review_path = work.path().join("review/REVIEW.md");
+    let review = fs::read_to_string(&review_path).unwrap().replacen(
+        "<!-- Review comments go here -->",
+        "Prefer checked_add here.",
+        1,
+    );
+    fs::write(&review_path, review).unwrap();
+
+    let output = env
+        .agpod(work.path())
+        .args(["diff", "comments", "--save-path", "review"])
+        .args(["--json", "--with-diff"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let json = String::from_utf8(output).unwrap();
+    assert!(json.contains("\"file\": \"src/lib.rs\""));
+    assert!(json.contains("\"comments\": \"Prefer checked_add here.\""));
+    assert!(json.contains("\"chunk\": \"chunk_aa.diff\""));
+    assert!(json.contains("a.saturating_add(b)"));
+    assert!(!json.contains("README.md"));
+
+    env.agpod(work.path())
+        .args(["diff", "comments", "--save-path", "review", "--markdown"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            "## src/lib.rs\n- status: pending\n- chunk: chunk_aa.diff\n\nPrefer checked_add here.\n",
+        ));
+}
+
+#[test]
+fn diff_publish_review_prints_payload_or_explains_missing_gh() {
+    let env = TestEnv::new();
+    let work = tempfile::tempdir().unwrap();
+
+    env.agpod(work.path())
+        .args(["diff", "--save", "--save-path", "review"])
+        .write_stdin(TWO_FILES_DIFF)
+        .assert()
+        .success();
+    env.agpod(work.path())
+        .args([
+            "diff",
+            "publish-review",
+            "--pr",
+            "7",
+            "--save-path",
+            "review",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("has review comments"));
+    let output = env
+        .agpod(work.path())
+        .args([
+            "diff",
+            "publish-review",
+            "--pr",
+            "7",
+            "--save-path",
+            "review",
+            "--dry-run",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let payload: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(payload["comments"], serde_json::json!([]));
+
+    let review_path = work.path().join("review/REVIEW.md");
+    let review = fs::read_to_string(&review_path).unwrap().replacen(
+        "<!-- Review comments go here -->",
+        "Prefer checked_add here.",
+        1,
+    );
+    fs::write(&review_path, review).unwrap();
+
+    let output = env
+        .agpod(work.path())
+        .args([
+            "diff",
+            "publish-review",
+            "--pr",
+            "7",
+            "--save-path",
+            "review",
+        ])
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let payload: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(payload["event"], "COMMENT");
+    assert_eq!(payload["body"], "Review status: 2 files (2 pending)");
+    assert_eq!(
+        payload["comments"],
+        serde_json::json!([
+            { "path": "src/lib.rs", "body": "Prefer checked_add here.", "subject_type": "file" }
+        ])
+    );
+
+    let empty_path = tempfile::tempdir().unwrap();
+    env.agpod(work.path())
+        .args([
+            "diff",
+            "publish-review",
+            "--pr",
+            "7",
+            "--save-path",
+            "review",
+        ])
+        .env("PATH", empty_path.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("gh not found"));
+}
+
+#[test]
+fn vcs_path_info_reports_branches() {
+    let env = TestEnv::new();
+    let repo = TestRepo::new();
+    repo.checkout_new_branch("feature/cli-tests");
+    let plain = tempfile::tempdir().unwrap();
+
+    let input = format!("{}\n{}\n", repo.path().display(), plain.path().display());
+    env.agpod(env.home())
+        .arg("vcs-path-info")
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(format!(
+            "{} feature/cli-tests\n{}\n",
+            repo.path().display(),
+            plain.path().display()
+        ));
+
+    env.agpod(env.home())
+        .args(["vcs-path-info", "--filter", "--format", "{branch}"])
+        .write_stdin(format!("{}\n", plain.path().display()))
+        .assert()
+        .success()
+        .stdout("");
+}
+
+#[test]
+fn diff_show_reconstructs_from_git() {
+    let env = TestEnv::new();
+    let repo = TestRepo::new();
+    repo.commit_file("notes.txt", "one\ntwo\nthree\n", "add notes");
+    fs::write(repo.path().join("notes.txt"), "one\nTWO\nthree\n").unwrap();
+
+    env.agpod(repo.path())
+        .args(["diff", "show", "notes.txt", "--from-git"])
+        .assert()
+        .success()
+        .stdout("one\nTWO\nthree\n");
+
+    let out = repo.path().join("before.txt");
+    env.agpod(repo.path())
+        .args(["diff", "show", "notes.txt", "--from-git", "--before", "-o"])
+        .arg(&out)
+        .assert()
+        .success()
+        .stdout("");
+    assert_eq!(fs::read_to_string(out).unwrap(), "one\ntwo\nthree\n");
+
+    env.agpod(env.home())
+        .args(["diff", "show", "missing.rs"])
+        .write_stdin(TWO_FILES_DIFF)
+        .assert()
+        .failure()
+        .stderr("Error: missing.rs is not in the diff\n");
+}
+
+#[test]
+fn diff_review_merge_as_git_merge_driver() {
+    let env = TestEnv::new();
+    let work = tempfile::tempdir().unwrap();
+    let section = |path: &str, status: &str, comments: &str| {
+        format!("## {path}\n- meta:hash: {path}1\n- meta:status: {status}\n\n{comments}\n---\n\n")
+    };
+    let title = "# Code Review Tracking\n\n";
+    let placeholder = "<!-- Review comments go here -->\n";
+    fs::write(
+        work.path().join("base.md"),
+        [
+            title,
+            &section("a.rs", "pending", placeholder),
+            &section("b.rs", "pending", placeholder),
+        ]
+        .concat(),
+    )
+    .unwrap();
+    fs::write(
+        work.path().join("ours.md"),
+        [
+            title,
+            &section("a.rs", "reviewed@2024-05-01", "Ours."),
+            &section("b.rs", "pending", placeholder),
+        ]
+        .concat(),
+    )
+    .unwrap();
+    fs::write(
+        work.path().join("theirs.md"),
+        [
+            title,
+            &section("a.rs", "pending", placeholder),
+            &section("b.rs", "reviewed@2024-05-02", "Theirs."),
+        ]
+        .concat(),
+    )
+    .unwrap();
+
+    env.agpod(work.path())
+        .args([
+            "diff",
+            "review",
+            "merge",
+            "--git-merge-driver",
+            "base.md",
+            "ours.md",
+            "theirs.md",
+        ])
+        .assert()
+        .success()
+        .stdout("");
+    let merged = fs::read_to_string(work.path().join("ours.md")).unwrap();
+    assert_eq!(
+        merged,
+        [
+            title,
+            &section("a.rs", "reviewed@2024-05-01", "Ours."),
+            &section("b.rs", "reviewed@2024-05-02", "Theirs.")
+        ]
+        .concat()
+    );
+
+    env.agpod(work.path())
+        .args(["diff", "review", "merge", "--ours", "ours.md"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn diff_since_ref_diffs_from_merge_base() {
+    let env = TestEnv::new();
+    let repo = TestRepo::new();
+    let base = repo.branch();
+    repo.checkout_new_branch("feature");
+    repo.commit_file("feature.txt", "new\n", "add feature");
+    fs::write(repo.path().join("README.md"), "# changed\n").unwrap();
+
+    env.agpod(repo.path())
+        .args(["diff", "--from-git", "--since-ref", &base])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "diff --git a/feature.txt b/feature.txt",
+        ))
+        .stdout(predicate::str::contains("+# changed"));
+    env.agpod(repo.path())
+        .args(["diff", "--from-git", "--since-ref", &base, "--committed"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "diff --git a/feature.txt b/feature.txt",
+        ))
+        .stdout(predicate::str::contains("README.md").not());
+    env.agpod(repo.path())
+        .args(["diff", "--from-git"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("+# changed"))
+        .stdout(predicate::str::contains("feature.txt").not());
+
+    let typo = format!("{}x", base);
+    env.agpod(repo.path())
+        .args(["diff", "--from-git", "--since-ref", &typo])
+        .assert()
+        .code(4)
+        .stderr(format!(
+            "Error: unknown ref `{typo}`; did you mean: {base}?\n"
+        ));
+    env.agpod(repo.path())
+        .args(["diff", "--since-ref", &base])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--from-git"));
+}
+
+#[test]
+fn schema_describes_commands_and_json_outputs() {
+    let env = TestEnv::new();
+    let output = env
+        .agpod(env.home())
+        .args(["schema", "--json"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let schema: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(schema["name"], "agpod");
+
+    let commands = schema["commands"].as_array().unwrap();
+    let command = |path: &str| {
+        commands
+            .iter()
+            .find(|command| command["path"] == path)
+            .unwrap_or_else(|| panic!("missing command {path}"))
+    };
+    let diff = command("diff");
+    assert_eq!(
+        diff["subcommands"],
+        serde_json::json!(["comments", "publish-review", "review", "show"])
+    );
+    let exclude = diff["args"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|arg| arg["long"] == "exclude")
+        .unwrap();
+    assert_eq!(exclude["type"], "string");
+    assert_eq!(exclude["multiple"], true);
+    let split = diff["args"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|arg| arg["long"] == "split")
+        .unwrap();
+    assert_eq!(split["type"], "integer");
+    assert_eq!(split["value_name"], "N");
+    // hidden flags stay out of the manifest
+    assert!(!output
+        .windows(b"deterministic".len())
+        .any(|window| window == b"deterministic"));
+    assert_eq!(command("diff show")["args"][0]["name"], "path");
+    let nth = command("vcs-path-info")["args"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|arg| arg["long"] == "nth")
+        .unwrap();
+    assert_eq!(nth["type"], "integer");
+
+    let outputs = schema["outputs"].as_array().unwrap();
+    assert!(outputs
+        .iter()
+        .any(|output| output["command"] == "diff comments" && output["schema"]["type"] == "array"));
+
+    env.agpod(env.home())
+        .arg("schema")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "agpod diff comments - Print files with review comments",
+        ));
+}
+
+#[cfg(feature = "serve")]
+#[test]
+fn serve_mcp_lists_and_calls_tools() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::process::Stdio;
+
+    let env = TestEnv::new();
+    let requests = [
+        serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "initialize",
+            "params": {"protocolVersion": "2025-03-26", "capabilities": {},
+                "clientInfo": {"name": "test", "version": "0"}}}),
+        serde_json::json!({"jsonrpc": "2.0", "method": "notifications/initialized"}),
+        serde_json::json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"}),
+        serde_json::json!({"jsonrpc": "2.0", "id": 3, "method": "tools/call",
+            "params": {"name": "minimize_diff", "arguments": {"diff": TWO_FILES_DIFF}}}),
+        serde_json::json!({"jsonrpc": "2.0", "id": 4, "method": "tools/call",
+            "params": {"name": "minimize_diff", "arguments": {"diff": "commit abc\n"}}}),
+        serde_json::json!({"jsonrpc": "2.0", "id": 5, "method": "tools/call",
+            "params": {"name": "minimize_diff", "arguments": {}}}),
+    ];
+    let mut server = env
+        .agpod_process(env.home())
+        .args(["serve", "--mcp"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = server.stdin.take().unwrap();
+    let mut stdout = BufReader::new(server.stdout.take().unwrap());
+
+    // requests are answered concurrently, so wait for each response
+    // before sending the next request
+    let mut responses = Vec::new();
+    for request in &requests {
+        writeln!(stdin, "{request}").unwrap();
+        if request.get("id").is_some() {
+            let mut line = String::new();
+            stdout.read_line(&mut line).unwrap();
+            responses.push(serde_json::from_str::<serde_json::Value>(&line).unwrap());
+        }
+    }
+    drop(stdin);
+    assert!(server.wait().unwrap().success());
+
+    // the notification gets no response
+    assert_eq!(responses.len(), 5);
+    assert_eq!(responses[0]["result"]["serverInfo"]["name"], "agpod");
+    let tools: Vec<&str> = responses[1]["result"]["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|tool| tool["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        tools,
+        [
+            "get_review_comments",
+            "get_review_status",
+            "minimize_diff",
+            "save_diff_chunks"
+        ]
+    );
+    assert_eq!(responses[2]["result"]["isError"], false);
+    let text = responses[2]["result"]["content"][0]["text"]
+        .as_str()
+        .unwrap();
+    assert!(text.contains("Deleted file: README.md"));
+    assert_eq!(responses[3]["result"]["isError"], true);
+    // missing arguments are a protocol error, not a tool failure
+    assert_eq!(responses[4]["error"]["code"], -32602);
+}
+
+#[cfg(feature = "server")]
+#[test]
+fn serve_http_minimizes_and_requires_token() {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpStream;
+    use std::process::Stdio;
+
+    let env = TestEnv::new();
+    let mut server = env
+        .agpod_process(env.home())
+        .args(["serve", "--http", "127.0.0.1:0", "--token", "secret"])
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut banner = String::new();
+    BufReader::new(server.stderr.take().unwrap())
+        .read_line(&mut banner)
+        .unwrap();
+    let addr = banner
+        .trim()
+        .strip_prefix("agpod: listening on http://")
+        .unwrap_or_else(|| panic!("unexpected banner: {banner}"))
+        .to_string();
+
+    let request = |head: &str, body: &str| {
+        let mut stream = TcpStream::connect(&addr).unwrap();
+        write!(
+            stream,
+            "{head}\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (status, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = status.split(' ').nth(1).unwrap().to_string();
+        (
+            status,
+            serde_json::from_str::<serde_json::Value>(body).unwrap(),
+        )
+    };
+
+    let (status, _) = request("POST /diff/minimize HTTP/1.1", TWO_FILES_DIFF);
+    assert_eq!(status, "401");
+    let (status, _) = request(
+        "POST /diff/minimize HTTP/1.1\r\nAuthorization: Bearer secreT",
+        TWO_FILES_DIFF,
+    );
+    assert_eq!(status, "401");
+
+    let (status, body) = request(
+        "POST /diff/minimize?exclude=README.md HTTP/1.1\r\nAuthorization: Bearer secret",
+        TWO_FILES_DIFF,
+    );
+    assert_eq!(status, "200");
+    let output = body["output"].as_str().unwrap();
+    assert!(output.contains("+    a.saturating_add(b)"));
+    assert!(!output.contains("Deleted file: README.md"));
+
+    let (status, body) = request(
+        "GET /diff/review?output_dir=missing HTTP/1.1\r\nAuthorization: Bearer secret",
+        "",
+    );
+    assert_eq!(status, "500");
+    assert!(body["error"].as_str().unwrap().contains("REVIEW.md"));
+
+    let (status, _) = request("GET /nope HTTP/1.1\r\nAuthorization: Bearer secret", "");
+    assert_eq!(status, "404");
+
+    server.kill().unwrap();
+    server.wait().unwrap();
+}
+
+#[cfg(feature = "server")]
+#[test]
+fn serve_http_without_token_requires_loopback_host() {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpStream;
+    use std::process::Stdio;
+
+    let env = TestEnv::new();
+    let mut server = env
+        .agpod_process(env.home())
+        .args(["serve", "--http", "127.0.0.1:0"])
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut banner = String::new();
+    BufReader::new(server.stderr.take().unwrap())
+        .read_line(&mut banner)
+        .unwrap();
+    let addr = banner
+        .trim()
+        .strip_prefix("agpod: listening on http://")
+        .unwrap_or_else(|| panic!("unexpected banner: {banner}"))
+        .to_string();
+    let port = addr.rsplit_once(':').unwrap().1.to_string();
+
+    let status = |host: &str| {
+        let mut stream = TcpStream::connect(&addr).unwrap();
+        write!(
+            stream,
+            "POST /diff/minimize HTTP/1.1\r\nHost: {host}\r\nContent-Length: {}\r\n\r\n{}",
+            TWO_FILES_DIFF.len(),
+            TWO_FILES_DIFF
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response.split(' ').nth(1).unwrap().to_string()
+    };
+
+    assert_eq!(status(&format!("127.0.0.1:{port}")), "200");
+    assert_eq!(status(&format!("localhost:{port}")), "200");
+    assert_eq!(status(&format!("rebound.example:{port}")), "403");
+    assert_eq!(status("localhost:1"), "403");
+
+    server.kill().unwrap();
+    server.wait().unwrap();
+}
@@ -0,0 +1,117 @@
+//! Shared helpers for the CLI integration tests.
+
+#![allow(dead_code)]
+
+use assert_cmd::Command;
+use git2::{Repository, Signature};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// An isolated environment for running the `agpod` binary: HOME, XDG config
+/// and data directories all point into a temp dir so user config and logs
+/// never leak into a test.
+pub struct TestEnv {
+    home: TempDir,
+}
+
+impl TestEnv {
+    pub fn new() -> Self {
+        Self {
+            home: tempfile::tempdir().unwrap(),
+        }
+    }
+
+    pub fn home(&self) -> &Path {
+        self.home.path()
+    }
+
+    pub fn config_home(&self) -> PathBuf {
+        self.home().join(".config")
+    }
+
+    /// Write `$XDG_CONFIG_HOME/agpod/config.toml`.
+    pub fn write_config(&self, contents: &str) {
+        let dir = self.config_home().join("agpod");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("config.toml"), contents).unwrap();
+    }
+
+    /// A command for the `agpod` binary running inside `cwd`.
+    pub fn agpod(&self, cwd: &Path) -> Command {
+        Command::from_std(self.agpod_process(cwd))
+    }
+
+    /// Like `agpod`, as a plain std command for long-running processes.
+    pub fn agpod_process(&self, cwd: &Path) -> std::process::Command {
+        let mut cmd = std::process::Command::new(assert_cmd::cargo::cargo_bin!("agpod"));
+        cmd.current_dir(cwd)
+            .env("HOME", self.home())
+            .env("XDG_CONFIG_HOME", self.config_home())
+            .env("XDG_DATA_HOME", self.home().join(".local/share"))
+            .env("NO_COLOR", "1");
+        cmd
+    }
+}
+
+/// A throwaway git repository with an initial commit on its default branch.
+pub struct TestRepo {
+    dir: TempDir,
+    repo: Repository,
+}
+
+impl TestRepo {
+    pub fn new() -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let test_repo = Self { dir, repo };
+        test_repo.commit_file("README.md", "# test\n", "initial commit");
+        test_repo
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    pub fn branch(&self) -> String {
+        self.repo.head().unwrap().shorthand().unwrap().to_owned()
+    }
+
+    /// Write `name` with `contents` and commit it on HEAD.
+    pub fn commit_file(&self, name: &str, contents: &str, message: &str) {
+        let path = self.path().join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(&path, contents).unwrap();
+
+        let mut index = self.repo.index().unwrap();
+        index.add_path(Path::new(name)).unwrap();
+        index.write().unwrap();
+        let tree = self.repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("agpod", "agpod@localhost").unwrap();
+        let parent = self
+            .repo
+            .head()
+            .ok()
+            .and_then(|head| head.peel_to_commit().ok());
+        let parents: Vec<_> = parent.iter().collect();
+        self.repo
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                message,
+                &tree,
+                &parents,
+            )
+            .unwrap();
+    }
+
+    /// Check out a new branch at HEAD.
+    pub fn checkout_new_branch(&self, name: &str) {
+        let head = self.repo.head().unwrap().peel_to_commit().unwrap();
+        self.repo.branch(name, &head, false).unwrap();
+        self.repo.set_head(&format!("refs/heads/{name}")).unwrap();
+    }
+}